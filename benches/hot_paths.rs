@@ -0,0 +1,54 @@
+//! Benchmarks for the work done on every command line: `cargo bench`.
+
+use std::{hint::black_box, path::Path};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use mash::{Shell, bench};
+use rustyline::{Editor, history::FileHistory};
+
+const LINES: &[(&str, &str)] = &[
+    ("simple", "ls -la /tmp"),
+    ("quoted", r#"printf '%s\n' "$HOME/some dir" 'single quoted' a\ b"#),
+    ("compound", "for f in a b c; do if test -n \"$f\"; then echo \"$f\"; fi; done"),
+    ("function", "greet() { case $1 in hi|hello) echo hello;; *) echo \"${1:-nobody}\";; esac; }"),
+];
+
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tokenize");
+    for (name, line) in LINES {
+        group.bench_function(*name, |b| b.iter(|| bench::tokenize(black_box(line))));
+    }
+    group.finish();
+    let mut group = c.benchmark_group("parse");
+    for (name, line) in LINES {
+        group.bench_function(*name, |b| b.iter(|| bench::parse(black_box(line))));
+    }
+    group.finish();
+}
+
+fn prompt(c: &mut Criterion) {
+    let mut shell = Shell::new();
+    c.bench_function("prompt", |b| b.iter(|| bench::render_prompt(&mut shell)));
+}
+
+fn path_lookup(c: &mut Criterion) {
+    let path = std::env::var("PATH").unwrap_or_else(|_| String::from("/usr/local/bin:/usr/bin:/bin"));
+    let mut group = c.benchmark_group("path lookup");
+    group.bench_function("found", |b| b.iter(|| bench::find_program(black_box("sh"), Some(&path), Path::new("/"))));
+    group.bench_function("missing", |b| b.iter(|| bench::find_program(black_box("no-such-command"), Some(&path), Path::new("/"))));
+    group.finish();
+}
+
+fn history(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("history");
+    let mut rl = Editor::<(), FileHistory>::new().unwrap();
+    let mut n = 0u64;
+    c.bench_function("history append", |b| b.iter(|| {
+        n += 1;
+        bench::append_history(&mut rl, &file, &format!("echo {}", n));
+    }));
+}
+
+criterion_group!(benches, parsing, prompt, path_lookup, history);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+//! Feed arbitrary input to the parser, which must report a syntax error
+//! rather than panic: `cargo +nightly fuzz run parse`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let _ = mash::check_syntax(source);
+    }
+});
@@ -0,0 +1,118 @@
+//! Aliases: a command whose first word is an alias runs with that word
+//! replaced by the alias's text, which may hold several words or commands.
+//! They come from the `[aliases]` table of the configuration file and from
+//! `alias`, typically run by the rc file.
+
+use crate::{Builtin, Io, Shell, ShellError, exec::quote, parser::{self, Command}};
+
+impl Shell {
+    /// Run `command` with its alias expanded, returning false if its first
+    /// word is not an alias. The first word must be unquoted, and an alias
+    /// is not expanded again within its own expansion, so `alias ls='ls -F'`
+    /// works.
+    pub(crate) fn run_alias(&mut self, command: &Command) -> bool {
+        let Some(word) = command.words.first().filter(|w| !w.contains(['\'', '"', '\\', '$'])) else {
+            return false;
+        };
+        if self.expanding_aliases.contains(word) {
+            return false;
+        }
+        let Some(value) = self.aliases.get(word).cloned() else {
+            return false;
+        };
+        let mut expanded = command.clone();
+        expanded.words[0] = value;
+        match parser::parse_from(&expanded.to_string(), command.line) {
+            Ok(list) => {
+                self.expanding_aliases.push(word.clone());
+                self.run_list(&list);
+                self.expanding_aliases.pop();
+            }
+            Err(e) => self.report(e.into()),
+        }
+        true
+    }
+}
+
+/// Whether `name` can be an alias: a word the lexer keeps whole and that is
+/// not quoted or expanded.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || "=/$'\"\\`;|&()<>".contains(c))
+}
+
+pub(crate) struct Alias;
+
+impl Builtin for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "alias [name[=value] ...]"
+    }
+
+    /// Define the aliases given with a value and print the others, or all
+    /// of them without arguments.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.is_empty() {
+            let mut all: Vec<_> = shell.aliases.iter().collect();
+            all.sort();
+            for (name, value) in all {
+                let _ = writeln!(io.stdout, "alias {}={}", name, quote(value));
+            }
+            return Ok(0);
+        }
+        let mut status = 0;
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, _)) if !valid_name(name) => {
+                    let _ = writeln!(io.stderr, "alias: `{}': invalid alias name", name);
+                    status = 1;
+                }
+                Some((name, value)) => {
+                    shell.aliases.insert(name.to_string(), value.to_string());
+                }
+                None => match shell.aliases.get(arg) {
+                    Some(value) => {
+                        let _ = writeln!(io.stdout, "alias {}={}", arg, quote(value));
+                    }
+                    None => {
+                        let _ = writeln!(io.stderr, "alias: {}: not found", arg);
+                        status = 1;
+                    }
+                },
+            }
+        }
+        Ok(status)
+    }
+}
+
+pub(crate) struct Unalias;
+
+impl Builtin for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "unalias [-a] name ..."
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.first().is_some_and(|arg| arg == "-a") {
+            shell.aliases.clear();
+            return Ok(0);
+        }
+        if args.is_empty() {
+            return Err(ShellError::Usage(String::from("unalias: alias name required")));
+        }
+        let mut status = 0;
+        for name in args {
+            if shell.aliases.remove(name).is_none() {
+                let _ = writeln!(io.stderr, "unalias: {}: not found", name);
+                status = 1;
+            }
+        }
+        Ok(status)
+    }
+}
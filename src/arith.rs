@@ -0,0 +1,379 @@
+//! Arithmetic: `$((expression))` expands to the value of the expression, and
+//! the command `((expression))` succeeds if it is not zero.
+//!
+//! Values are 64-bit integers that wrap around. Numbers are decimal, octal
+//! with a leading `0`, hexadecimal with `0x` or `base#digits` for bases up to
+//! 36. The operators are C's, with `**` for powers, and bind as in bash. A
+//! name stands for the value of that variable, 0 if it is unset or empty and
+//! evaluated in turn if it holds an expression; `=`, `+=` and the like, `++`
+//! and `--` assign to it.
+
+use crate::{Shell, ShellError};
+
+/// How deep variables holding expressions may refer to one another.
+const MAX_DEPTH: usize = 64;
+
+/// Binary operators from the loosest binding to the tightest, above `**`.
+const LEVELS: &[&[&str]] = &[&["||"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="], &["<", "<=", ">", ">="], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+/// Operators, longest first so that they are matched whole.
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/",
+    "%", "<", ">", "=", "!", "~", "&", "^", "|", "?", ":", "(", ")", ",",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    /// `name = value`, or with an operator, as in `name += value`.
+    Assign(String, Option<&'static str>, Box<Expr>),
+    /// `++name` or `name--`: the change and whether the new value is the
+    /// result.
+    Step(String, i64, bool),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '#').unwrap_or(rest.len());
+            tokens.push(Token::Number(number(&rest[..len])?));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else {
+            let op = OPERATORS.iter().find(|op| rest.starts_with(**op)).ok_or_else(|| format!("syntax error: invalid character `{}'", c))?;
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The value of a number as written in an expression.
+fn number(text: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid number `{}'", text);
+    let (base, digits) = match text.split_once('#') {
+        Some((base, digits)) => (base.parse().ok().filter(|b| (2..=36).contains(b)).ok_or_else(invalid)?, digits),
+        None if text.starts_with("0x") || text.starts_with("0X") => (16, &text[2..]),
+        None if text.len() > 1 && text.starts_with('0') => (8, &text[1..]),
+        None => (10, text),
+    };
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+    digits.chars().try_fold(0i64, |value, c| {
+        let digit = c.to_digit(base).ok_or_else(invalid)?;
+        Ok(value.wrapping_mul(base as i64).wrapping_add(digit as i64))
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// Take the next token if it is one of `ops`.
+    fn take_op(&mut self, ops: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        self.take_op(&[op]).map(|_| ()).ok_or_else(|| self.unexpected())
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(Token::Number(n)) => format!("syntax error: unexpected `{}'", n),
+            Some(Token::Name(name)) => format!("syntax error: unexpected `{}'", name),
+            Some(Token::Op(op)) => format!("syntax error: unexpected `{}'", op),
+            None => String::from("syntax error: operand expected"),
+        }
+    }
+
+    fn comma(&mut self) -> Result<Expr, String> {
+        let mut expr = self.assignment()?;
+        while self.take_op(&[","]).is_some() {
+            expr = Expr::Comma(Box::new(expr), Box::new(self.assignment()?));
+        }
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> Result<Expr, String> {
+        if let (Some(Token::Name(name)), Some(Token::Op(op))) = (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)) {
+            if op.ends_with('=') && !matches!(*op, "==" | "!=" | "<=" | ">=") {
+                let (name, op) = (name.clone(), op.strip_suffix('=').filter(|op| !op.is_empty()));
+                // the operator without its `=`, as a binary operator
+                let op = op.map(|op| *OPERATORS.iter().find(|o| **o == op).unwrap());
+                self.pos += 2;
+                return Ok(Expr::Assign(name, op, Box::new(self.assignment()?)));
+            }
+        }
+        self.conditional()
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if self.take_op(&["?"]).is_none() {
+            return Ok(condition);
+        }
+        let then = self.comma()?;
+        self.expect(":")?;
+        let otherwise = self.conditional()?;
+        Ok(Expr::Cond(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.power();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.take_op(ops) {
+            left = Expr::Binary(op, Box::new(left), Box::new(self.binary(level + 1)?));
+        }
+        Ok(left)
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.unary()?;
+        match self.take_op(&["**"]) {
+            Some(op) => Ok(Expr::Binary(op, Box::new(base), Box::new(self.power()?))),
+            None => Ok(base),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if let Some(op) = self.take_op(&["++", "--"]) {
+            let Some(Token::Name(name)) = self.peek().cloned() else {
+                return Err(format!("syntax error: `{}' needs a variable", op));
+            };
+            self.pos += 1;
+            return Ok(Expr::Step(name, if op == "++" { 1 } else { -1 }, true));
+        }
+        if let Some(op) = self.take_op(&["+", "-", "!", "~"]) {
+            return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+        }
+        let primary = self.primary()?;
+        match (primary, self.peek()) {
+            (Expr::Var(name), Some(Token::Op(op @ ("++" | "--")))) => {
+                let step = if *op == "++" { 1 } else { -1 };
+                self.pos += 1;
+                Ok(Expr::Step(name, step, false))
+            }
+            (primary, _) => Ok(primary),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let expr = match self.peek().cloned() {
+            Some(Token::Number(n)) => Expr::Number(n),
+            Some(Token::Name(name)) => Expr::Var(name),
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let expr = self.comma()?;
+                self.expect(")")?;
+                return Ok(expr);
+            }
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+}
+
+fn parse(src: &str) -> Result<Expr, String> {
+    let mut parser = Parser { tokens: tokenize(src)?, pos: 0 };
+    let expr = parser.comma()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err(parser.unexpected()),
+    }
+}
+
+/// Apply a binary operator other than `&&` and `||`.
+fn apply(op: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err(String::from("division by zero")),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" if right < 0 => return Err(String::from("exponent less than 0")),
+        "**" => {
+            let (mut base, mut exponent, mut value) = (left, right, 1i64);
+            while exponent > 0 {
+                if exponent & 1 == 1 {
+                    value = value.wrapping_mul(base);
+                }
+                base = base.wrapping_mul(base);
+                exponent >>= 1;
+            }
+            value
+        }
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        "==" => i64::from(left == right),
+        "!=" => i64::from(left != right),
+        "<" => i64::from(left < right),
+        "<=" => i64::from(left <= right),
+        ">" => i64::from(left > right),
+        ">=" => i64::from(left >= right),
+        _ => unreachable!("not a binary operator: {}", op),
+    })
+}
+
+impl Shell {
+    /// The value of the arithmetic expression `src`, whose parameters and
+    /// substitutions have been expanded already.
+    pub(crate) fn arithmetic(&mut self, src: &str) -> Result<i64, ShellError> {
+        self.evaluate_arithmetic(src, 0).map_err(|e| ShellError::Failed(format!("{}: {}", src.trim(), e)))
+    }
+
+    /// Run `((expression))`, which succeeds if the expression is not zero.
+    pub(crate) fn run_arith(&mut self, expression: &str) -> Result<(), ShellError> {
+        self.extension("((...))")?;
+        let expression = self.expand_word(expression)?;
+        self.last_status = i32::from(self.arithmetic(&expression)? == 0);
+        Ok(())
+    }
+
+    fn evaluate_arithmetic(&mut self, src: &str, depth: usize) -> Result<i64, String> {
+        let expr = parse(src)?;
+        self.eval_expr(&expr, depth)
+    }
+
+    fn eval_expr(&mut self, expr: &Expr, depth: usize) -> Result<i64, String> {
+        Ok(match expr {
+            Expr::Number(n) => *n,
+            Expr::Var(name) => self.arithmetic_var(name, depth)?,
+            Expr::Unary(op, operand) => {
+                let value = self.eval_expr(operand, depth)?;
+                match *op {
+                    "-" => value.wrapping_neg(),
+                    "!" => i64::from(value == 0),
+                    "~" => !value,
+                    _ => value,
+                }
+            }
+            Expr::Binary("&&", left, right) => i64::from(self.eval_expr(left, depth)? != 0 && self.eval_expr(right, depth)? != 0),
+            Expr::Binary("||", left, right) => i64::from(self.eval_expr(left, depth)? != 0 || self.eval_expr(right, depth)? != 0),
+            Expr::Binary(op, left, right) => {
+                let left = self.eval_expr(left, depth)?;
+                apply(op, left, self.eval_expr(right, depth)?)?
+            }
+            Expr::Assign(name, op, value) => {
+                let mut value = self.eval_expr(value, depth)?;
+                if let Some(op) = op {
+                    value = apply(op, self.arithmetic_var(name, depth)?, value)?;
+                }
+                self.restrict_variable(name).map_err(|e| e.to_string())?;
+                self.set_var(name, value.to_string());
+                value
+            }
+            Expr::Step(name, step, prefix) => {
+                let old = self.arithmetic_var(name, depth)?;
+                let new = old.wrapping_add(*step);
+                self.restrict_variable(name).map_err(|e| e.to_string())?;
+                self.set_var(name, new.to_string());
+                if *prefix { new } else { old }
+            }
+            Expr::Cond(condition, then, otherwise) => match self.eval_expr(condition, depth)? {
+                0 => self.eval_expr(otherwise, depth)?,
+                _ => self.eval_expr(then, depth)?,
+            },
+            Expr::Comma(first, second) => {
+                self.eval_expr(first, depth)?;
+                self.eval_expr(second, depth)?
+            }
+        })
+    }
+
+    /// The value of the variable `name` in an expression.
+    fn arithmetic_var(&mut self, name: &str, depth: usize) -> Result<i64, String> {
+        let value = self.var(name).unwrap_or_default();
+        if value.trim().is_empty() {
+            return Ok(0);
+        }
+        if depth >= MAX_DEPTH {
+            return Err(String::from("expression recursion level exceeded"));
+        }
+        self.evaluate_arithmetic(&value, depth + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Shell;
+
+    fn eval(shell: &mut Shell, src: &str) -> Result<i64, String> {
+        shell.arithmetic(src).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn operators_bind_as_in_c() {
+        let mut shell = Shell::new();
+        assert_eq!(eval(&mut shell, "1 + 2 * 3 - 4 / 2"), Ok(5));
+        assert_eq!(eval(&mut shell, "(1 + 2) * 3 % 4"), Ok(1));
+        assert_eq!(eval(&mut shell, "-2 ** 2 + 2 ** 3 ** 2"), Ok(516));
+        assert_eq!(eval(&mut shell, "1 < 2 && 3 == 3 || 0"), Ok(1));
+        assert_eq!(eval(&mut shell, "!5 | ~0 & 6 ^ 1 << 2"), Ok(2));
+        assert_eq!(eval(&mut shell, "0 ? 1 : 2 ? 3 : 4"), Ok(3));
+        assert_eq!(eval(&mut shell, "010 + 0x10 + 2#101 + 36#z"), Ok(64));
+        assert_eq!(eval(&mut shell, "9223372036854775807 + 1"), Ok(i64::MIN));
+    }
+
+    #[test]
+    fn names_are_variables() {
+        let mut shell = Shell::new();
+        shell.set_var("x", String::from("4"));
+        shell.set_var("e", String::from("x * 2"));
+        assert_eq!(eval(&mut shell, "x++ + ++x + e + unset"), Ok(4 + 6 + 12));
+        assert_eq!(eval(&mut shell, "y = x += 2, y *= 2"), Ok(16));
+        assert_eq!(shell.var("y").as_deref(), Some("16"));
+        assert_eq!(eval(&mut shell, "0 && (z = 1)"), Ok(0));
+        assert_eq!(shell.var("z"), None);
+    }
+
+    #[test]
+    fn bad_expressions_are_errors() {
+        let mut shell = Shell::new();
+        assert_eq!(eval(&mut shell, "1 / 0"), Err(String::from("1 / 0: division by zero")));
+        assert_eq!(eval(&mut shell, "1 +"), Err(String::from("1 +: syntax error: operand expected")));
+        assert_eq!(eval(&mut shell, "2 3"), Err(String::from("2 3: syntax error: unexpected `3'")));
+        assert_eq!(eval(&mut shell, "08"), Err(String::from("08: invalid number `08'")));
+        shell.set_var("loop", String::from("loop"));
+        assert!(eval(&mut shell, "loop").unwrap_err().ends_with("recursion level exceeded"));
+    }
+}
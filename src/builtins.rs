@@ -0,0 +1,447 @@
+//! Commands built into the shell, which run in the shell process itself
+//! rather than being looked up on `PATH`.
+//!
+//! Each builtin implements [`Builtin`] and is looked up by name in a
+//! [`Builtins`] registry, which `help` lists and embedders may extend.
+
+use std::{collections::HashMap, io::Write, path::PathBuf, rc::Rc};
+
+use crate::{Shell, ShellError, exec::Flow, shell::Options};
+
+/// Where a builtin writes its output.
+pub struct Io<'a> {
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+/// A command run inside the shell process.
+pub trait Builtin {
+    fn name(&self) -> &'static str;
+
+    /// A one-line usage summary, shown by `help`.
+    fn synopsis(&self) -> &'static str;
+
+    /// Run the builtin, returning its exit status. An error is reported by
+    /// the shell and sets the status from [`ShellError::status`].
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError>;
+}
+
+/// The builtins known to a shell, by name. Builtins take precedence over
+/// functions and external programs.
+#[derive(Clone)]
+pub struct Builtins(HashMap<&'static str, Rc<dyn Builtin>>);
+
+impl Default for Builtins {
+    fn default() -> Self {
+        let mut builtins = Builtins(HashMap::new());
+        builtins.register(Cd);
+        builtins.register(Exit);
+        builtins.register(Return);
+        builtins.register(Shift);
+        builtins.register(LoopControl("break"));
+        builtins.register(LoopControl("continue"));
+        builtins.register(Source("source"));
+        builtins.register(Source("."));
+        builtins.register(Declare("declare"));
+        builtins.register(Declare("typeset"));
+        builtins.register(Trap);
+        builtins.register(Set);
+        builtins.register(Export);
+        builtins.register(Unset);
+        builtins.register(Help);
+        builtins.register(crate::plugin::PluginBuiltin);
+        builtins.register(crate::config::Reload);
+        builtins.register(crate::editor::Bind);
+        builtins.register(crate::envfile::Env);
+        builtins.register(crate::dirstack::Pushd);
+        builtins.register(crate::dirstack::Popd);
+        builtins.register(crate::dirstack::Dirs);
+        builtins.register(crate::jobs::Jobs);
+        builtins.register(crate::jobs::Fg);
+        builtins.register(crate::jobs::Bg);
+        builtins.register(crate::jobs::Kill);
+        builtins.register(crate::jobs::Wait);
+        builtins.register(crate::jobs::Disown);
+        builtins.register(crate::alias::Alias);
+        builtins.register(crate::alias::Unalias);
+        builtins.register(crate::history::History);
+        builtins.register(crate::hash::Hash);
+        builtins.register(crate::hash::Rehash);
+        builtins.register(crate::lookup::Type);
+        builtins.register(crate::lookup::Which);
+        builtins.register(crate::lookup::Command);
+        builtins.register(crate::read::Read);
+        builtins.register(crate::process::Umask);
+        builtins.register(crate::process::Ulimit);
+        builtins.register(crate::process::Exec);
+        builtins
+    }
+}
+
+impl Builtins {
+    /// Add a builtin, replacing any existing one of the same name.
+    pub fn register(&mut self, builtin: impl Builtin + 'static) {
+        self.insert(Rc::new(builtin));
+    }
+
+    pub(crate) fn insert(&mut self, builtin: Rc<dyn Builtin>) {
+        self.0.insert(builtin.name(), builtin);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.0.get(name).cloned()
+    }
+
+    /// The builtins in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<dyn Builtin>> {
+        let mut builtins: Vec<_> = self.0.values().collect();
+        builtins.sort_by_key(|b| b.name());
+        builtins.into_iter()
+    }
+}
+
+struct Cd;
+
+impl Builtin for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "cd [dir | - | -N | --list]"
+    }
+
+    /// `cd -` goes back to `$OLDPWD`, and a relative name is looked for in
+    /// the directories of `CDPATH`. Either way the new directory is printed.
+    /// With `CORRECT` set, a directory that is not there may be corrected.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let mut print = false;
+        let (dir, target) = match args.first().map(String::as_str) {
+            Some("--list") => match shell.pick_recent_dir(io.stdout)? {
+                Some(target) => (target.display().to_string(), target),
+                None => return Ok(0),
+            },
+            // `cd -2` goes back two directories
+            Some(arg) if arg.len() > 1 && arg.strip_prefix('-').is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit())) => {
+                let target = shell.recent_dir(arg[1..].parse().map_err(|_| ShellError::Failed(format!("cd: {}: no such entry in the directory history", arg)))?)?;
+                (target.display().to_string(), target)
+            }
+            Some("-") => {
+                let dir = shell.var("OLDPWD").filter(|d| !d.is_empty()).ok_or_else(|| ShellError::Failed(String::from("cd: OLDPWD not set")))?;
+                print = true;
+                (dir.clone(), PathBuf::from(dir))
+            }
+            Some(dir) => match shell.search_cdpath(dir) {
+                Some((target, found)) => {
+                    print = found;
+                    (dir.to_string(), target)
+                }
+                None => {
+                    let dir = shell.correct_dir(dir).unwrap_or_else(|| dir.to_string());
+                    let target = shell.path.join(&dir);
+                    (dir, target)
+                }
+            },
+            None => (shell.home.display().to_string(), shell.home.clone()),
+        };
+        let from = shell.change_dir(&dir, target)?;
+        if print {
+            let _ = writeln!(io.stdout, "{}", shell.path.display());
+        }
+        if shell.options.autopushd {
+            shell.dir_stack.push(from);
+        }
+        Ok(0)
+    }
+}
+
+struct Exit;
+
+impl Builtin for Exit {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "exit [n]"
+    }
+
+    /// Leave with status `n`, or that of the last command. A bad status
+    /// still leaves, with status 2.
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        shell.exiting = true;
+        match args.first() {
+            Some(status) => Ok(status.parse::<i32>().map_err(|_| ShellError::Usage(format!("exit: {}: numeric argument required", status)))? & 0xff),
+            None => Ok(shell.last_status),
+        }
+    }
+}
+
+struct Return;
+
+impl Builtin for Return {
+    fn name(&self) -> &'static str {
+        "return"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "return [n]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        if shell.function_depth == 0 && shell.source_depth == 0 {
+            return Err(ShellError::Failed(String::from("return: can only `return' from a function or sourced script")));
+        }
+        let status = match args.first() {
+            Some(status) => status.parse::<i32>().map_err(|_| ShellError::Usage(format!("return: {}: numeric argument required", status)))? & 0xff,
+            None => shell.last_status,
+        };
+        shell.flow = Flow::Return;
+        Ok(status)
+    }
+}
+
+struct Shift;
+
+impl Builtin for Shift {
+    fn name(&self) -> &'static str {
+        "shift"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "shift [n]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        let n = match args.first() {
+            Some(n) => n.parse::<usize>().map_err(|_| ShellError::Usage(format!("shift: {}: numeric argument required", n)))?,
+            None => 1,
+        };
+        if n > shell.positional.len() {
+            return Err(ShellError::Failed(format!("shift: {}: shift count out of range", n)));
+        }
+        shell.positional.drain(..n);
+        Ok(0)
+    }
+}
+
+/// `break` and `continue`.
+struct LoopControl(&'static str);
+
+impl Builtin for LoopControl {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn synopsis(&self) -> &'static str {
+        if self.0 == "break" { "break [n]" } else { "continue [n]" }
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        let levels = match args.first() {
+            Some(n) => n.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(|| ShellError::Failed(format!("{}: {}: loop count out of range", self.0, n)))?,
+            None => 1,
+        };
+        if shell.loop_depth == 0 {
+            return Err(ShellError::Failed(format!("{}: only meaningful in a `for', `while', or `until' loop", self.0)));
+        }
+        let levels = levels.min(shell.loop_depth);
+        shell.flow = if self.0 == "break" { Flow::Break(levels) } else { Flow::Continue(levels) };
+        Ok(0)
+    }
+}
+
+/// `source` and `.`.
+struct Source(&'static str);
+
+impl Builtin for Source {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn synopsis(&self) -> &'static str {
+        if self.0 == "." { ". file [args]" } else { "source file [args]" }
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        let (file, args) = args.split_first().ok_or_else(|| ShellError::Usage(format!("{}: filename argument required", self.0)))?;
+        if file.contains('/') {
+            shell.restrict(file)?;
+        }
+        let file = shell.path.join(file);
+        // arguments after the file name temporarily replace the positional parameters
+        let saved = (!args.is_empty()).then(|| std::mem::replace(&mut shell.positional, args.to_vec()));
+        let result = shell.source_file(&file);
+        if let Some(positional) = saved {
+            shell.positional = positional;
+        }
+        result?;
+        Ok(shell.last_status)
+    }
+}
+
+/// `declare` and its synonym `typeset`.
+struct Declare(&'static str);
+
+impl Builtin for Declare {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn synopsis(&self) -> &'static str {
+        if self.0 == "typeset" { "typeset [-aAfFp] [name[=value] ...]" } else { "declare [-aAfFp] [name[=value] ...]" }
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        shell.declare(args, io.stdout)?;
+        Ok(0)
+    }
+}
+
+struct Trap;
+
+impl Builtin for Trap {
+    fn name(&self) -> &'static str {
+        "trap"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "trap [-p] [action event ...]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        shell.trap(args, io.stdout)?;
+        Ok(0)
+    }
+}
+
+struct Set;
+
+impl Builtin for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "set [-euxnvf] [-o option] [--] [arg ...]"
+    }
+
+    /// Without arguments, list the variables.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.is_empty() {
+            shell.print_vars(io.stdout);
+            return Ok(0);
+        }
+        shell.set(args)?;
+        Ok(0)
+    }
+}
+
+struct Export;
+
+impl Builtin for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "export [-np] [name[=value] ...]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        shell.export(args, io.stdout)?;
+        Ok(0)
+    }
+}
+
+struct Unset;
+
+impl Builtin for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "unset [-fv] name ..."
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        shell.unset(args)?;
+        Ok(0)
+    }
+}
+
+struct Help;
+
+impl Builtin for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "help [name ...]"
+    }
+
+    /// List the builtins, or just the ones named.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.is_empty() {
+            for builtin in shell.builtins.iter() {
+                let _ = writeln!(io.stdout, "{}", builtin.synopsis());
+            }
+            return Ok(0);
+        }
+        let mut status = 0;
+        for name in args {
+            match shell.builtins.get(name) {
+                Some(builtin) => {
+                    let _ = writeln!(io.stdout, "{}", builtin.synopsis());
+                }
+                None => {
+                    let _ = writeln!(io.stderr, "help: no help topics match `{}'", name);
+                    status = 1;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+impl Shell {
+    /// Make `builtin` available to commands run by this shell, replacing any
+    /// builtin of the same name.
+    pub fn register_builtin(&mut self, builtin: impl Builtin + 'static) {
+        self.builtins.register(builtin);
+    }
+
+    fn set(&mut self, args: &[String]) -> Result<(), ShellError> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (enable, flags) = match (arg.strip_prefix('-'), arg.strip_prefix('+')) {
+                // `set -- args` replaces the positional parameters
+                (Some("-"), _) => {
+                    self.positional = args.cloned().collect();
+                    return Ok(());
+                }
+                (Some(flags), _) if !flags.is_empty() => (true, flags),
+                (_, Some(flags)) if !flags.is_empty() => (false, flags),
+                _ => {
+                    self.positional = std::iter::once(arg).chain(args).cloned().collect();
+                    return Ok(());
+                }
+            };
+            for flag in flags.chars() {
+                let name = match flag {
+                    'o' => args.next().ok_or_else(|| ShellError::Usage(String::from("set: option name required")))?.as_str(),
+                    _ => Options::FLAGS.iter().find(|(c, _)| *c == flag).map(|(_, name)| *name).ok_or_else(|| ShellError::Usage(format!("set: {}{}: invalid option", &arg[..1], flag)))?,
+                };
+                if name == "restricted" && !enable {
+                    self.restrict("set +r")?;
+                }
+                if !self.options.set(name, enable) {
+                    return Err(ShellError::Usage(format!("set: {}: invalid option name", name)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+//! Tab completion. The first word of a command completes to the programs in
+//! `PATH` and the shell's aliases, functions and builtins, and `$NAME` to a
+//! variable. Other words complete according to the command: `cd` and `pushd`
+//! take directories, `export` and `unset` variable names, `alias` aliases, and
+//! commands without a completion of their own take file names, with a `/`
+//! after directories. [`Shell::register_completion`] adds more.
+
+use std::{cell::RefCell, collections::HashMap, fs, os::unix::fs::PermissionsExt, path::PathBuf, rc::Rc};
+
+use rustyline::completion::Pair;
+
+use crate::{highlight::Programs, parser};
+
+/// Words after which a new command starts.
+const COMMAND_STARTERS: &[&str] = &["if", "then", "else", "elif", "while", "until", "do", "!", "{", "time"];
+
+/// The word ending at `pos` in `line`, as typed and with its quoting
+/// removed, and the byte offset it starts at.
+pub(crate) struct Word {
+    pub(crate) start: usize,
+    pub(crate) text: String,
+    /// The command the word is an argument of, or `None` if it is in
+    /// command position.
+    pub(crate) command: Option<String>,
+}
+
+impl Word {
+    pub(crate) fn at(line: &str, pos: usize) -> Self {
+        let line = &line[..pos];
+        let mut start = 0;
+        let mut command_start = 0;
+        let mut quote = None;
+        let mut escaped = false;
+        for (i, c) in line.char_indices() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') => escaped = true,
+                (Some(_), _) => {}
+                (None, '\\') => escaped = true,
+                (None, '\'' | '"') => quote = Some(c),
+                (None, ';' | '|' | '&' | '(' | ')' | '\n') => {
+                    start = i + 1;
+                    command_start = i + 1;
+                }
+                (None, ' ' | '\t' | '<' | '>') => start = i + 1,
+                _ => {}
+            }
+        }
+        // redirections and assignments can come before the command name,
+        // and reserved words such as `then` are followed by one
+        let mut before: Vec<&str> = line[command_start..start].split_whitespace().filter(|w| !w.contains(['<', '>'])).collect();
+        while before.first().is_some_and(|w| COMMAND_STARTERS.contains(w) || w.split_once('=').is_some_and(|(name, _)| parser::is_name(name))) {
+            before.remove(0);
+        }
+        let redirect = line[..start].trim_end_matches([' ', '\t']).ends_with(['<', '>']);
+        // redirection targets are always files
+        let command = match before.first() {
+            _ if redirect => Some(String::new()),
+            first => first.map(|w| unquote(w)),
+        };
+        Word { start, text: unquote(&line[start..]), command }
+    }
+}
+
+/// The names the shell knew at the last prompt, for completion functions to
+/// draw on.
+#[derive(Debug, Clone, Default)]
+pub struct Names {
+    pub variables: Vec<String>,
+    pub aliases: Vec<String>,
+    pub functions: Vec<String>,
+    pub builtins: Vec<String>,
+}
+
+/// Completes the arguments of a command. It is given the word typed so far,
+/// without its quotes, and returns the words it could become; those ending
+/// in `/` are left open for more and the rest are followed by a space.
+pub type Completion = fn(&Names, &str) -> Vec<String>;
+
+/// How the arguments of each command complete, by command name. Commands
+/// without an entry complete file names.
+pub(crate) struct Completions {
+    pub(crate) names: Names,
+    /// The programs in `PATH`, for highlighting.
+    pub(crate) programs: Programs,
+    by_command: HashMap<String, Completion>,
+}
+
+impl Default for Completions {
+    fn default() -> Self {
+        let mut completions = Completions { names: Names::default(), programs: Programs::default(), by_command: HashMap::new() };
+        completions.register("cd", directories);
+        completions.register("pushd", directories);
+        for name in ["export", "unset", "declare", "typeset"] {
+            completions.register(name, variables);
+        }
+        completions.register("alias", aliases);
+        completions.register("unalias", aliases);
+        completions.register("help", builtins);
+        completions
+    }
+}
+
+impl Completions {
+    /// Complete the arguments of `command` with `completion`, replacing any
+    /// completion it had.
+    pub(crate) fn register(&mut self, command: &str, completion: Completion) {
+        self.by_command.insert(command.to_string(), completion);
+    }
+
+    /// Completions for the word ending at `pos` in `line`.
+    pub(crate) fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let word = Word::at(line, pos);
+        if let Some(name) = word.text.strip_prefix('$').filter(|_| line[word.start..pos].starts_with('$')) {
+            let pairs = variables(&self.names, name).into_iter().map(|v| Pair { display: v.clone(), replacement: format!("${} ", v) }).collect();
+            return (word.start, pairs);
+        }
+        let words = match &word.command {
+            None if !word.text.contains('/') => commands(&self.names, &word.text),
+            None => files(&word.text, false),
+            Some(command) => match self.by_command.get(command) {
+                Some(completion) => completion(&self.names, &word.text),
+                None => files(&word.text, false),
+            },
+        };
+        (word.start, words.iter().map(|w| pair(w)).collect())
+    }
+}
+
+/// A completion as listed, with only the last part of a path shown, and as
+/// inserted.
+fn pair(word: &str) -> Pair {
+    let display = match word.trim_end_matches('/').rfind('/') {
+        Some(slash) => &word[slash + 1..],
+        None => word,
+    };
+    let space = if word.ends_with('/') { "" } else { " " };
+    Pair { display: display.to_string(), replacement: format!("{}{}", escape(word), space) }
+}
+
+fn matching(names: &[String], prefix: &str) -> Vec<String> {
+    names.iter().filter(|n| n.starts_with(prefix)).cloned().collect()
+}
+
+fn variables(names: &Names, prefix: &str) -> Vec<String> {
+    matching(&names.variables, prefix)
+}
+
+fn aliases(names: &Names, prefix: &str) -> Vec<String> {
+    matching(&names.aliases, prefix)
+}
+
+fn builtins(names: &Names, prefix: &str) -> Vec<String> {
+    matching(&names.builtins, prefix)
+}
+
+fn directories(_names: &Names, word: &str) -> Vec<String> {
+    files(word, true)
+}
+
+/// Aliases, functions, builtins and the programs in `PATH`.
+fn commands(names: &Names, prefix: &str) -> Vec<String> {
+    let path = std::env::var("PATH").unwrap_or_default();
+    let mut commands = programs(prefix, &path);
+    for group in [&names.aliases, &names.functions, &names.builtins] {
+        commands.extend(matching(group, prefix));
+    }
+    commands.sort();
+    commands.dedup();
+    commands
+}
+
+/// The executables in the directories of `path` whose names start with
+/// `prefix`.
+pub(crate) fn programs(prefix: &str, path: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for dir in path.split(':').filter(|d| !d.is_empty()) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) {
+                continue;
+            }
+            // follows symlinks, which is how most of /usr/bin gets there
+            if fs::metadata(entry.path()).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// File names completing `word`, a path relative to the working directory
+/// that may start with `~/`, sorted and with a `/` after directories. Dot
+/// files are only offered once the name starts with `.`.
+fn files(word: &str, directories_only: bool) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(slash) => (&word[..=slash], &word[slash + 1..]),
+        None => ("", word),
+    };
+    let listed = match dir.strip_prefix("~/") {
+        Some(rest) => home().join(rest),
+        None if dir.is_empty() => PathBuf::from("."),
+        None => PathBuf::from(dir),
+    };
+    let Ok(entries) = fs::read_dir(&listed) else {
+        return Vec::new();
+    };
+    let mut words: Vec<String> = entries.flatten().filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            return None;
+        }
+        let is_dir = fs::metadata(entry.path()).is_ok_and(|m| m.is_dir());
+        if directories_only && !is_dir {
+            return None;
+        }
+        Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+    }).collect();
+    words.sort();
+    words
+}
+
+impl crate::Shell {
+    /// Complete the arguments of `command` with `completion` at the
+    /// interactive prompt, replacing any completion it had.
+    pub fn register_completion(&mut self, command: &str, completion: Completion) {
+        self.completions.borrow_mut().register(command, completion);
+    }
+
+    /// Let completion functions see the names defined so far.
+    pub(crate) fn update_completion_names(&self) {
+        let mut variables: Vec<String> = self.vars.keys().cloned().chain(std::env::vars_os().map(|(k, _)| k.to_string_lossy().into_owned())).collect();
+        variables.sort();
+        variables.dedup();
+        let mut aliases: Vec<String> = self.aliases.keys().cloned().collect();
+        aliases.sort();
+        let mut functions: Vec<String> = self.functions.keys().cloned().collect();
+        functions.sort();
+        let builtins = self.builtins.iter().map(|b| b.name().to_string()).filter(|b| self.builtin_enabled(b)).collect();
+        let mut completions = self.completions.borrow_mut();
+        completions.names = Names { variables, aliases, functions, builtins };
+        if !self.options.nohighlight {
+            completions.programs.refresh(&self.var("PATH").unwrap_or_default());
+        }
+    }
+}
+
+/// The completions shared by a shell and its line editor.
+pub(crate) type Shared = Rc<RefCell<Completions>>;
+
+fn home() -> PathBuf {
+    std::env::var_os("HOME").map_or_else(|| PathBuf::from("/"), PathBuf::from)
+}
+
+/// What a partly typed word means without its quotes and backslashes. An
+/// unfinished quote is taken to run to the end.
+fn unquote(word: &str) -> String {
+    let mut text = String::new();
+    let mut quote = None;
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => text.extend(chars.next()),
+            (None, '\'' | '"') => quote = Some(c),
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// Backslash the characters in `text` the shell would otherwise treat
+/// specially.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if " \t\n'\"\\$`&|;<>()*?[]#!{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Completions, Names, Word, escape, files, programs};
+
+    #[test]
+    fn words_know_their_command() {
+        assert_eq!(Word::at("ec", 2).command, None);
+        assert_eq!(Word::at("ls | gr", 7).command, None);
+        assert_eq!(Word::at("if tr", 5).command, None);
+        let word = Word::at("cat some\\ fi", 12);
+        assert_eq!((word.start, word.text.as_str(), word.command.as_deref()), (4, "some fi", Some("cat")));
+        assert_eq!(Word::at("echo hi >fi", 11).command.as_deref(), Some(""));
+        assert_eq!(Word::at(">out ec", 7).command, None);
+        assert_eq!(Word::at("LANG=C sor", 10).command, None);
+    }
+
+    #[test]
+    fn files_and_programs_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub dir")).unwrap();
+        std::fs::write(dir.path().join("subject"), "").unwrap();
+        std::fs::write(dir.path().join(".subtle"), "").unwrap();
+        let base = format!("{}/", dir.path().display());
+        assert_eq!(files(&format!("{}sub", base), false), [format!("{}sub dir/", base), format!("{}subject", base)]);
+        assert_eq!(files(&format!("{}sub", base), true).len(), 1);
+        let line = format!("cd {}", escape(&base));
+        let (start, pairs) = Completions::default().complete(&line, line.len());
+        assert_eq!(start, 3);
+        let pairs: Vec<_> = pairs.into_iter().map(|p| (p.display, p.replacement)).collect();
+        assert_eq!(pairs, [(String::from("sub dir/"), format!("{}sub\\ dir/", escape(&base)))]);
+        assert_eq!(programs("s", "/nonexistent"), Vec::<String>::new());
+        assert!(programs("s", "/bin:/usr/bin").contains(&String::from("sh")));
+    }
+
+    #[test]
+    fn arguments_complete_by_command() {
+        let names = Names { variables: vec![String::from("PATH"), String::from("PS1")], aliases: vec![String::from("ll")], ..Names::default() };
+        let mut completions = Completions { names, ..Completions::default() };
+        let replacements = |c: &Completions, line: &str| c.complete(line, line.len()).1.into_iter().map(|p| p.replacement).collect::<Vec<_>>();
+        assert_eq!(replacements(&completions, "unset PA"), ["PATH "]);
+        assert_eq!(replacements(&completions, "echo $P"), ["$PATH ", "$PS1 "]);
+        assert_eq!(replacements(&completions, "unalias l"), ["ll "]);
+        completions.register("greet", |_, word| vec![format!("{}orld", word)]);
+        assert_eq!(replacements(&completions, "greet w"), ["world "]);
+    }
+}
@@ -0,0 +1,221 @@
+//! The declarative configuration file, `$XDG_CONFIG_HOME/mash/config.toml`
+//! (`~/.config/mash/config.toml` by default):
+//!
+//! ```toml
+//! [options]
+//! vi = true
+//!
+//! [history]
+//! file = "~/.local/state/mash/history"
+//! size = 5000
+//! ignore_dups = true
+//!
+//! [prompt]
+//! symbol = "$"
+//! color = "cyan"
+//!
+//! [keybindings]
+//! ctrl-p = "history-search-backward"
+//!
+//! [aliases]
+//! ll = "ls -l"
+//!
+//! [notify]
+//! after = 30
+//! ```
+//!
+//! Interactive shells read it before the rc file, so the rc file and any
+//! commands typed later override it; built-in defaults apply to anything it
+//! leaves out; keys can also be bound from the rc file with `bind ctrl-l
+//! clear-screen`. `reload` reads both files again, as does the next prompt
+//! after either changes when `set -o autoreload` is on.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::SystemTime};
+
+use rustyline::{Cmd, Editor, Helper, KeyEvent, config::Configurer, history::FileHistory};
+use serde::Deserialize;
+
+use crate::{Builtin, Io, Shell, ShellError, editor, error, notify::Notify, prompt::Theme};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Options as named by `set -o`.
+    options: BTreeMap<String, bool>,
+    history: History,
+    prompt: Option<Theme>,
+    /// Editor commands by key, using readline's command names.
+    keybindings: BTreeMap<String, String>,
+    aliases: BTreeMap<String, String>,
+    notify: Notify,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct History {
+    file: Option<PathBuf>,
+    /// The most entries kept.
+    size: Option<usize>,
+    /// Don't record a line that repeats the one before it.
+    ignore_dups: Option<bool>,
+    /// Don't record lines starting with a space.
+    ignore_space: Option<bool>,
+}
+
+impl Config {
+    /// Where the configuration file is looked for.
+    pub(crate) fn path(home: &Path) -> PathBuf {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|p| p.is_absolute());
+        config_home.unwrap_or_else(|| home.join(".config")).join("mash/config.toml")
+    }
+
+    /// Read the configuration file. A missing file is an empty configuration;
+    /// one that does not parse is reported and ignored.
+    pub(crate) fn load(path: &Path) -> Config {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(_) => return Config::default(),
+        };
+        match toml::from_str(&source) {
+            Ok(config) => config,
+            Err(e) => {
+                error::warn(format!("{}: {}", path.display(), e));
+                Config::default()
+            }
+        }
+    }
+
+    /// The key bindings, skipping any that are not understood.
+    fn bindings(&self) -> Vec<(KeyEvent, Cmd)> {
+        let mut bindings = Vec::new();
+        for (key, command) in &self.keybindings {
+            match (editor::parse_key(key), editor::parse_command(command)) {
+                (Some(key), Some(command)) => bindings.push((key, command)),
+                (None, _) => error::warn(format!("keybindings: {}: unknown key", key)),
+                (_, None) => error::warn(format!("keybindings: {}: unknown editor command", command)),
+            }
+        }
+        bindings
+    }
+}
+
+impl Shell {
+    /// Read the rc file chosen at startup, if any.
+    pub(crate) fn source_rcfile(&mut self) {
+        if let Some(rcfile) = self.rcfile.clone() {
+            if let Err(e) = self.source_file(&rcfile) {
+                self.report(e);
+            }
+        }
+        self.startup_mtimes = self.startup_files().iter().map(|f| modified(f)).collect();
+    }
+
+    /// `reload`: read the configuration and rc files again, replacing the
+    /// aliases, prompt theme and key bindings they set. Options they set are
+    /// applied again; those they no longer mention keep their values. The
+    /// history file stays where it was.
+    pub(crate) fn reload(&mut self) {
+        let config = Config::load(&Config::path(&self.home));
+        self.aliases.clear();
+        self.bindings.clear();
+        self.theme = Theme::default();
+        self.notify_after = None;
+        self.apply_config(&config);
+        self.source_rcfile();
+        self.editor_config = Some(config);
+    }
+
+    fn startup_files(&self) -> Vec<PathBuf> {
+        std::iter::once(Config::path(&self.home)).chain(self.rcfile.clone()).collect()
+    }
+
+    /// Whether the configuration or rc file has changed since it was read,
+    /// for `set -o autoreload`.
+    pub(crate) fn startup_files_changed(&self) -> bool {
+        let mtimes: Vec<_> = self.startup_files().iter().map(|f| modified(f)).collect();
+        mtimes != self.startup_mtimes
+    }
+
+    /// Apply the parts of `config` that belong to the shell rather than the
+    /// line editor.
+    pub(crate) fn apply_config(&mut self, config: &Config) {
+        for (name, &enable) in &config.options {
+            if !self.options.set(name, enable) {
+                error::warn(format!("options: {}: invalid option name", name));
+            }
+        }
+        if let Some(file) = &config.history.file {
+            self.history_file = match file.strip_prefix("~") {
+                Ok(rest) => self.home.join(rest),
+                Err(_) => file.clone(),
+            };
+        }
+        if let Some(theme) = &config.prompt {
+            match theme.check() {
+                Ok(()) => self.theme = theme.clone(),
+                Err(e) => error::warn(format!("prompt: {}", e)),
+            }
+        }
+        for (name, value) in &config.aliases {
+            self.aliases.insert(name.clone(), value.clone());
+        }
+        if let Some(after) = config.notify.after {
+            self.notify_after = Some(std::time::Duration::from_secs(after));
+        }
+        if let Err(e) = self.update_prompt() {
+            error::warn(e);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// `reload`, for picking up edits to the configuration and rc files without
+/// starting a new shell.
+pub(crate) struct Reload;
+
+impl Builtin for Reload {
+    fn name(&self) -> &'static str {
+        "reload"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "reload"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        if !args.is_empty() {
+            return Err(ShellError::Usage(String::from("reload: too many arguments")));
+        }
+        if !shell.interactive {
+            return Err(ShellError::Failed(String::from("reload: not an interactive shell")));
+        }
+        shell.reload();
+        Ok(shell.last_status)
+    }
+}
+
+/// Apply the history settings and key bindings of `config` to the editor,
+/// returning the keys bound.
+pub(crate) fn configure_editor<H: Helper>(rl: &mut Editor<H, FileHistory>, config: &Config) -> Vec<KeyEvent> {
+    if let Some(size) = config.history.size {
+        if let Err(e) = rl.set_max_history_size(size) {
+            error::warn(format!("history: {}", e));
+        }
+    }
+    if let Some(ignore) = config.history.ignore_dups {
+        if let Err(e) = rl.set_history_ignore_dups(ignore) {
+            error::warn(format!("history: {}", e));
+        }
+    }
+    if let Some(ignore) = config.history.ignore_space {
+        rl.set_history_ignore_space(ignore);
+    }
+    let bindings = config.bindings();
+    for (key, command) in &bindings {
+        rl.bind_sequence(*key, command.clone());
+    }
+    bindings.into_iter().map(|(key, _)| key).collect()
+}
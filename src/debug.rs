@@ -0,0 +1,160 @@
+//! `mash --debug`: an interactive debugger for scripts. Before each simple
+//! command the interpreter asks the debugger whether to stop; when it does,
+//! debugger commands are read from the terminal until one resumes the script.
+
+use std::{fs::File, io::{BufRead, BufReader, Write}, path::Path};
+
+use crate::{Shell, parser::Command};
+
+const HELP: &str = "\
+break [FILE:]LINE  stop before the command on LINE
+delete [N]         remove breakpoint N, or all of them
+info               list breakpoints
+step               run the next command, stepping into functions
+next               run the next command, stepping over functions
+continue           run until the next breakpoint
+print WORD...      expand and print words, as in `print $x ${a[@]}`
+vars               show all shell variables
+quit               stop the script";
+
+/// How far to run before stopping again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+    Step,
+    /// Stop at the next command not inside a function called from this depth.
+    Next(usize),
+    Continue,
+}
+
+pub struct Debugger {
+    breakpoints: Vec<(String, usize)>,
+    resume: Resume,
+    /// Where debugger commands are read from: the terminal, so that the
+    /// script keeps its own standard input.
+    input: Box<dyn BufRead>,
+}
+
+impl Debugger {
+    /// A debugger that stops before the first command.
+    pub fn new() -> Self {
+        let input: Box<dyn BufRead> = match File::open("/dev/tty") {
+            Ok(tty) => Box::new(BufReader::new(tty)),
+            Err(_) => Box::new(BufReader::new(std::io::stdin())),
+        };
+        eprintln!("mash debugger: type `help' for a list of commands");
+        Self { breakpoints: Vec::new(), resume: Resume::Step, input }
+    }
+
+    fn should_stop(&self, file: &str, line: usize, depth: usize) -> bool {
+        let at_breakpoint = self.breakpoints.iter().any(|(f, l)| *l == line && (f == file || Path::new(file).file_name().is_some_and(|name| name == f.as_str())));
+        match self.resume {
+            Resume::Step => true,
+            Resume::Next(from) => depth <= from || at_breakpoint,
+            Resume::Continue => at_breakpoint,
+        }
+    }
+
+    fn read_command(&mut self) -> Option<String> {
+        eprint!("(mashdb) ");
+        let _ = std::io::stderr().flush();
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+}
+
+impl Shell {
+    /// Give the debugger a chance to stop before `command` runs.
+    pub(crate) fn debug_stop(&mut self, command: &Command) {
+        let Some(mut debugger) = self.debugger.take() else {
+            return;
+        };
+        if debugger.should_stop(&self.file, command.line, self.function_depth) {
+            let file = if self.file.is_empty() { "-" } else { &self.file };
+            eprintln!("{}:{}: {}", file, command.line, command);
+            self.debug_prompt(&mut debugger);
+        }
+        self.debugger = Some(debugger);
+    }
+
+    /// Read debugger commands until one resumes the script. End of input
+    /// lets the script run to completion.
+    fn debug_prompt(&mut self, debugger: &mut Debugger) {
+        loop {
+            let Some(line) = debugger.read_command() else {
+                eprintln!();
+                debugger.resume = Resume::Continue;
+                debugger.breakpoints.clear();
+                return;
+            };
+            let mut words = line.split_whitespace();
+            let Some(command) = words.next() else {
+                continue;
+            };
+            let args: Vec<&str> = words.collect();
+            match command {
+                "s" | "step" => {
+                    debugger.resume = Resume::Step;
+                    return;
+                }
+                "n" | "next" => {
+                    debugger.resume = Resume::Next(self.function_depth);
+                    return;
+                }
+                "c" | "continue" => {
+                    debugger.resume = Resume::Continue;
+                    return;
+                }
+                "q" | "quit" => {
+                    self.exiting = true;
+                    return;
+                }
+                "b" | "break" => {
+                    let Some(spec) = args.first() else {
+                        eprintln!("break: location required");
+                        continue;
+                    };
+                    let (file, line) = match spec.rsplit_once(':') {
+                        Some((file, line)) => (file.to_string(), line),
+                        None => (self.file.clone(), *spec),
+                    };
+                    match line.parse::<usize>() {
+                        Ok(line) => {
+                            eprintln!("breakpoint {} at {}:{}", debugger.breakpoints.len() + 1, file, line);
+                            debugger.breakpoints.push((file, line));
+                        }
+                        Err(_) => eprintln!("break: {}: invalid line number", line),
+                    }
+                }
+                "d" | "delete" => match args.first().map(|n| n.parse::<usize>()) {
+                    None => debugger.breakpoints.clear(),
+                    Some(Ok(n)) if (1..=debugger.breakpoints.len()).contains(&n) => {
+                        debugger.breakpoints.remove(n - 1);
+                    }
+                    Some(_) => eprintln!("delete: no such breakpoint"),
+                },
+                "i" | "info" => {
+                    for (i, (file, line)) in debugger.breakpoints.iter().enumerate() {
+                        eprintln!("{}: {}:{}", i + 1, file, line);
+                    }
+                }
+                "p" | "print" => {
+                    let words: Vec<String> = args.iter().map(|w| w.to_string()).collect();
+                    match self.expand_words(&words) {
+                        Ok(fields) => eprintln!("{}", fields.join(" ")),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+                "vars" => {
+                    if let Err(e) = self.declare(&[String::from("-p")], &mut std::io::stderr()) {
+                        eprintln!("{}", e);
+                    }
+                }
+                "h" | "help" => eprintln!("{}", HELP),
+                _ => eprintln!("{}: unknown command; try `help'", command),
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Recently visited directories, most recent first, so `cd -N` can go back
+//! several steps and `cd --list` can pick one from a list. Interactive
+//! shells keep them in the state directory between sessions.
+
+use std::{io::Write, path::PathBuf};
+
+use crate::{Shell, ShellError, dirs, error, shell::read_stdin_line};
+
+/// How many directories are remembered.
+const LIMIT: usize = 20;
+
+/// The file in the state directory the history is saved in.
+const DIRS_FILE: &str = "dirs";
+
+impl Shell {
+    /// Record that the shell moved from `from` to the current directory.
+    /// Each directory is listed once, and the current one not at all.
+    pub(crate) fn remember_dir(&mut self, from: PathBuf) {
+        let from = from.canonicalize().unwrap_or(from);
+        let here = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        self.dir_history.retain(|dir| *dir != from && *dir != here);
+        if from != here {
+            self.dir_history.insert(0, from);
+        }
+        self.dir_history.truncate(LIMIT);
+    }
+
+    /// The directory `cd -N` goes to, `cd -1` being the previous one.
+    pub(crate) fn recent_dir(&self, n: usize) -> Result<PathBuf, ShellError> {
+        n.checked_sub(1).and_then(|i| self.dir_history.get(i)).cloned().ok_or_else(|| ShellError::Failed(format!("cd: -{}: no such entry in the directory history", n)))
+    }
+
+    /// `cd --list`: number the recent directories and, on a terminal, ask
+    /// which one to go to. Returns `None` if the answer is empty.
+    pub(crate) fn pick_recent_dir(&self, out: &mut dyn Write) -> Result<Option<PathBuf>, ShellError> {
+        for (i, dir) in self.dir_history.iter().enumerate() {
+            let _ = writeln!(out, "{:>2}  {}", i + 1, self.tilde(dir));
+        }
+        if self.dir_history.is_empty() || !self.interactive {
+            return Ok(None);
+        }
+        let _ = write!(out, "cd to: ");
+        let _ = out.flush();
+        match read_stdin_line().as_deref().map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(answer) => match answer.parse() {
+                Ok(n) => self.recent_dir(n).map(Some),
+                Err(_) => Err(ShellError::Failed(format!("cd: {}: not a number", answer))),
+            },
+        }
+    }
+
+    /// `dir` with the home directory shown as `~`.
+    pub(crate) fn tilde(&self, dir: &std::path::Path) -> String {
+        match dir.strip_prefix(&self.home) {
+            Ok(rest) if rest.as_os_str().is_empty() => String::from("~"),
+            Ok(rest) => format!("~/{}", rest.display()),
+            Err(_) => dir.display().to_string(),
+        }
+    }
+}
+
+impl Shell {
+    /// Read the directory history saved by the last shell to exit, keeping
+    /// the directories that still exist.
+    pub(crate) fn load_dir_history(&mut self) {
+        let file = dirs::state_file(&self.home, DIRS_FILE);
+        if let Ok(text) = std::fs::read_to_string(file) {
+            self.dir_history = text.lines().map(PathBuf::from).filter(|dir| dir.is_dir()).take(LIMIT).collect();
+        }
+    }
+
+    /// Save the directory history for the next shell. The file is replaced
+    /// in one step so a crash cannot leave half of it.
+    pub(crate) fn save_dir_history(&self) {
+        let file = dirs::state_file(&self.home, DIRS_FILE);
+        let partial = file.with_extension("new");
+        let mut text = String::new();
+        for dir in &self.dir_history {
+            text.push_str(&dir.to_string_lossy());
+            text.push('\n');
+        }
+        let saved = dirs::create_parent(&file).and_then(|_| std::fs::write(&partial, text)).and_then(|_| std::fs::rename(&partial, &file));
+        if let Err(e) = saved {
+            error::warn(format!("{}: {}", file.display(), error::describe_io(&e)));
+        }
+    }
+}
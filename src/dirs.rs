@@ -0,0 +1,58 @@
+//! Where the shell keeps its files, following the XDG base directory
+//! specification: state that should survive restarts, such as history and
+//! the allowed environment files, goes in `$XDG_STATE_HOME/mash`
+//! (`~/.local/state/mash`).
+
+use std::path::{Path, PathBuf};
+
+use crate::error;
+
+/// `$name` if it is an absolute path, as the specification requires, or
+/// `default` under the home directory.
+fn base(name: &str, home: &Path, default: &str) -> PathBuf {
+    std::env::var_os(name).map(PathBuf::from).filter(|p| p.is_absolute()).unwrap_or_else(|| home.join(default)).join("mash")
+}
+
+pub(crate) fn state_dir(home: &Path) -> PathBuf {
+    base("XDG_STATE_HOME", home, ".local/state")
+}
+
+/// The file `name` in the state directory.
+pub(crate) fn state_file(home: &Path, name: &str) -> PathBuf {
+    state_dir(home).join(name)
+}
+
+/// Create the directory `file` goes in, if needed.
+pub(crate) fn create_parent(file: &Path) -> std::io::Result<()> {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// Move files left by older versions of mash into the state directory,
+/// unless a file is already there. Before this, history was written to
+/// `.mash_history` in whatever directory the shell was started from.
+pub(crate) fn migrate(home: &Path) {
+    let mut legacy: Vec<(PathBuf, &str)> = Vec::new();
+    if let Ok(cwd) = std::env::current_dir() {
+        legacy.push((cwd.join(".mash_history"), "history"));
+    }
+    legacy.push((home.join(".mash_history"), "history"));
+    legacy.push((home.join(".mash/allowed_env"), "allowed_env"));
+    for (old, name) in legacy {
+        let new = state_file(home, name);
+        if !old.is_file() || new.exists() {
+            continue;
+        }
+        let moved = create_parent(&new).and_then(|()| match std::fs::rename(&old, &new) {
+            Ok(()) => Ok(()),
+            // across file systems, copy instead
+            Err(_) => std::fs::copy(&old, &new).and_then(|_| std::fs::remove_file(&old)),
+        });
+        match moved {
+            Ok(()) => tracing::info!("moved {} to {}", old.display(), new.display()),
+            Err(e) => error::warn(format!("{}: {}", old.display(), error::describe_io(&e))),
+        }
+    }
+}
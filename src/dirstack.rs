@@ -0,0 +1,178 @@
+//! The directory stack: `pushd` puts directories on it, `popd` returns
+//! along it and `dirs` shows it. Entries are numbered from 0, the working
+//! directory, as `dirs -v` lists them; `+n` counts from that end and `-n`
+//! from the other. With `set -o autopushd` (zsh's AUTO_PUSHD) every `cd`
+//! pushes the directory it leaves, so there is always somewhere to go back
+//! to.
+
+use std::{io::Write, path::PathBuf};
+
+use crate::{Builtin, Io, Shell, ShellError};
+
+impl Shell {
+    /// Print the working directory followed by the stack, as `dirs` does.
+    pub(crate) fn print_dir_stack(&self, out: &mut dyn Write) {
+        let dirs: Vec<_> = self.dir_entries().iter().map(|dir| self.tilde(dir)).collect();
+        let _ = writeln!(out, "{}", dirs.join(" "));
+    }
+
+    /// The working directory and then the stack, from the top.
+    fn dir_entries(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.path).chain(self.dir_stack.iter().rev()).cloned().collect()
+    }
+
+    /// Make the stack hold `entries` after the working directory.
+    fn set_dir_entries(&mut self, entries: &[PathBuf]) {
+        self.dir_stack = entries[1..].iter().rev().cloned().collect();
+    }
+}
+
+/// The entry `+n` or `-n` names in a stack of `len` entries, or `None` if
+/// `arg` is not of that form.
+fn entry_index(builtin: &str, arg: &str, len: usize) -> Option<Result<usize, ShellError>> {
+    let (from_top, n) = match arg.split_at_checked(1)? {
+        ("+", n) => (true, n),
+        ("-", n) => (false, n),
+        _ => return None,
+    };
+    let n: usize = n.parse().ok()?;
+    if n >= len {
+        return Some(Err(ShellError::Failed(format!("{}: {}: directory stack index out of range", builtin, arg))));
+    }
+    Some(Ok(if from_top { n } else { len - 1 - n }))
+}
+
+pub(crate) struct Pushd;
+
+impl Builtin for Pushd {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "pushd [dir | +n | -n]"
+    }
+
+    /// Push the working directory and change to `dir`; rotate the stack so
+    /// entry `n` is on top; or with no argument swap the top two entries.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let mut entries = shell.dir_entries();
+        match args {
+            [] => {
+                if entries.len() < 2 {
+                    return Err(ShellError::Failed(String::from("pushd: no other directory")));
+                }
+                let target = entries[1].clone();
+                shell.change_dir(&target.display().to_string(), target)?;
+                entries.swap(0, 1);
+                shell.set_dir_entries(&entries);
+            }
+            [arg] => match entry_index("pushd", arg, entries.len()) {
+                Some(index) => {
+                    let index = index?;
+                    let target = entries[index].clone();
+                    shell.change_dir(&target.display().to_string(), target)?;
+                    entries.rotate_left(index);
+                    shell.set_dir_entries(&entries);
+                }
+                None => {
+                    let target = match shell.search_cdpath(arg) {
+                        Some((target, _)) => target,
+                        None => shell.path.join(arg),
+                    };
+                    let from = shell.change_dir(arg, target)?;
+                    shell.dir_stack.push(from);
+                }
+            },
+            _ => return Err(ShellError::Usage(String::from("pushd: too many arguments"))),
+        }
+        shell.print_dir_stack(io.stdout);
+        Ok(0)
+    }
+}
+
+pub(crate) struct Popd;
+
+impl Builtin for Popd {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "popd [+n | -n]"
+    }
+
+    /// Remove the top entry and change to the next, or remove entry `n`.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let mut entries = shell.dir_entries();
+        if entries.len() < 2 {
+            return Err(ShellError::Failed(String::from("popd: directory stack empty")));
+        }
+        let index = match args {
+            [] => 0,
+            [arg] => entry_index("popd", arg, entries.len()).unwrap_or_else(|| Err(ShellError::Usage(format!("popd: {}: invalid argument", arg))))?,
+            _ => return Err(ShellError::Usage(String::from("popd: too many arguments"))),
+        };
+        if index == 0 {
+            // the entry is only removed once the directory could be entered
+            let target = entries[1].clone();
+            shell.change_dir(&target.display().to_string(), target)?;
+            shell.dir_stack.pop();
+        } else {
+            entries.remove(index);
+            shell.set_dir_entries(&entries);
+        }
+        shell.print_dir_stack(io.stdout);
+        Ok(0)
+    }
+}
+
+pub(crate) struct Dirs;
+
+impl Builtin for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "dirs [-clpv]"
+    }
+
+    /// Show the stack on one line, or with `-p` one entry per line and with
+    /// `-v` numbered. `-l` shows home as it is rather than as `~`, and `-c`
+    /// empties the stack.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (mut clear, mut long, mut lines, mut numbered) = (false, false, false, false);
+        for arg in args {
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                return Err(ShellError::Usage(format!("dirs: {}: invalid argument", arg)));
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'c' => clear = true,
+                    'l' => long = true,
+                    'p' => lines = true,
+                    'v' => numbered = true,
+                    _ => return Err(ShellError::Usage(format!("dirs: -{}: invalid option", flag))),
+                }
+            }
+        }
+        if clear {
+            shell.dir_stack.clear();
+            return Ok(0);
+        }
+        let dirs: Vec<_> = shell.dir_entries().iter().map(|dir| if long { dir.display().to_string() } else { shell.tilde(dir) }).collect();
+        if numbered {
+            for (i, dir) in dirs.iter().enumerate() {
+                let _ = writeln!(io.stdout, "{:2}  {}", i, dir);
+            }
+        } else if lines {
+            for dir in &dirs {
+                let _ = writeln!(io.stdout, "{}", dir);
+            }
+        } else {
+            let _ = writeln!(io.stdout, "{}", dirs.join(" "));
+        }
+        Ok(0)
+    }
+}
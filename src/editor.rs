@@ -0,0 +1,284 @@
+//! Line editor integration: the rustyline helper, vi-mode tracking and
+//! `bind`. The helper highlights the line with [`highlight`](crate::highlight).
+
+use std::{borrow::Cow, cell::Cell, rc::Rc, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+
+use rustyline::{
+    Anchor, At, Cmd, ConditionalEventHandler, EditMode, Event, EventContext, Helper, InputMode, KeyCode, KeyEvent, Modifiers, Movement, RepeatCount, Word,
+    Context, completion::{Completer, Pair}, hint::{Hinter, HistoryHinter}, highlight::Highlighter, validate::Validator,
+};
+
+use crate::{Builtin, Io, Shell, ShellError, complete, highlight::{self, DIM, RESET}, plugin::Plugin, term};
+
+/// Prompt prefix shown while the vi editor is in insert mode.
+pub const VI_INSERT_INDICATOR: &str = "[I] ";
+/// Replacement for [`VI_INSERT_INDICATOR`] while in normal (command) mode.
+/// Both indicators must have the same width.
+pub const VI_NORMAL_INDICATOR: &str = "[N] ";
+
+/// Line editor helper. The prompt highlighter appends the OSC 133
+/// end-of-prompt mark and swaps in the current vi-mode indicator without
+/// affecting the prompt width.
+pub struct MashHelper {
+    vi_normal: Arc<AtomicBool>,
+    shown_normal: Cell<bool>,
+    /// Plugins asked for completions, in order.
+    plugins: Vec<Rc<dyn Plugin>>,
+    completions: complete::Shared,
+    /// Whether the line is highlighted and suggestions are shown.
+    highlighting: Cell<bool>,
+}
+
+impl MashHelper {
+    pub(crate) fn new(plugins: Vec<Rc<dyn Plugin>>, completions: complete::Shared) -> Self {
+        Self {
+            vi_normal: Arc::new(AtomicBool::new(false)),
+            shown_normal: Cell::new(false),
+            plugins,
+            completions,
+            highlighting: Cell::new(true),
+        }
+    }
+
+    /// Event handler that keeps this helper informed of vi mode changes.
+    pub fn vi_tracker(&self) -> ViModeTracker {
+        ViModeTracker { normal: self.vi_normal.clone() }
+    }
+
+    /// Every new line starts out in insert mode.
+    pub fn reset_vi_mode(&self) {
+        self.vi_normal.store(false, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_highlighting(&self, enable: bool) {
+        self.highlighting.set(enable);
+    }
+}
+
+impl Helper for MashHelper {}
+
+impl Completer for MashHelper {
+    type Candidate = Pair;
+
+    /// Plugins get the first say, then the shell's own completions apply.
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if let Some((start, words)) = self.plugins.iter().find_map(|p| p.complete(line, pos)) {
+            return Ok((start, words.into_iter().map(|w| Pair { display: w.clone(), replacement: w }).collect()));
+        }
+        Ok(self.completions.borrow().complete(line, pos))
+    }
+}
+
+impl Hinter for MashHelper {
+    type Hint = String;
+
+    /// The rest of the latest history entry starting with the line, once
+    /// the cursor is at its end.
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        if !self.highlighting.get() {
+            return None;
+        }
+        HistoryHinter {}.hint(line, pos, ctx)
+    }
+}
+
+impl Validator for MashHelper {}
+
+impl Highlighter for MashHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _default: bool) -> Cow<'b, str> {
+        let normal = self.vi_normal.load(Ordering::Relaxed);
+        self.shown_normal.set(normal);
+        match prompt.strip_prefix(VI_INSERT_INDICATOR) {
+            Some(rest) if normal => Cow::Owned(format!("{}{}{}", VI_NORMAL_INDICATOR, rest, term::PROMPT_END)),
+            _ => Cow::Owned(format!("{}{}", prompt, term::PROMPT_END)),
+        }
+    }
+
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !self.highlighting.get() {
+            return Cow::Borrowed(line);
+        }
+        let completions = self.completions.borrow();
+        Cow::Owned(highlight::highlight(line, |name| highlight::is_command(&completions.names, &completions.programs, name)))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{}{}{}", DIM, hint, RESET))
+    }
+
+    // rustyline only redraws the prompt on a full refresh, so ask for one
+    // whenever the indicator on screen is stale, and redraw the line on
+    // every change while it is highlighted, since a word's colour can
+    // change with any character
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        self.highlighting.get() || self.vi_normal.load(Ordering::Relaxed) != self.shown_normal.get()
+    }
+}
+
+/// Normal-mode keys that switch the editor back into insert mode.
+const INSERT_KEYS: &str = "aAiIsSCR";
+
+/// Watches every key press in vi mode and records which input mode the
+/// editor is about to switch to. It never overrides the default binding.
+pub struct ViModeTracker {
+    normal: Arc<AtomicBool>,
+}
+
+impl ConditionalEventHandler for ViModeTracker {
+    fn handle(&self, evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if ctx.mode() != EditMode::Vi {
+            return None;
+        }
+        if let Some(key) = evt.get(0) {
+            match (ctx.input_mode(), key.0, key.1) {
+                (InputMode::Insert | InputMode::Replace, KeyCode::Esc, _) => self.normal.store(true, Ordering::Relaxed),
+                // Alt-<key> leaves insert mode and applies <key> as a command
+                (InputMode::Insert | InputMode::Replace, KeyCode::Char(c), Modifiers::ALT) => {
+                    self.normal.store(!INSERT_KEYS.contains(c), Ordering::Relaxed)
+                }
+                (InputMode::Command, KeyCode::Char(c), Modifiers::NONE) if INSERT_KEYS.contains(c) => {
+                    self.normal.store(false, Ordering::Relaxed)
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Parse a key such as `ctrl-r`, `alt-f`, `tab`, `up` or `f5`. Modifiers
+/// are separated by `-` and may be combined, as in `ctrl-alt-x`.
+pub fn parse_key(key: &str) -> Option<KeyEvent> {
+    let mut mods = Modifiers::NONE;
+    let mut rest = key;
+    while let Some((modifier, tail)) = rest.split_once('-').filter(|(_, tail)| !tail.is_empty()) {
+        mods |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "c" => Modifiers::CTRL,
+            "alt" | "meta" | "m" => Modifiers::ALT,
+            "shift" | "s" => Modifiers::SHIFT,
+            _ => return None,
+        };
+        rest = tail;
+    }
+    let code = match rest.to_ascii_lowercase().as_str() {
+        "tab" => KeyCode::Tab,
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "insert" => KeyCode::Insert,
+        name if name.len() > 1 && name.starts_with('f') => KeyCode::F(name[1..].parse().ok()?),
+        _ => {
+            let mut chars = rest.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            return Some(KeyEvent::new(c, mods));
+        }
+    };
+    Some(KeyEvent(code, mods))
+}
+
+/// The editing command with the readline name `name`, such as
+/// `backward-kill-word`.
+pub fn parse_command(name: &str) -> Option<Cmd> {
+    Some(match name {
+        "abort" => Cmd::Abort,
+        "accept-line" => Cmd::AcceptLine,
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "end-of-history" => Cmd::EndOfHistory,
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "upcase-word" => Cmd::UpcaseWord,
+        "downcase-word" => Cmd::DowncaseWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" => Cmd::Complete,
+        "complete-backward" => Cmd::CompleteBackward,
+        "complete-hint" => Cmd::CompleteHint,
+        "end-of-file" => Cmd::EndOfFile,
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "previous-history" => Cmd::PreviousHistory,
+        "next-history" => Cmd::NextHistory,
+        "interrupt" => Cmd::Interrupt,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "undo" => Cmd::Undo(1),
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        "suspend" => Cmd::Suspend,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "backward-kill-line" | "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+        "unix-word-rubout" => Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+        _ => return None,
+    })
+}
+
+/// `bind`, for key bindings made from the rc file or the prompt. They apply
+/// from the next prompt on.
+pub(crate) struct Bind;
+
+impl Builtin for Bind {
+    fn name(&self) -> &'static str {
+        "bind"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "bind [key command | -r key ...]"
+    }
+
+    /// Bind `key` to the editor command with the readline name `command`,
+    /// or with `-r` undo the bindings of keys. Without arguments, the keys
+    /// bound are listed.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let parse = |key: &str| parse_key(key).ok_or_else(|| ShellError::Failed(format!("bind: {}: unknown key", key)));
+        match args {
+            [] => {
+                for (_, key, command) in &shell.bindings {
+                    let _ = writeln!(io.stdout, "bind {} {}", key, command);
+                }
+                return Ok(0);
+            }
+            [flag, keys @ ..] if flag == "-r" => {
+                for key in keys {
+                    let event = parse(key)?;
+                    shell.bindings.retain(|(bound, ..)| *bound != event);
+                }
+            }
+            [key, command] => {
+                let event = parse(key)?;
+                if parse_command(command).is_none() {
+                    return Err(ShellError::Failed(format!("bind: {}: unknown editor command", command)));
+                }
+                shell.bindings.retain(|(bound, ..)| *bound != event);
+                shell.bindings.push((event, key.clone(), command.clone()));
+            }
+            _ => return Err(ShellError::Usage(format!("bind: usage: {}", self.synopsis()))),
+        }
+        shell.bindings_changed = true;
+        Ok(0)
+    }
+}
@@ -0,0 +1,168 @@
+//! Per-directory environment files. When an interactive shell enters a
+//! directory with a `.mash.env` (or `.envrc`) in it or in one of its parents,
+//! the variables the file assigns are exported until the shell leaves that
+//! directory tree, at which point their old values come back.
+//!
+//! The file holds `NAME=value` lines, optionally preceded by `export`, with
+//! values expanded as in an assignment. Since it is found just by changing
+//! directory, a file only takes effect once `env allow` has been run for it,
+//! and again after every change to it.
+
+use std::{ffi::OsString, io::Write, path::{Path, PathBuf}};
+
+use crate::{Builtin, Io, Shell, ShellError, dirs, error, exec::find_program, parser::is_name};
+
+const FILE_NAMES: &[&str] = &[".mash.env", ".envrc"];
+
+/// The environment file in effect.
+pub(crate) struct Active {
+    file: PathBuf,
+    /// The variables it set and their values before.
+    saved: Vec<(String, Option<OsString>)>,
+}
+
+/// The list of allowed files, each with a hash of the contents allowed.
+fn allowed_list(home: &Path) -> PathBuf {
+    dirs::state_file(home, "allowed_env")
+}
+
+/// The environment file for `dir`: the first found there or in a parent.
+fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().flat_map(|d| FILE_NAMES.iter().map(move |name| d.join(name))).find(|f| f.is_file())
+}
+
+/// FNV-1a, which unlike the standard library's hasher is stable across
+/// releases, so the allowed list stays valid.
+fn hash(contents: &[u8]) -> u64 {
+    contents.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn entry(file: &Path) -> Option<String> {
+    let contents = std::fs::read(file).ok()?;
+    Some(format!("{:016x} {}", hash(&contents), file.display()))
+}
+
+impl Shell {
+    /// Load or unload environment files after the working directory changes.
+    pub(crate) fn enter_directory(&mut self) {
+        if !self.interactive || self.options.posix || self.options.restricted {
+            return;
+        }
+        let dir = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        let file = find(&dir);
+        if self.env_file.as_ref().map(|a| &a.file) == file.as_ref() {
+            return;
+        }
+        self.unload_env_file();
+        let Some(file) = file else {
+            return;
+        };
+        if self.env_file_allowed(&file) {
+            self.load_env_file(file);
+        } else {
+            error::warn(format!("{} is not allowed; run `env allow' to load it", file.display()));
+        }
+    }
+
+    fn env_file_allowed(&self, file: &Path) -> bool {
+        let Some(entry) = entry(file) else {
+            return false;
+        };
+        std::fs::read_to_string(allowed_list(&self.home)).is_ok_and(|list| list.lines().any(|line| line == entry))
+    }
+
+    fn load_env_file(&mut self, file: PathBuf) {
+        let source = match std::fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(e) => {
+                error::warn(format!("{}: {}", file.display(), error::describe_io(&e)));
+                return;
+            }
+        };
+        let mut saved = Vec::new();
+        for (n, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let assignment = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            let value = match assignment.split_once('=').filter(|(name, _)| is_name(name)) {
+                Some((name, value)) => self.expand_word(value).map(|value| (name, value)),
+                None => Err(ShellError::Failed(String::from("expected NAME=value"))),
+            };
+            match value {
+                Ok((name, value)) => {
+                    if !saved.iter().any(|(saved, _)| saved == name) {
+                        saved.push((name.to_string(), std::env::var_os(name)));
+                    }
+                    std::env::set_var(name, value);
+                }
+                Err(e) => eprintln!("{}: line {}: {}", file.display(), n + 1, e),
+            }
+        }
+        self.env_file = Some(Active { file, saved });
+    }
+
+    fn unload_env_file(&mut self) {
+        let Some(active) = self.env_file.take() else {
+            return;
+        };
+        for (name, value) in active.saved.into_iter().rev() {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+
+    /// Add or remove the environment file for the working directory in the
+    /// allowed list, then load or unload it.
+    fn allow_env_file(&mut self, allow: bool) -> Result<(), ShellError> {
+        let dir = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        let file = find(&dir).ok_or_else(|| ShellError::Failed(String::from("env: no .mash.env here or in a parent directory")))?;
+        let list = allowed_list(&self.home);
+        let current = std::fs::read_to_string(&list).unwrap_or_default();
+        let suffix = format!(" {}", file.display());
+        let mut lines: Vec<String> = current.lines().filter(|line| !line.ends_with(&suffix)).map(String::from).collect();
+        if allow {
+            lines.extend(entry(&file));
+        }
+        dirs::create_parent(&list).map_err(|e| ShellError::io(list.display().to_string(), e))?;
+        let mut out = std::fs::File::create(&list).map_err(|e| ShellError::io(list.display().to_string(), e))?;
+        for line in lines {
+            writeln!(out, "{}", line).map_err(|e| ShellError::io(list.display().to_string(), e))?;
+        }
+        self.unload_env_file();
+        if allow {
+            self.load_env_file(file);
+        }
+        Ok(())
+    }
+}
+
+/// `env allow` and `env deny` trust or distrust the environment file for
+/// the working directory; anything else runs the `env` program.
+pub(crate) struct Env;
+
+impl Builtin for Env {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "env allow | deny"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        match args.first().map(String::as_str) {
+            Some("allow") => shell.allow_env_file(true)?,
+            Some("deny") => shell.allow_env_file(false)?,
+            _ => {
+                let program = find_program("env", shell.var("PATH").as_deref(), &shell.path)?;
+                let argv: Vec<String> = std::iter::once(String::from("env")).chain(args.iter().cloned()).collect();
+                return shell.execute_external(&program, argv, &[]);
+            }
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,61 @@
+//! The errors a command can fail with, and the exit status each one leaves
+//! in `$?`.
+
+use crate::parser::ParseError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShellError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("{0}: command not found")]
+    CommandNotFound(String),
+    /// The program exists but cannot be executed.
+    #[error("{0}: Permission denied")]
+    NotExecutable(String),
+    /// A builtin was given arguments it does not accept.
+    #[error("{0}")]
+    Usage(String),
+    /// A system call failed.
+    #[error("{context}: {}", source.desc())]
+    Sys { context: String, source: nix::Error },
+    #[error("{context}: {}", describe_io(source))]
+    Io { context: String, source: std::io::Error },
+    /// Any other failure, such as expanding an unset variable under `set -u`.
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// An I/O error as the C library describes it, without Rust's
+/// `(os error N)` suffix.
+pub(crate) fn describe_io(error: &std::io::Error) -> String {
+    match error.raw_os_error() {
+        Some(code) => nix::errno::Errno::from_i32(code).desc().to_string(),
+        None => error.to_string(),
+    }
+}
+
+/// Print a message for the user on stderr, prefixed by the shell's name as
+/// other shells do. The log is for diagnosing mash itself.
+pub(crate) fn warn(message: impl std::fmt::Display) {
+    eprintln!("mash: {}", message);
+}
+
+impl ShellError {
+    /// The exit status a command failing with this error has.
+    pub fn status(&self) -> i32 {
+        match self {
+            ShellError::Parse(_) | ShellError::Usage(_) => 2,
+            ShellError::CommandNotFound(_) => 127,
+            ShellError::NotExecutable(_) => 126,
+            ShellError::Sys { .. } | ShellError::Io { .. } | ShellError::Failed(_) => 1,
+        }
+    }
+
+    pub(crate) fn sys(context: impl Into<String>, source: nix::Error) -> Self {
+        ShellError::Sys { context: context.into(), source }
+    }
+
+    pub(crate) fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        ShellError::Io { context: context.into(), source }
+    }
+}
@@ -0,0 +1,669 @@
+//! Tree-walking interpreter for the AST produced by the parser, and the
+//! fork/exec machinery for external commands.
+
+use std::{ffi::CString, io::Write, os::{fd::{AsRawFd, FromRawFd, OwnedFd}, unix::fs::PermissionsExt}, path::{Path, PathBuf}, rc::Rc};
+
+use nix::{errno::Errno, unistd::{ForkResult, fork, execv, chdir, dup2, pipe}};
+
+use crate::{Shell, ShellError, builtins::Io, error, redirect::Redirected, parser::{AndOr, Case, Command, Connector, For, If, Node, Pipeline, While}, pattern, shell::read_stdin_line, signals};
+
+/// Non-local control flow requested by `break`, `continue` or `return`,
+/// unwound by the enclosing loops, function or sourced file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Normal,
+    /// Leave this many enclosing loops.
+    Break(usize),
+    /// Leave this many enclosing loops minus one, then start the next
+    /// iteration of the outermost one.
+    Continue(usize),
+    Return,
+}
+
+/// An entry in the function table.
+#[derive(Debug, Clone)]
+pub struct ShellFunction {
+    pub body: Rc<Vec<Node>>,
+    /// The file the function was defined in, empty if it was not read from
+    /// a file.
+    pub file: String,
+}
+
+/// A function call or sourced file in progress, recording where it was
+/// called from.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The function name, or `source`.
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+}
+
+impl Shell {
+    /// Run each node in turn, stopping early once `exit`, `return`, `break` or
+    /// `continue` has been called or the user pressed Ctrl-C. Nothing runs
+    /// under `set -n`.
+    pub(crate) fn run_list(&mut self, list: &[Node]) {
+        for node in list {
+            if self.options.noexec && !self.interactive {
+                break;
+            }
+            // those of a loop's words or a compound command's redirections
+            let substitutions = self.process_substitutions.len();
+            self.run_node(node);
+            self.finish_process_substitutions(substitutions);
+            self.run_signal_traps();
+            if self.unwinding() {
+                break;
+            }
+        }
+    }
+
+    fn unwinding(&self) -> bool {
+        self.exiting || self.flow != Flow::Normal || signals::interrupted()
+    }
+
+    /// Run a function body with `args` as its positional parameters. Loops in
+    /// the caller are not visible to `break` and `continue` in the function.
+    pub(crate) fn call_function(&mut self, name: &str, function: &ShellFunction, args: Vec<String>) {
+        let saved = std::mem::replace(&mut self.positional, args);
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        self.push_frame(name, function.file.clone());
+        self.run_list(&function.body);
+        self.pop_frame();
+        self.function_depth -= 1;
+        if self.flow == Flow::Return {
+            self.flow = Flow::Normal;
+        }
+        self.loop_depth = loop_depth;
+        self.positional = saved;
+    }
+
+    /// Enter a function or sourced file, whose commands come from `file`.
+    pub(crate) fn push_frame(&mut self, name: &str, file: String) {
+        let caller = Frame { name: name.to_string(), file: std::mem::replace(&mut self.file, file), line: self.lineno };
+        self.frames.push(caller);
+    }
+
+    /// Return to the caller of the innermost function or sourced file.
+    pub(crate) fn pop_frame(&mut self) {
+        if let Some(caller) = self.frames.pop() {
+            self.file = caller.file;
+            self.lineno = caller.line;
+        }
+    }
+
+    /// Print an error message on stderr, prefixed with the file and line it
+    /// happened on when commands are being read from a file, or else with
+    /// the shell's name.
+    pub(crate) fn error(&self, message: impl std::fmt::Display) {
+        if self.file.is_empty() {
+            error::warn(message);
+        } else {
+            eprintln!("{}: line {}: {}", self.file, self.lineno, message);
+        }
+    }
+
+    /// Report a failed command and set `$?` to the status its error implies.
+    /// At the prompt, a command that was not found may get a suggestion,
+    /// unless one was offered as a correction already.
+    pub(crate) fn report(&mut self, error: ShellError) {
+        self.error(&error);
+        if let ShellError::CommandNotFound(name) = &error {
+            if let Some(suggestion) = (self.interactive && !self.correcting()).then(|| self.suggest_command(name)).flatten() {
+                error::warn(format!("did you mean '{}'?", suggestion));
+            }
+        }
+        self.last_status = error.status();
+    }
+
+    /// With `set -x`, show a command about to run on stderr, after its
+    /// expansion and prefixed by the expanded `PS4`.
+    pub(crate) fn trace(&mut self, assignments: &[(String, String)], words: &[String]) {
+        if !self.options.xtrace {
+            return;
+        }
+        let prefix = match self.var("PS4") {
+            Some(ps4) => self.expand_word(&ps4).unwrap_or(ps4),
+            None => String::from("+ "),
+        };
+        let assignments = assignments.iter().map(|(name, value)| format!("{}={}", name, quote(value)));
+        let line: Vec<_> = assignments.chain(words.iter().map(|w| quote(w))).collect();
+        eprintln!("{}{}", prefix, line.join(" "));
+    }
+
+    /// Run a simple command, dispatching to builtins before external programs.
+    /// Its process substitutions end with it.
+    pub(crate) fn execute(&mut self, command: &Command) -> Result<(), ShellError> {
+        let substitutions = self.process_substitutions.len();
+        let result = self.execute_simple(command);
+        self.finish_process_substitutions(substitutions);
+        result
+    }
+
+    fn execute_simple(&mut self, command: &Command) -> Result<(), ShellError> {
+        let _span = tracing::debug_span!("exec", line = command.line, %command).entered();
+        let in_place = std::mem::take(&mut self.exec_in_place);
+        self.substitution_status = None;
+        let words = self.expand_words(&command.words)?;
+        // undone when this returns, unless `exec` keeps them
+        let redirected = self.redirect(&command.redirects)?;
+        if words.is_empty() {
+            // only assignments, or the whole command expanded to nothing
+            for assignment in &command.assignments {
+                self.assign(assignment)?;
+            }
+            let assigned: Vec<_> = command.assignments.iter().map(|a| (a.name.clone(), self.var(&a.name).unwrap_or_default())).collect();
+            self.trace(&assigned, &[]);
+            // `x=$(cmd)` has the status of `cmd`
+            self.last_status = self.substitution_status.take().unwrap_or(0);
+            return Ok(());
+        }
+        self.declare_arrays = command.arrays.iter().map(|(_, a)| a.clone()).collect();
+        if command.assignments.is_empty() {
+            self.trace(&[], &words);
+            let result = self.execute_words(words, &[], in_place);
+            self.declare_arrays.clear();
+            self.keep_redirections(redirected);
+            return result;
+        }
+        // assignments before a command only last for that command
+        let saved: Vec<_> = command.assignments.iter().map(|a| (a.name.clone(), self.vars.get(&a.name).cloned(), std::env::var_os(&a.name))).collect();
+        let mut result = command.assignments.iter().try_for_each(|a| self.assign(a));
+        if result.is_ok() {
+            let env: Vec<_> = command.assignments.iter().map(|a| (a.name.clone(), self.var(&a.name).unwrap_or_default())).collect();
+            self.trace(&env, &words);
+            result = self.execute_words(words, &env, in_place);
+        }
+        self.declare_arrays.clear();
+        for (name, value, exported) in saved.into_iter().rev() {
+            if let Some(exported) = exported {
+                std::env::set_var(&name, exported);
+            }
+            match value {
+                Some(value) => self.vars.insert(name, value),
+                None => self.vars.remove(&name),
+            };
+        }
+        self.keep_redirections(redirected);
+        result
+    }
+
+    fn keep_redirections(&mut self, mut redirected: Redirected) {
+        if std::mem::take(&mut self.keep_redirections) {
+            redirected.keep();
+        }
+    }
+
+    /// Run an expanded command, `words` being its name and arguments. They
+    /// become the argument vector of an external program without being
+    /// copied. `env` holds variables to export to an external program, which
+    /// replaces the shell process if `in_place` is set.
+    fn execute_words(&mut self, mut words: Vec<String>, env: &[(String, String)], in_place: bool) -> Result<(), ShellError> {
+        let name = words[0].as_str();
+        let args = &words[1..];
+        if let Some(builtin) = self.builtins.get(name).filter(|_| self.builtin_enabled(name)) {
+            let _span = tracing::debug_span!("builtin", name).entered();
+            let (mut stdout, mut stderr) = (std::io::stdout(), std::io::stderr());
+            let mut io = Io { stdout: &mut stdout, stderr: &mut stderr };
+            let result = builtin.run(self, args, &mut io);
+            if result.is_err() && self.fatal_builtin_error(name) {
+                self.exiting = true;
+            }
+            self.last_status = result?;
+            Ok(())
+        } else if let Some(function) = self.functions.get(name).cloned() {
+            let name = words.remove(0);
+            self.call_function(&name, &function, words);
+            Ok(())
+        } else if self.autoload(name) && self.functions.contains_key(name) {
+            self.execute_words(words, env, in_place)
+        } else {
+            let program = match self.hashed_program(name) {
+                Err(error @ ShellError::CommandNotFound(_)) => match self.correct_command(name) {
+                    Some(corrected) => {
+                        words[0] = corrected;
+                        return self.execute_words(words, env, in_place);
+                    }
+                    None => return Err(error),
+                },
+                result => result?,
+            };
+            let _span = tracing::debug_span!("external", program = %program.display()).entered();
+            if in_place {
+                return exec_in_place(&program, words, env, &self.path);
+            }
+            self.last_status = self.execute_external(&program, words, env)?;
+            tracing::debug!(status = self.last_status, "exited");
+            Ok(())
+        }
+    }
+
+    /// Run the condition of an `if` or a loop, which may fail without
+    /// triggering `set -e`.
+    fn run_condition(&mut self, condition: &[Node]) {
+        self.condition_depth += 1;
+        self.run_list(condition);
+        self.condition_depth -= 1;
+    }
+
+    /// Called after each part of a loop runs, consuming any `break` or
+    /// `continue` aimed at this loop. Returns whether the loop carries on.
+    fn next_iteration(&mut self) -> bool {
+        if signals::interrupted() {
+            self.last_status = 130;
+            return false;
+        }
+        if self.exiting {
+            return false;
+        }
+        match self.flow {
+            Flow::Normal => true,
+            Flow::Continue(1) => {
+                self.flow = Flow::Normal;
+                true
+            }
+            Flow::Continue(n) => {
+                self.flow = Flow::Continue(n - 1);
+                false
+            }
+            Flow::Break(n) => {
+                self.flow = if n > 1 { Flow::Break(n - 1) } else { Flow::Normal };
+                false
+            }
+            Flow::Return => false,
+        }
+    }
+
+    pub(crate) fn run_node(&mut self, node: &Node) {
+        let result = match node {
+            Node::Simple(command) => {
+                // only the command itself may replace the process, not what
+                // an alias or trap runs
+                let in_place = std::mem::take(&mut self.exec_in_place);
+                if self.run_alias(command) {
+                    return;
+                }
+                self.lineno = command.line;
+                if self.debugger.is_some() {
+                    self.debug_stop(command);
+                    if self.exiting {
+                        return;
+                    }
+                }
+                self.run_trap("DEBUG");
+                self.exec_in_place = in_place;
+                self.execute(command)
+            }
+            Node::If(clause) => {
+                self.run_if(clause);
+                return;
+            }
+            Node::For(lp) => self.run_for(lp),
+            Node::Select(lp) => self.run_select(lp),
+            Node::While(lp) => {
+                self.run_while(lp);
+                return;
+            }
+            Node::Case(case) => self.run_case(case),
+            Node::Function(function) => {
+                self.functions.insert(function.name.clone(), ShellFunction { body: function.body.clone(), file: self.file.clone() });
+                self.last_status = 0;
+                return;
+            }
+            Node::Pipeline(pipeline) => self.run_pipeline(pipeline),
+            Node::AndOr(and_or) => {
+                self.run_and_or(and_or);
+                return;
+            }
+            Node::Background(node) => self.run_background(node),
+            Node::Subshell(list) => self.run_subshell(list, node),
+            Node::Group(list) => {
+                self.run_list(list);
+                return;
+            }
+            // like a condition, a negated pipeline may fail without `set -e`
+            Node::Not(node) => {
+                self.run_condition(std::slice::from_ref(&**node));
+                if !self.exiting {
+                    self.last_status = i32::from(self.last_status == 0);
+                }
+                return;
+            }
+            Node::Timed(node, posix) => {
+                self.run_timed(node, *posix);
+                return;
+            }
+            Node::Arith(expression) => self.run_arith(expression),
+            Node::Redirected(node, redirects) => match self.redirect(redirects) {
+                Ok(redirected) => {
+                    self.run_node(node);
+                    drop(redirected);
+                    return;
+                }
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            self.report(e);
+        }
+        if self.last_status != 0 && self.condition_depth == 0 {
+            self.run_trap("ERR");
+            if self.options.errexit {
+                self.exiting = true;
+            }
+        }
+    }
+
+    /// Run the pipelines of an `&&`/`||` list left to right. A failure that
+    /// only decides whether the rest runs does not fire `ERR` or `set -e`;
+    /// only the last pipeline's can.
+    fn run_and_or(&mut self, and_or: &AndOr) {
+        self.run_condition(std::slice::from_ref(&*and_or.first));
+        for (i, (connector, node)) in and_or.rest.iter().enumerate() {
+            if self.unwinding() {
+                return;
+            }
+            if (self.last_status == 0) != (*connector == Connector::And) {
+                continue;
+            }
+            match i + 1 == and_or.rest.len() {
+                true => self.run_node(node),
+                false => self.run_condition(std::slice::from_ref(node)),
+            }
+        }
+    }
+
+    /// Run each command of a pipeline in a child process, connected by
+    /// pipes, and wait for them all. The status is that of the last one.
+    fn run_pipeline(&mut self, pipeline: &Pipeline) -> Result<(), ShellError> {
+        // output buffered in the shell must not be written again by each child
+        let _ = std::io::stdout().flush();
+        let mut children = Vec::with_capacity(pipeline.commands.len());
+        // the read end of the pipe from the previous command
+        let mut input: Option<OwnedFd> = None;
+        let mut result = Ok(());
+        for (i, node) in pipeline.commands.iter().enumerate() {
+            let (read, write) = if i + 1 < pipeline.commands.len() {
+                match pipe() {
+                    // SAFETY: both descriptors were just created and are owned by nothing else
+                    Ok((read, write)) => unsafe { (Some(OwnedFd::from_raw_fd(read)), Some(OwnedFd::from_raw_fd(write))) },
+                    Err(e) => {
+                        result = Err(ShellError::sys("pipe", e));
+                        break;
+                    }
+                }
+            } else {
+                (None, None)
+            };
+            match unsafe { fork() } {
+                Ok(ForkResult::Parent { child }) => {
+                    self.jobs.place(child, children.first().copied());
+                    children.push(child);
+                }
+                Ok(ForkResult::Child) => {
+                    self.jobs.enter_child(children.first().copied(), true);
+                    let redirected = input.as_ref().map_or(Ok(0), |fd| dup2(fd.as_raw_fd(), 0)).and_then(|_| write.as_ref().map_or(Ok(1), |fd| dup2(fd.as_raw_fd(), 1)));
+                    if let Err(e) = redirected {
+                        error::warn(ShellError::sys("dup2", e));
+                        std::process::exit(1);
+                    }
+                    drop((input, read, write));
+                    signals::default_pipe_signal();
+                    // a simple command execs in this process rather than forking again
+                    self.exec_in_place = matches!(node, Node::Simple(_));
+                    self.run_node(node);
+                    let _ = std::io::stdout().flush();
+                    std::process::exit(self.last_status);
+                }
+                Err(e) => {
+                    result = Err(ShellError::sys("fork", e));
+                    break;
+                }
+            }
+            // the parent closes its copy of the write end so the next
+            // command sees end of input once this one exits
+            input = read;
+        }
+        drop(input);
+        if let Some(&pgid) = children.first() {
+            self.last_status = self.wait_foreground(pgid, children, || pipeline.to_string())?;
+        }
+        result
+    }
+
+    /// Run `list` in a child process, a copy of the shell, in the foreground
+    /// as a job of its own.
+    fn run_subshell(&mut self, list: &[Node], node: &Node) -> Result<(), ShellError> {
+        let _ = std::io::stdout().flush();
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                self.jobs.place(child, None);
+                self.last_status = self.wait_foreground(child, vec![child], || node.to_string())?;
+                Ok(())
+            }
+            Ok(ForkResult::Child) => {
+                self.jobs.enter_child(None, true);
+                signals::default_pipe_signal();
+                self.run_list(list);
+                let _ = std::io::stdout().flush();
+                std::process::exit(self.last_status);
+            }
+            Err(e) => Err(ShellError::sys("fork", e)),
+        }
+    }
+
+    fn run_case(&mut self, case: &Case) -> Result<(), ShellError> {
+        let word = self.expand_word(&case.word)?;
+        for arm in &case.arms {
+            for pattern in &arm.patterns {
+                if pattern::matches(&self.expand_pattern(pattern)?, &word) {
+                    self.last_status = 0;
+                    self.run_list(&arm.body);
+                    return Ok(());
+                }
+            }
+        }
+        self.last_status = 0;
+        Ok(())
+    }
+
+    fn run_while(&mut self, lp: &While) {
+        self.loop_depth += 1;
+        let mut status = 0;
+        loop {
+            self.run_condition(&lp.condition);
+            if !self.next_iteration() {
+                break;
+            }
+            if (self.last_status == 0) == lp.until {
+                // the status of the last body run, or 0 if it never ran
+                self.last_status = status;
+                break;
+            }
+            self.run_list(&lp.body);
+            status = self.last_status;
+            if !self.next_iteration() {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+    }
+
+    fn run_for(&mut self, lp: &For) -> Result<(), ShellError> {
+        let items = match &lp.words {
+            Some(words) => self.expand_words(words)?,
+            None => self.positional.clone(),
+        };
+        self.restrict_variable(&lp.var)?;
+        self.loop_depth += 1;
+        self.last_status = 0;
+        for item in items {
+            self.set_var(&lp.var, item);
+            self.run_list(&lp.body);
+            if !self.next_iteration() {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+        Ok(())
+    }
+
+    /// Show a numbered menu of the words on stderr and read choices from stdin
+    /// until the body breaks out or input ends. Each line read is stored in
+    /// `REPLY` and the chosen word, or an empty string, in the loop variable.
+    fn run_select(&mut self, lp: &For) -> Result<(), ShellError> {
+        self.extension("select")?;
+        let items = match &lp.words {
+            Some(words) => self.expand_words(words)?,
+            None => self.positional.clone(),
+        };
+        self.restrict_variable(&lp.var)?;
+        let menu: String = items.iter().enumerate().map(|(i, item)| format!("{}) {}\n", i + 1, item)).collect();
+        self.loop_depth += 1;
+        self.last_status = 0;
+        let mut show_menu = true;
+        loop {
+            let mut stderr = std::io::stderr();
+            if show_menu {
+                let _ = stderr.write_all(menu.as_bytes());
+            }
+            let _ = write!(stderr, "{}", self.var("PS3").unwrap_or_else(|| String::from("#? ")));
+            let _ = stderr.flush();
+            let Some(reply) = read_stdin_line() else {
+                // like other shells, end of input ends the loop on a fresh line
+                let _ = writeln!(stderr);
+                break;
+            };
+            // an empty line just shows the menu again
+            show_menu = reply.trim().is_empty();
+            if show_menu {
+                continue;
+            }
+            let choice = reply.trim().parse::<usize>().ok().and_then(|n| items.get(n.wrapping_sub(1)));
+            self.set_var(&lp.var, choice.cloned().unwrap_or_default());
+            self.set_var("REPLY", reply);
+            self.run_list(&lp.body);
+            if !self.next_iteration() {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+        Ok(())
+    }
+
+    fn run_if(&mut self, clause: &If) {
+        for (condition, body) in &clause.branches {
+            self.run_condition(condition);
+            if self.unwinding() {
+                return;
+            }
+            if self.last_status == 0 {
+                self.run_list(body);
+                return;
+            }
+        }
+        match &clause.else_body {
+            Some(body) => self.run_list(body),
+            // no branch ran: the status of `if` is 0, not the failed condition's
+            None => self.last_status = 0,
+        }
+    }
+}
+
+/// Single-quote `word` if it would not read back as the same word.
+pub fn quote(word: &str) -> String {
+    if !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(c)) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Find the program `name` runs: the file itself if the name contains a
+/// slash, otherwise the first executable file of that name in `path`.
+pub fn find_program(name: &str, path: Option<&str>, workdir: &Path) -> Result<PathBuf, ShellError> {
+    if name.contains('/') {
+        let program = workdir.join(name);
+        return match std::fs::metadata(&program) {
+            Ok(meta) if meta.is_file() && meta.permissions().mode() & 0o111 != 0 => Ok(program),
+            Ok(_) => Err(ShellError::NotExecutable(name.to_string())),
+            Err(_) => Err(ShellError::CommandNotFound(name.to_string())),
+        };
+    }
+    // a matching file without execute permission is only reported if no
+    // later directory has an executable one
+    let mut found = false;
+    for dir in path.unwrap_or_default().split(':') {
+        let program = workdir.join(if dir.is_empty() { "." } else { dir }).join(name);
+        match std::fs::metadata(&program) {
+            Ok(meta) if meta.is_file() && meta.permissions().mode() & 0o111 != 0 => return Ok(program),
+            Ok(meta) if meta.is_file() => found = true,
+            _ => {}
+        }
+    }
+    Err(if found { ShellError::NotExecutable(name.to_string()) } else { ShellError::CommandNotFound(name.to_string()) })
+}
+
+impl Shell {
+    /// Run `program` in a child process, as a job of its own, and wait for
+    /// it.
+    pub(crate) fn execute_external(&mut self, program: &Path, argv: Vec<String>, env: &[(String, String)]) -> Result<i32, ShellError> {
+        // the C strings are made before forking, reusing the arguments' buffers,
+        // so the child only has to exec
+        let (cmd, argv) = c_strings(program, argv)?;
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                self.jobs.place(child, None);
+                self.wait_foreground(child, vec![child], || argv.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "))
+            }
+            Ok(ForkResult::Child) => {
+                self.jobs.enter_child(None, true);
+                exec_or_exit(&cmd, &argv, env, &self.path)
+            }
+            Err(e) => Err(ShellError::sys("fork", e)),
+        }
+    }
+}
+
+/// Replace the current process with `program`, as the last command of a
+/// process forked for a pipeline does instead of forking again.
+pub(crate) fn exec_in_place(program: &Path, argv: Vec<String>, env: &[(String, String)], workdir: &Path) -> Result<(), ShellError> {
+    let (cmd, argv) = c_strings(program, argv)?;
+    exec_or_exit(&cmd, &argv, env, workdir)
+}
+
+fn c_strings(program: &Path, argv: Vec<String>) -> Result<(CString, Vec<CString>), ShellError> {
+    let cmd = CString::new(program.as_os_str().as_encoded_bytes()).map_err(|_| ShellError::Failed(format!("{}: invalid program name", program.display())))?;
+    let argv = argv.into_iter().map(CString::new).collect::<Result<Vec<_>, _>>().map_err(|_| ShellError::Failed(format!("{}: argument contains a null byte", program.display())))?;
+    Ok((cmd, argv))
+}
+
+/// In a forked child, exec the program; the child must never return into
+/// the shell's loop. It exits 127 if the program, or the interpreter its
+/// `#!` line names, is missing and 126 if it cannot be run otherwise.
+fn exec_or_exit(program: &CString, argv: &[CString], env: &[(String, String)], workdir: &Path) -> ! {
+    let status = match exec(program, argv, env, workdir) {
+        Err(e @ ShellError::Sys { source: Errno::ENOENT, .. }) => {
+            error::warn(e);
+            127
+        }
+        Err(e) => {
+            error::warn(e);
+            126
+        }
+        Ok(()) => 126,
+    };
+    std::process::exit(status);
+}
+
+fn exec(program: &CString, argv: &[CString], env: &[(String, String)], workdir: &Path) -> Result<(), ShellError> {
+    // a working directory that has gone is no reason to exit 127
+    chdir(workdir.as_os_str()).map_err(|e| ShellError::Failed(format!("cd: {}: {}", workdir.display(), e.desc())))?;
+    signals::default_pipe_signal();
+    for (key, value) in env {
+        std::env::set_var(key, value);
+    }
+    execv(program, argv).map_err(|e| ShellError::sys(program.to_string_lossy(), e))?;
+    Ok(())
+}
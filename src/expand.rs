@@ -0,0 +1,508 @@
+//! Word expansion: tilde expansion, parameter substitution, field splitting,
+//! filename expansion and quote removal.
+//!
+//! Words arrive from the lexer with their quotes intact. An unquoted `~` at
+//! the start of a word is the home directory, and `~user` that of `user`. Text inside single
+//! quotes is literal; inside double quotes parameters are expanded but the
+//! result is not split; unquoted expansions are split into fields on the
+//! characters of `IFS`, or on blanks if it is unset. `"$@"`
+//! expands to one field per positional parameter, and `"${a[@]}"` to one
+//! field per array element. `$?` is the status of the last command, `$$` the
+//! process of the shell and `$!` that of the last background job. `$(commands)` and
+//! `` `commands` `` are replaced by the output of the commands, and split
+//! like a parameter unless quoted. Braces hold a name, special parameter or
+//! subscript; anything else in them is a bad substitution.
+//!
+//! A field with an unquoted `*`, `?` or `[` is then a pattern, replaced by
+//! the files it matches. One that matches nothing is left as it is, or
+//! removed with `set -o nullglob`.
+
+use crate::{ParseError, Shell, ShellError, lexer, parser, pattern, read};
+
+/// The result of expanding a parameter.
+enum Expansion {
+    One(String),
+    /// `$@` or `${a[@]}`, which inside double quotes produce a separate field
+    /// for each value.
+    Each(Vec<String>),
+}
+
+impl Expansion {
+    fn joined(self) -> String {
+        match self {
+            Expansion::One(value) => value,
+            Expansion::Each(values) => values.join(" "),
+        }
+    }
+}
+
+/// How the result of an expansion is used.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Split into fields, as for command arguments.
+    Fields,
+    /// A single string, as for the word of a `case` command.
+    Single,
+    /// A single pattern in which quoted characters match literally.
+    Pattern,
+}
+
+/// Collects the fields produced by expanding one word.
+struct Fields {
+    mode: Mode,
+    fields: Vec<String>,
+    current: String,
+    /// Whether `current` is a field even if empty, which is the case once
+    /// any quoted text has been seen.
+    exists: bool,
+    /// When splitting into fields, `current` as a pattern for filename
+    /// expansion, and whether it has any unquoted pattern characters.
+    pattern: String,
+    wild: bool,
+    /// The fields that are patterns, by index.
+    patterns: Vec<(usize, String)>,
+    /// The characters unquoted expansions are split on.
+    ifs: String,
+}
+
+impl Fields {
+    fn new(mode: Mode, ifs: String) -> Self {
+        Self { mode, fields: Vec::new(), current: String::new(), exists: false, pattern: String::new(), wild: false, patterns: Vec::new(), ifs }
+    }
+
+    /// Append text that is not subject to splitting.
+    fn push_quoted(&mut self, text: &str) {
+        match self.mode {
+            Mode::Pattern => escape(&mut self.current, text),
+            Mode::Fields => {
+                escape(&mut self.pattern, text);
+                self.current.push_str(text);
+            }
+            Mode::Single => self.current.push_str(text),
+        }
+        self.exists = true;
+    }
+
+    /// Append unquoted text, whose pattern characters stay active.
+    fn push_active(&mut self, text: &str) {
+        self.current.push_str(text);
+        if self.mode == Mode::Fields {
+            self.pattern.push_str(text);
+            self.wild |= text.contains(['*', '?', '[']);
+        }
+        self.exists = true;
+    }
+
+    /// Append the result of an unquoted expansion, splitting it on the
+    /// characters of `IFS`. Runs of IFS blanks separate fields and are
+    /// dropped at either end; any other IFS character ends a field, empty
+    /// or not, taking the blanks around it with it.
+    fn push_unquoted(&mut self, value: &str) {
+        if self.mode != Mode::Fields || self.ifs.is_empty() {
+            if !value.is_empty() {
+                self.push_active(value);
+            }
+            return;
+        }
+        let ifs = std::mem::take(&mut self.ifs);
+        let mut piece = String::new();
+        // whether blanks have just ended a field, which a following IFS
+        // character belongs with
+        let mut after_blanks = false;
+        for c in value.chars() {
+            if !ifs.contains(c) {
+                piece.push(c);
+                after_blanks = false;
+                continue;
+            }
+            if !piece.is_empty() {
+                self.push_active(&std::mem::take(&mut piece));
+            }
+            if is_blank(c) {
+                after_blanks |= self.exists;
+                self.end_field();
+            } else if !std::mem::take(&mut after_blanks) {
+                self.push_field();
+                self.exists = false;
+            }
+        }
+        if !piece.is_empty() {
+            self.push_active(&piece);
+        }
+        self.ifs = ifs;
+    }
+
+    /// Finish the current field if there is one.
+    fn end_field(&mut self) {
+        if self.exists {
+            self.push_field();
+            self.exists = false;
+        }
+    }
+
+    /// Start a new field even if the current one is empty, as between the
+    /// parameters of `"$@"`.
+    fn split_here(&mut self) {
+        self.push_field();
+        self.exists = true;
+    }
+
+    fn push_field(&mut self) {
+        let pattern = std::mem::take(&mut self.pattern);
+        if std::mem::take(&mut self.wild) {
+            self.patterns.push((self.fields.len(), pattern));
+        }
+        self.fields.push(std::mem::take(&mut self.current));
+    }
+
+    fn finish(mut self) -> (Vec<String>, Vec<(usize, String)>) {
+        self.end_field();
+        (self.fields, self.patterns)
+    }
+}
+
+/// Append `text` to a pattern so that it only matches itself.
+fn escape(pattern: &mut String, text: &str) {
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n')
+}
+
+/// The error for a `${...}` whose body is no parameter mash knows.
+fn bad_substitution(body: &str) -> ShellError {
+    ShellError::Failed(format!("${{{}}}: bad substitution", body))
+}
+
+impl Shell {
+    /// Expand a list of words into the fields they produce.
+    pub(crate) fn expand_words(&mut self, words: &[String]) -> Result<Vec<String>, ShellError> {
+        let _span = tracing::trace_span!("expand").entered();
+        let mut fields = Vec::with_capacity(words.len());
+        for word in words {
+            fields.extend(self.expand(word, Mode::Fields)?);
+        }
+        tracing::trace!(?fields);
+        Ok(fields)
+    }
+
+    /// Expand a single word without field splitting, as done for the word of
+    /// a `case` command.
+    pub(crate) fn expand_word(&mut self, word: &str) -> Result<String, ShellError> {
+        Ok(self.expand(word, Mode::Single)?.concat())
+    }
+
+    /// Expand a word into a glob pattern, escaping characters that were
+    /// quoted so they only match themselves.
+    pub(crate) fn expand_pattern(&mut self, word: &str) -> Result<String, ShellError> {
+        Ok(self.expand(word, Mode::Pattern)?.concat())
+    }
+
+    /// What `$(body)` stands for: the value of an arithmetic expression for
+    /// `$((expression))`, once its parameters and substitutions are
+    /// expanded, or else the output of the commands.
+    fn dollar_paren(&mut self, body: &str) -> Result<String, ShellError> {
+        match body.strip_prefix('(').and_then(|b| b.strip_suffix(')')) {
+            Some(expression) => {
+                let expression = self.expand_word(expression)?;
+                Ok(self.arithmetic(&expression)?.to_string())
+            }
+            None => self.substitute(body),
+        }
+    }
+
+    /// Expand the body of a here-document: parameters and command
+    /// substitutions, with a backslash quoting only `$`, `` ` ``, `\` and
+    /// newline. Quotes are ordinary characters there.
+    pub(crate) fn expand_here_doc(&mut self, body: &str) -> Result<String, ShellError> {
+        let mut text = String::new();
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next_if(|c| matches!(c, '$' | '`' | '\\' | '\n')) {
+                    Some('\n') => {}
+                    Some(c) => text.push(c),
+                    None => text.push('\\'),
+                },
+                '$' if chars.next_if_eq(&'(').is_some() => {
+                    let body = lexer::read_substitution(&mut chars).ok_or(ParseError::UnterminatedQuote('('))?;
+                    text.push_str(&self.dollar_paren(&body)?);
+                }
+                '`' => {
+                    let body = lexer::read_backquoted(&mut chars).ok_or(ParseError::UnterminatedQuote('`'))?;
+                    text.push_str(&self.substitute(&body)?);
+                }
+                '$' => match self.param_name(&mut chars) {
+                    Some(name) => text.push_str(&self.param(&name)?.joined()),
+                    None => text.push('$'),
+                },
+                _ => text.push(c),
+            }
+        }
+        Ok(text)
+    }
+
+    fn expand(&mut self, word: &str, mode: Mode) -> Result<Vec<String>, ShellError> {
+        let mut out = Fields::new(mode, self.var("IFS").unwrap_or_else(|| String::from(read::DEFAULT_IFS)));
+        let mut word = word;
+        if let Some(rest) = word.strip_prefix('~') {
+            let end = rest.find('/').unwrap_or(rest.len());
+            if let Some(home) = self.home_of(&rest[..end]) {
+                // the directory is not split or matched as a pattern
+                out.push_quoted(&home);
+                word = &rest[end..];
+            }
+        }
+        let mut chars = word.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => {
+                    let text: String = chars.by_ref().take_while(|&c| c != '\'').collect();
+                    out.push_quoted(&text);
+                }
+                '"' => self.expand_double_quoted(&mut chars, &mut out)?,
+                '\\' => match chars.next() {
+                    // a line continuation, which leaves nothing behind
+                    Some('\n') => {}
+                    Some(c) => out.push_quoted(&c.to_string()),
+                    None => {}
+                },
+                '$' if chars.next_if_eq(&'(').is_some() => {
+                    let body = lexer::read_substitution(&mut chars).ok_or(ParseError::UnterminatedQuote('('))?;
+                    out.push_unquoted(&self.dollar_paren(&body)?);
+                }
+                '`' => {
+                    let body = lexer::read_backquoted(&mut chars).ok_or(ParseError::UnterminatedQuote('`'))?;
+                    out.push_unquoted(&self.substitute(&body)?);
+                }
+                // the lexer only leaves these in a word to start a process
+                // substitution
+                '<' | '>' if chars.next_if_eq(&'(').is_some() => {
+                    let body = lexer::read_substitution(&mut chars).ok_or(ParseError::UnterminatedQuote('('))?;
+                    out.push_quoted(&self.substitute_process(&body, c == '>')?);
+                }
+                '$' => match self.param_name(&mut chars) {
+                    Some(name) => out.push_unquoted(&self.param(&name)?.joined()),
+                    None => out.push_quoted("$"),
+                },
+                _ if mode == Mode::Single => out.push_quoted(c.encode_utf8(&mut [0; 4])),
+                // unquoted pattern characters stay active
+                _ => out.push_active(c.encode_utf8(&mut [0; 4])),
+            }
+        }
+        let (mut fields, patterns) = out.finish();
+        if mode == Mode::Fields && !self.options.noglob {
+            // later fields first, so the indices of earlier ones stay put
+            for (i, pattern) in patterns.into_iter().rev() {
+                let matches = pattern::glob(&pattern, &self.path);
+                if matches.is_empty() && self.options.failglob {
+                    return Err(ShellError::Failed(format!("no match: {}", fields[i])));
+                }
+                if !matches.is_empty() || self.options.nullglob {
+                    fields.splice(i..=i, matches);
+                }
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Expand the inside of a double-quoted string, up to the closing quote.
+    fn expand_double_quoted(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>, out: &mut Fields) -> Result<(), ShellError> {
+        // `"$@"` with no positional parameters produces no field at all
+        let mut only_empty_list = false;
+        let mut text = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next_if(|c| matches!(c, '$' | '`' | '"' | '\\' | '\n')) {
+                    Some('\n') => {}
+                    Some(c) => text.push(c),
+                    None => text.push('\\'),
+                },
+                '$' if chars.next_if_eq(&'(').is_some() => {
+                    let body = lexer::read_substitution(chars).ok_or(ParseError::UnterminatedQuote('('))?;
+                    text.push_str(&self.dollar_paren(&body)?);
+                }
+                '`' => {
+                    let body = lexer::read_backquoted(chars).ok_or(ParseError::UnterminatedQuote('`'))?;
+                    text.push_str(&self.substitute(&body)?);
+                }
+                '$' => match self.param_name(chars).map(|name| self.param(&name)).transpose()? {
+                    Some(Expansion::Each(values)) => {
+                        if !text.is_empty() {
+                            out.push_quoted(&std::mem::take(&mut text));
+                        }
+                        for (i, value) in values.iter().enumerate() {
+                            if i > 0 {
+                                out.split_here();
+                            }
+                            out.push_quoted(value);
+                        }
+                        only_empty_list = values.is_empty() && !out.exists;
+                    }
+                    Some(Expansion::One(value)) => text.push_str(&value),
+                    None => text.push('$'),
+                },
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() || !only_empty_list {
+            out.push_quoted(&text);
+        }
+        Ok(())
+    }
+
+    /// The directory a `~` followed by `user` stands for: the shell's home
+    /// directory without a name, or else that user's from the password
+    /// database. `None` if there is no such user, or the name has quotes or
+    /// other characters that cannot be part of one.
+    fn home_of(&self, user: &str) -> Option<String> {
+        if user.is_empty() {
+            return Some(self.home.display().to_string());
+        }
+        if !user.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-')) {
+            return None;
+        }
+        let user = nix::unistd::User::from_name(user).ok().flatten()?;
+        Some(user.dir.display().to_string())
+    }
+
+    /// Read the name of a parameter following a `$`: a variable name, a
+    /// `{name}` in braces, or a single digit or special character. Returns
+    /// `None` if the `$` does not start an expansion.
+    fn param_name(&self, chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+        match chars.peek() {
+            Some('{') => {
+                chars.next();
+                Some(chars.by_ref().take_while(|&c| c != '}').collect())
+            }
+            Some(&c) if c.is_ascii_digit() || matches!(c, '#' | '@' | '*' | '!' | '?' | '$') => {
+                chars.next();
+                Some(c.to_string())
+            }
+            Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of a parameter, named or special. Besides plain names this
+    /// understands the forms allowed in braces: `a[i]`, `a[@]`, `a[*]`,
+    /// `#name` and `#a[@]` for lengths, and `!a[@]` for the keys of an array.
+    /// With `set -u`, expanding an unset parameter is an error.
+    fn param(&mut self, name: &str) -> Result<Expansion, ShellError> {
+        if let Some(target) = name.strip_prefix('#').filter(|t| !t.is_empty()) {
+            let len = match self.param(target)? {
+                Expansion::Each(values) => values.len(),
+                // `${#a[*]}` counts elements too, not characters
+                Expansion::One(_) if target.ends_with("[*]") => self.array(&target[..target.len() - 3]).len(),
+                Expansion::One(value) => value.chars().count(),
+            };
+            return Ok(Expansion::One(len.to_string()));
+        }
+        if let Some(array) = name.strip_prefix('!').and_then(|n| n.strip_suffix("[@]").or_else(|| n.strip_suffix("[*]"))) {
+            self.extension("arrays")?;
+            if !parser::is_name(array) {
+                return Err(bad_substitution(name));
+            }
+            let keys = self.value(array).map(|v| v.keys()).unwrap_or_default();
+            return Ok(if name.ends_with("[@]") { Expansion::Each(keys) } else { Expansion::One(keys.join(" ")) });
+        }
+        if let Some((array, subscript)) = name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+            self.extension("arrays")?;
+            if !parser::is_name(array) {
+                return Err(bad_substitution(name));
+            }
+            return Ok(match subscript {
+                "@" => Expansion::Each(self.array(array)),
+                "*" => Expansion::One(self.array(array).join(" ")),
+                _ => {
+                    let subscript = self.expand_word(subscript)?;
+                    Expansion::One(self.or_unbound(name, self.element(array, &subscript))?)
+                }
+            });
+        }
+        Ok(Expansion::One(match name {
+            "0" => self.name.clone(),
+            "#" => self.positional.len().to_string(),
+            "@" => return Ok(Expansion::Each(self.positional.clone())),
+            "*" => self.positional.join(" "),
+            "?" => self.last_status.to_string(),
+            "$" => self.pid.to_string(),
+            "!" => self.jobs.last_pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            _ => match name.parse::<usize>() {
+                Ok(n) => self.or_unbound(name, n.checked_sub(1).and_then(|n| self.positional.get(n)).cloned())?,
+                Err(_) if parser::is_name(name) => self.or_unbound(name, self.var(name))?,
+                Err(_) => return Err(bad_substitution(name)),
+            },
+        }))
+    }
+
+    /// The value of a parameter that may be unset, which is empty unless
+    /// `set -u` is on. Then expanding it is an error that a non-interactive
+    /// shell exits on.
+    fn or_unbound(&mut self, name: &str, value: Option<String>) -> Result<String, ShellError> {
+        match value {
+            Some(value) => Ok(value),
+            None if self.options.nounset => {
+                self.exiting |= !self.interactive;
+                Err(ShellError::Failed(format!("{}: unbound variable", name)))
+            }
+            None => Ok(String::new()),
+        }
+    }
+
+    /// The elements of an array, or of the environment variable of that name.
+    fn array(&self, name: &str) -> Vec<String> {
+        match self.value(name).as_deref() {
+            Some(value) => value.values(),
+            None => self.var(name).into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Shell, ShellError};
+
+    #[test]
+    fn braces_hold_names_special_parameters_and_subscripts() {
+        let mut shell = Shell::new();
+        shell.set_var("x", String::from("one"));
+        shell.positional = vec![String::from("a"), String::from("b")];
+        assert_eq!(shell.expand_word("${x}-${#x}-${1}${2}-${#}-${unset}").unwrap(), "one-3-ab-2-");
+        assert_eq!(shell.expand_word("${x[0]}").unwrap(), "one");
+    }
+
+    #[test]
+    fn arrays_expand_by_element_and_by_key() {
+        let mut shell = Shell::new();
+        shell.eval("a=(x 'y z' [4]=w); declare -A m=([k]=v [j]=u)").unwrap();
+        assert_eq!(shell.expand_word("${a[1]}:${a[-1]}:${a}:${#a[@]}:${m[k]}:${m[none]}").unwrap(), "y z:w:x:3:v:");
+        assert_eq!(shell.expand_words(&[String::from("\"${a[@]}\""), String::from("${!a[@]}"), String::from("${!m[@]}")]).unwrap(), ["x", "y z", "w", "0", "1", "4", "j", "k"]);
+        assert_eq!(shell.expand_word("${a[*]}|${m[@]}").unwrap(), "x y z w|u v");
+    }
+
+    #[test]
+    fn other_braced_bodies_are_bad_substitutions() {
+        let mut shell = Shell::new();
+        shell.set_var("x", String::from("abc"));
+        for body in ["x:-d", "x#a", "x%c", "x/b/B", "", "!x", "-1", "x y"] {
+            match shell.expand_word(&format!("${{{}}}", body)) {
+                Err(ShellError::Failed(message)) => assert_eq!(message, format!("${{{}}}: bad substitution", body)),
+                other => panic!("{:?} expanded to {:?}", body, other.map_err(|e| e.to_string())),
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! The `\g` prompt escape: the git branch of the working directory, with a
+//! `*` after it when tracked files have uncommitted changes.
+//!
+//! The branch is read from the repository's `HEAD` file each time, which is
+//! cheap. Finding out whether the tree is dirty means running `git status`,
+//! so the answer is kept until the index or `HEAD` changes, the shell moves
+//! to another repository, or a few seconds have passed.
+
+use std::{cell::RefCell, fs, path::{Path, PathBuf}, process::{Command, Stdio}, time::{Duration, Instant, SystemTime}};
+
+use crate::Shell;
+
+/// How long to trust a cached dirty state when nothing else says it changed.
+const FRESH: Duration = Duration::from_secs(5);
+
+/// The last dirty check, shared by every prompt.
+#[derive(Default)]
+pub(crate) struct Cache(RefCell<Option<Checked>>);
+
+struct Checked {
+    git_dir: PathBuf,
+    stamp: Stamp,
+    at: Instant,
+    dirty: bool,
+}
+
+/// When the index and `HEAD` were last modified.
+type Stamp = (Option<SystemTime>, Option<SystemTime>);
+
+impl Shell {
+    /// The `\g` segment: the branch name, or the abbreviated commit when
+    /// `HEAD` is detached, and `*` if dirty; empty outside a repository.
+    pub(crate) fn git_segment(&self) -> String {
+        let Some((work_tree, git_dir)) = find_repository(&self.path) else {
+            return String::new();
+        };
+        let Some(branch) = branch(&git_dir) else {
+            return String::new();
+        };
+        if self.git.dirty(&work_tree, &git_dir) {
+            branch + "*"
+        } else {
+            branch
+        }
+    }
+}
+
+impl Cache {
+    fn dirty(&self, work_tree: &Path, git_dir: &Path) -> bool {
+        let stamp = (modified(&git_dir.join("index")), modified(&git_dir.join("HEAD")));
+        let mut cached = self.0.borrow_mut();
+        if let Some(checked) = cached.as_ref() {
+            if checked.git_dir == git_dir && checked.stamp == stamp && checked.at.elapsed() < FRESH {
+                return checked.dirty;
+            }
+        }
+        let dirty = status_is_dirty(work_tree);
+        *cached = Some(Checked { git_dir: git_dir.to_path_buf(), stamp, at: Instant::now(), dirty });
+        dirty
+    }
+}
+
+/// The work tree containing `dir` and its git directory. `.git` may be a
+/// file pointing elsewhere, as in worktrees and submodules.
+fn find_repository(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for work_tree in dir.ancestors() {
+        let dotgit = work_tree.join(".git");
+        if dotgit.is_dir() {
+            return Some((work_tree.to_path_buf(), dotgit));
+        }
+        if let Ok(contents) = fs::read_to_string(&dotgit) {
+            let git_dir = contents.strip_prefix("gitdir:")?.trim();
+            return Some((work_tree.to_path_buf(), work_tree.join(git_dir)));
+        }
+    }
+    None
+}
+
+fn branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string())
+        }
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+/// Whether tracked files differ from `HEAD`. A failure to run git counts as
+/// clean.
+fn status_is_dirty(work_tree: &Path) -> bool {
+    Command::new("git").args(["status", "--porcelain", "--untracked-files=no"]).current_dir(work_tree)
+        .stdin(Stdio::null()).stderr(Stdio::null()).output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{branch, find_repository};
+
+    #[test]
+    fn branch_comes_from_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let git_dir = dir.path().join(".git");
+        fs::create_dir(&git_dir).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/prompt\n").unwrap();
+        let (work_tree, found) = find_repository(&dir.path().join("src")).unwrap();
+        assert_eq!((work_tree.as_path(), found.as_path()), (dir.path(), git_dir.as_path()));
+        assert_eq!(branch(&git_dir).as_deref(), Some("feature/prompt"));
+        fs::write(git_dir.join("HEAD"), "0123456789abcdef0123456789abcdef01234567\n").unwrap();
+        assert_eq!(branch(&git_dir).as_deref(), Some("0123456"));
+    }
+}
@@ -0,0 +1,138 @@
+//! The command hash table: where each program run so far was found in
+//! `PATH`, so it is not searched for again. The table empties itself when
+//! `PATH` changes, and `hash -r` (or zsh's `rehash`) empties it after
+//! programs are installed or moved.
+
+use std::{collections::HashMap, io::Write, path::PathBuf};
+
+use crate::{Builtin, Io, Shell, ShellError, exec::find_program};
+
+#[derive(Default)]
+pub(crate) struct CommandHash {
+    /// The `PATH` the entries were found in.
+    path: Option<String>,
+    /// Each program's location and how many times it was looked up.
+    entries: HashMap<String, (PathBuf, usize)>,
+}
+
+impl Shell {
+    /// Where the program `name` is, from the hash table if it is there and
+    /// still exists. Names containing `/` are never hashed, and neither are
+    /// programs when `PATH` has relative directories, which depend on the
+    /// working directory.
+    pub(crate) fn hashed_program(&mut self, name: &str) -> Result<PathBuf, ShellError> {
+        let path = self.var("PATH");
+        if name.contains('/') {
+            self.restrict(name)?;
+            return find_program(name, path.as_deref(), &self.path);
+        }
+        self.check_hash_path();
+        if let Some((program, hits)) = self.hash.entries.get_mut(name) {
+            if program.is_file() {
+                *hits += 1;
+                return Ok(program.clone());
+            }
+        }
+        let program = find_program(name, path.as_deref(), &self.path)?;
+        if path.unwrap_or_default().split(':').all(|dir| dir.starts_with('/')) {
+            self.hash.entries.insert(name.to_string(), (program.clone(), 1));
+        }
+        Ok(program)
+    }
+
+    /// Empty the table if `PATH` is not what it was built for.
+    fn check_hash_path(&mut self) {
+        let path = self.var("PATH");
+        if self.hash.path != path {
+            self.hash.entries.clear();
+            self.hash.path = path;
+        }
+    }
+
+    fn print_hash(&mut self, out: &mut dyn Write) {
+        self.check_hash_path();
+        let mut entries: Vec<_> = self.hash.entries.iter().collect();
+        entries.sort();
+        if entries.is_empty() {
+            let _ = writeln!(out, "hash: hash table empty");
+            return;
+        }
+        let _ = writeln!(out, "hits\tcommand");
+        for (_, (program, hits)) in entries {
+            let _ = writeln!(out, "{:4}\t{}", hits, program.display());
+        }
+    }
+}
+
+pub(crate) struct Hash;
+
+impl Builtin for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "hash [-r] [-d name ...] [-t name ...] [name ...]"
+    }
+
+    /// List the table, or look up each name and add it. `-r` empties the
+    /// table first, `-d` forgets the names and `-t` prints where they are.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (mut forget, mut print) = (false, false);
+        let mut names = args;
+        while let Some(flag) = names.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+            for c in flag[1..].chars() {
+                match c {
+                    'r' => shell.hash.entries.clear(),
+                    'd' => forget = true,
+                    't' => print = true,
+                    _ => return Err(ShellError::Usage(format!("hash: -{}: invalid option", c))),
+                }
+            }
+            names = &names[1..];
+        }
+        if args.is_empty() {
+            shell.print_hash(io.stdout);
+            return Ok(0);
+        }
+        let mut status = 0;
+        for name in names {
+            if forget {
+                if shell.hash.entries.remove(name).is_none() {
+                    let _ = writeln!(io.stderr, "hash: {}: not found", name);
+                    status = 1;
+                }
+                continue;
+            }
+            match shell.hashed_program(name) {
+                Ok(program) if print => {
+                    let _ = writeln!(io.stdout, "{}", program.display());
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    let _ = writeln!(io.stderr, "hash: {}: not found", name);
+                    status = 1;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// `rehash`, as in zsh: empty the hash table.
+pub(crate) struct Rehash;
+
+impl Builtin for Rehash {
+    fn name(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn run(&self, shell: &mut Shell, _: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        shell.hash.entries.clear();
+        Ok(0)
+    }
+}
@@ -0,0 +1,176 @@
+//! Colours and suggestions for the line being typed at the prompt. The word
+//! naming each command is green if the shell knows the command and red if
+//! it does not, and the latest history entry the line starts is suggested
+//! after it, dimmed, for Right arrow to accept. `set -o nohighlight` turns
+//! both off.
+
+use std::{collections::HashSet, ops::Range, path::Path, time::SystemTime};
+
+use crate::{complete, parser};
+
+const KNOWN: &str = "\x1b[32m";
+const UNKNOWN: &str = "\x1b[31m";
+pub(crate) const DIM: &str = "\x1b[2m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// Reserved words after which a command still starts.
+const COMMAND_KEYWORDS: &[&str] = &["if", "then", "elif", "else", "while", "until", "do", "{", "}", "time", "!"];
+
+/// The names of the programs in `PATH`, so that a command can be checked as
+/// it is typed without searching for it. They are read again when `PATH` or
+/// one of its directories changes.
+#[derive(Default)]
+pub(crate) struct Programs {
+    path: String,
+    modified: Vec<Option<SystemTime>>,
+    names: HashSet<String>,
+}
+
+impl Programs {
+    pub(crate) fn refresh(&mut self, path: &str) {
+        let modified: Vec<_> = path.split(':').map(|dir| std::fs::metadata(dir).and_then(|m| m.modified()).ok()).collect();
+        if self.path == path && self.modified == modified {
+            return;
+        }
+        self.names = complete::programs("", path).into_iter().collect();
+        self.path = path.to_string();
+        self.modified = modified;
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+}
+
+/// `line` with its command words coloured by whether `known` knows them.
+/// Words whose meaning depends on an expansion are left alone.
+pub(crate) fn highlight(line: &str, known: impl Fn(&str) -> bool) -> String {
+    let mut out = String::with_capacity(line.len() + 16);
+    let mut done = 0;
+    for word in command_words(line) {
+        let name: String = line[word.clone()].chars().filter(|c| !matches!(c, '\'' | '"' | '\\')).collect();
+        if name.contains(['$', '`']) {
+            continue;
+        }
+        out.push_str(&line[done..word.start]);
+        out.push_str(if known(&name) { KNOWN } else { UNKNOWN });
+        out.push_str(&line[word.clone()]);
+        out.push_str(RESET);
+        done = word.end;
+    }
+    out.push_str(&line[done..]);
+    out
+}
+
+/// Whether the command `name` would be found: by the names the shell knew
+/// at the prompt, the programs in `PATH`, or as a path to an executable.
+pub(crate) fn is_command(names: &complete::Names, programs: &Programs, name: &str) -> bool {
+    if name.contains('/') {
+        use std::os::unix::fs::PermissionsExt;
+        return Path::new(name).metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+    }
+    parser::RESERVED.contains(&name) || [&names.aliases, &names.functions, &names.builtins].iter().any(|group| group.iter().any(|n| n == name)) || programs.contains(name)
+}
+
+/// Where the words naming commands are in `line`: the first of each simple
+/// command, after any assignments and the keywords that can come before
+/// one.
+fn command_words(line: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut command = true;
+    let mut redirect = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+                continue;
+            }
+            // an arithmetic command names none
+            '(' if command && line[start + 1..].starts_with('(') => {
+                let end = line[start..].find("))").map_or(line.len(), |i| start + i + 2);
+                while chars.next_if(|&(i, _)| i < end).is_some() {}
+                command = false;
+                continue;
+            }
+            ';' | '&' | '|' | '(' | ')' | '\n' => {
+                chars.next();
+                command = true;
+                continue;
+            }
+            '<' | '>' => {
+                while chars.next_if(|&(_, c)| matches!(c, '<' | '>' | '&' | '|' | '-')).is_some() {}
+                redirect = true;
+                continue;
+            }
+            '#' => break,
+            _ => {}
+        }
+        let end = word_end(line, start);
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+        let word = &line[start..end];
+        if std::mem::take(&mut redirect) || !command {
+            continue;
+        }
+        // a descriptor number before a redirection is not a command
+        if word.bytes().all(|b| b.is_ascii_digit()) && line[end..].starts_with(['<', '>']) {
+            continue;
+        }
+        if parser::split_assignment(word).is_some() || COMMAND_KEYWORDS.contains(&word) {
+            continue;
+        }
+        command = false;
+        if !parser::RESERVED.contains(&word) {
+            words.push(start..end);
+        }
+    }
+    words
+}
+
+/// Where the word starting at `start` ends, taking in quotes and `$(...)`
+/// whole. An unfinished quote runs to the end of the line.
+fn word_end(line: &str, start: usize) -> usize {
+    let mut quote = None;
+    let mut depth = 0;
+    let mut chars = line[start..].char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some('"'), '"') => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (_, '$') if chars.next_if(|&(_, c)| c == '(').is_some() => depth += 1,
+            (_, ')') if depth > 0 => depth -= 1,
+            (None, ' ' | '\t' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>') if depth == 0 => return start + i,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command_words, highlight};
+
+    fn words(line: &str) -> Vec<&str> {
+        command_words(line).into_iter().map(|range| &line[range]).collect()
+    }
+
+    #[test]
+    fn command_words_start_each_command() {
+        assert_eq!(words("ls -l | grep 'a b' && x=1 make; if true; then echo $(date) >out; fi"), ["ls", "grep", "make", "true", "echo"]);
+        assert_eq!(words("for i in a b; do echo \"$i\"; done 2>/dev/null"), ["echo"]);
+        assert_eq!(words("</dev/null cat # ls"), ["cat"]);
+        assert_eq!(words("while (( i < 3 )); do ((i++)); done"), Vec::<&str>::new());
+        assert_eq!(words("'unfinished quote"), ["'unfinished quote"]);
+    }
+
+    #[test]
+    fn known_commands_are_green() {
+        assert_eq!(highlight("ls x | nope", |name| name == "ls"), "\x1b[32mls\x1b[0m x | \x1b[31mnope\x1b[0m");
+        assert_eq!(highlight("$cmd x", |_| false), "$cmd x");
+    }
+}
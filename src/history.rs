@@ -0,0 +1,380 @@
+//! Command history for interactive shells, kept in the state directory
+//! unless the configuration file says otherwise.
+//!
+//! `HISTSIZE` limits the number of entries kept, and `HISTCONTROL` is a
+//! colon-separated list of `ignoredups` (don't record a line repeating the
+//! one before it), `ignorespace` (don't record lines starting with a space)
+//! and `ignoreboth`. Once set, they take precedence over the `[history]`
+//! table of the configuration file.
+//!
+//! Lines typed at the prompt go through history expansion before they are
+//! parsed: `!!` is the last entry, `!n` entry `n`, `!-n` the `n`th most
+//! recent and `!text` the most recent starting with `text`. A `!` quoted
+//! with `'` or `\\` is left alone. Entries are numbered from 1 as `history`
+//! lists them.
+//!
+//! Sessions share the history file: each appends its entries with the time
+//! they were added, as bash does with `HISTTIMEFORMAT`, holding a lock on
+//! the file while it does. Entries other sessions add are merged in at the
+//! prompt every minute, or at once with `history -m`.
+
+use std::{fs::{File, OpenOptions}, io::{Read, Seek, SeekFrom, Write as _}, os::fd::AsRawFd, path::Path, time::{Duration, Instant, SystemTime}};
+
+use nix::fcntl::{FlockArg, flock};
+use rustyline::{Editor, Helper, config::Configurer, history::{FileHistory, History as _}};
+
+use crate::{Builtin, Io, Shell, ShellError, dirs, error};
+
+/// The history file's name in the state directory.
+pub(crate) const HISTORY_FILE: &str = "history";
+
+/// How often entries from other sessions are merged in at the prompt.
+const MERGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An entry of the history file and when it was added, in seconds since the
+/// epoch; 0 for entries from files without times.
+type Entry = (u64, String);
+
+/// What this session knows of the history file, which other sessions
+/// append to as well.
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    /// Entries from this time on may not have been merged in yet.
+    since: u64,
+    /// The entries from `since` on that were read or added by this session.
+    seen: Vec<Entry>,
+    merged_at: Option<Instant>,
+    /// Entries merged in that the editor has yet to take.
+    merged: Vec<String>,
+}
+
+impl Shared {
+    /// Take note of `entries`, all of the history file, as read.
+    fn read(&mut self, entries: &[Entry]) {
+        self.since = entries.iter().map(|(time, _)| *time).max().unwrap_or(0).max(self.since);
+        self.seen = entries.iter().filter(|(time, _)| *time == self.since).cloned().collect();
+        self.merged_at = Some(Instant::now());
+    }
+
+    /// Those of `entries`, all of the history file, that are new to this
+    /// session.
+    fn unseen(&self, entries: &[Entry]) -> Vec<String> {
+        let mut seen = self.seen.clone();
+        entries.iter().filter(|(time, _)| *time >= self.since)
+            .filter(|entry| match seen.iter().position(|s| s == *entry) {
+                Some(i) => {
+                    seen.swap_remove(i);
+                    false
+                }
+                None => true,
+            })
+            .map(|(_, entry)| entry.clone())
+            .collect()
+    }
+
+    fn merge_due(&self) -> bool {
+        self.merged_at.is_some_and(|at| at.elapsed() >= MERGE_INTERVAL)
+    }
+}
+
+/// Open the history file, creating it, and lock it against other sessions
+/// until it is closed.
+fn open_locked(file: &Path, exclusive: bool) -> std::io::Result<File> {
+    let handle = OpenOptions::new().read(true).append(true).create(true).open(file)?;
+    flock(handle.as_raw_fd(), if exclusive { FlockArg::LockExclusive } else { FlockArg::LockShared })?;
+    Ok(handle)
+}
+
+fn read_entries(handle: &mut File) -> std::io::Result<Vec<Entry>> {
+    let mut bytes = Vec::new();
+    handle.seek(SeekFrom::Start(0))?;
+    handle.read_to_end(&mut bytes)?;
+    Ok(parse(&String::from_utf8_lossy(&bytes)))
+}
+
+/// The entries of a history file. Each follows a `#` line with its time and
+/// runs to the next such line; lines before the first are an entry each,
+/// as files written by rustyline have them.
+fn parse(text: &str) -> Vec<Entry> {
+    let escaped = text.starts_with("#V2\n");
+    let mut entries: Vec<(u64, Vec<&str>)> = Vec::new();
+    let mut timed = false;
+    for line in text.lines() {
+        let time = line.strip_prefix('#').filter(|t| !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit()));
+        if let Some(time) = time.and_then(|t| t.parse().ok()) {
+            entries.push((time, Vec::new()));
+            timed = true;
+        } else if timed {
+            entries.last_mut().unwrap().1.push(line);
+        } else if !(escaped && line == "#V2") {
+            entries.push((0, vec![line]));
+        }
+    }
+    let unescape = |line: String| match escaped {
+        true => line.replace("\\n", "\n").replace("\\\\", "\\"),
+        false => line,
+    };
+    entries.into_iter().filter(|(_, lines)| !lines.is_empty())
+        .map(|(time, lines)| (time, if time == 0 { unescape(lines.join("\n")) } else { lines.join("\n") }))
+        .collect()
+}
+
+fn format_entry((time, entry): &Entry) -> String {
+    format!("#{}\n{}\n", time, entry)
+}
+
+/// Load the history file into the editor, creating it if it does not exist.
+/// The shell carries on without history if this fails.
+pub(crate) fn load<H: Helper>(rl: &mut Editor<H, FileHistory>, file: &Path, shared: &mut Shared) -> Result<(), ShellError> {
+    let context = || format!("history: {}", file.display());
+    if !file.exists() {
+        dirs::create_parent(file).map_err(|e| ShellError::io(context(), e))?;
+    }
+    let entries = open_locked(file, false).and_then(|mut handle| read_entries(&mut handle)).map_err(|e| ShellError::io(context(), e))?;
+    for (_, entry) in &entries {
+        let _ = rl.add_history_entry(entry.as_str());
+    }
+    shared.read(&entries);
+    Ok(())
+}
+
+/// Record an entry and append it to the history file, which is cut down to
+/// the size of the history once it grows past it. The entry is kept for
+/// this session even if the file cannot be written.
+pub(crate) fn add<H: Helper>(rl: &mut Editor<H, FileHistory>, file: &Path, shared: &mut Shared, entry: &str) -> Result<(), ShellError> {
+    let context = || format!("history: {}", file.display());
+    // an entry ignored as a duplicate or for its leading space is not kept
+    if !rl.add_history_entry(entry).map_err(|e| ShellError::Failed(format!("{}: {}", context(), e)))? {
+        return Ok(());
+    }
+    let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let entry = (time, entry.to_string());
+    let size = rl.config_mut().max_history_size();
+    let written = open_locked(file, true).and_then(|mut handle| {
+        let mut entries = read_entries(&mut handle)?;
+        entries.push(entry.clone());
+        if entries.len() <= size {
+            return handle.write_all(format_entry(&entry).as_bytes());
+        }
+        handle.set_len(0)?;
+        handle.write_all(entries[entries.len() - size..].iter().map(format_entry).collect::<String>().as_bytes())
+    });
+    shared.seen.push(entry);
+    written.map_err(|e| ShellError::io(context(), e))
+}
+
+impl Shell {
+    /// Read the entries other sessions added to the history file since it
+    /// was last read, returning how many there were. The editor takes them
+    /// at the next prompt. Only interactive shells keep history.
+    pub(crate) fn merge_history(&mut self) -> Result<usize, ShellError> {
+        if !self.interactive {
+            return Ok(0);
+        }
+        let entries = open_locked(&self.history_file, false).and_then(|mut handle| read_entries(&mut handle))
+            .map_err(|e| ShellError::io(format!("history: {}", self.history_file.display()), e))?;
+        let unseen = self.history_shared.unseen(&entries);
+        self.history_shared.read(&entries);
+        self.history.extend(unseen.iter().cloned());
+        self.history_shared.merged.extend(unseen);
+        Ok(self.history_shared.merged.len())
+    }
+}
+
+/// Merge in the entries from other sessions if it is time to, and give the
+/// editor those merged since the last prompt.
+pub(crate) fn take_merged<H: Helper>(rl: &mut Editor<H, FileHistory>, shell: &mut Shell) {
+    if shell.history_shared.merge_due() {
+        let _ = shell.merge_history();
+    }
+    let merged = std::mem::take(&mut shell.history_shared.merged);
+    if merged.is_empty() {
+        return;
+    }
+    for entry in merged {
+        let _ = rl.add_history_entry(entry);
+    }
+    shell.history.clear();
+    sync(&mut shell.history, rl);
+}
+
+/// The values of `HISTSIZE` and `HISTCONTROL` last applied to the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Variables {
+    size: Option<String>,
+    control: Option<String>,
+}
+
+impl Shell {
+    pub(crate) fn history_variables(&self) -> Variables {
+        Variables { size: self.var("HISTSIZE"), control: self.var("HISTCONTROL") }
+    }
+}
+
+/// Apply the history variables that are set to the editor.
+pub(crate) fn apply_variables<H: Helper>(rl: &mut Editor<H, FileHistory>, variables: &Variables) {
+    // like other shells, a size that is not a number is ignored
+    if let Some(size) = variables.size.as_deref().and_then(|s| s.trim().parse().ok()) {
+        if let Err(e) = rl.set_max_history_size(size) {
+            error::warn(format!("history: {}", e));
+        }
+    }
+    if let Some(control) = &variables.control {
+        let has = |name| control.split(':').any(|c| c == name || c == "ignoreboth");
+        if let Err(e) = rl.set_history_ignore_dups(has("ignoredups")) {
+            error::warn(format!("history: {}", e));
+        }
+        rl.set_history_ignore_space(has("ignorespace"));
+    }
+}
+
+/// Bring the shell's copy of the history up to date with the editor's, after
+/// loading the file or adding an entry.
+pub(crate) fn sync<H: Helper>(entries: &mut Vec<String>, rl: &Editor<H, FileHistory>) {
+    let history = rl.history();
+    if entries.is_empty() {
+        entries.extend(history.iter().cloned());
+        return;
+    }
+    // an entry ignored as a duplicate or for its leading space is not there
+    if let Some(last) = history.iter().next_back() {
+        if entries.last() != Some(last) {
+            entries.push(last.clone());
+        }
+    }
+    if entries.len() > history.len() {
+        entries.drain(..entries.len() - history.len());
+    }
+}
+
+/// Characters that end the text of a `!text` event.
+const EVENT_END: &[char] = &[' ', '\t', '\n', ';', '|', '&', '(', ')', '<', '>', '"', '\''];
+
+/// Expand the history events in `line`, returning `None` if it has none.
+pub(crate) fn expand(line: &str, entries: &[String]) -> Result<Option<String>, ShellError> {
+    let mut expanded = String::new();
+    let mut found = false;
+    let mut single_quoted = false;
+    let mut double_quoted = false;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        match c {
+            '\\' if !single_quoted => {
+                let escaped = after.chars().next().map_or(0, char::len_utf8);
+                expanded.push_str(&rest[..1 + escaped]);
+                rest = &after[escaped..];
+                continue;
+            }
+            '\'' if !double_quoted => single_quoted = !single_quoted,
+            '"' if !single_quoted => double_quoted = !double_quoted,
+            '!' if !single_quoted => {
+                if let Some((entry, len)) = event(after, entries)? {
+                    expanded.push_str(entry);
+                    rest = &after[len..];
+                    found = true;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        expanded.push(c);
+        rest = after;
+    }
+    Ok(found.then_some(expanded))
+}
+
+/// The entry named by the event designator at the start of `spec`, just
+/// after a `!`, and the length of the designator; `None` if the `!` is not
+/// the start of one.
+fn event<'a>(spec: &str, entries: &'a [String]) -> Result<Option<(&'a str, usize)>, ShellError> {
+    let not_found = |len: usize| ShellError::Failed(format!("!{}: event not found", &spec[..len]));
+    if spec.starts_with('!') {
+        return entries.last().map(|e| Some((e.as_str(), 1))).ok_or_else(|| not_found(1));
+    }
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let negative = spec.starts_with('-') && spec[1..].starts_with(|c: char| c.is_ascii_digit());
+    if negative || spec.starts_with(|c: char| c.is_ascii_digit()) {
+        let len = if negative { 1 + digits(&spec[1..]) } else { digits(spec) };
+        let n: isize = spec[..len].parse().map_err(|_| not_found(len))?;
+        let index = if n < 0 { entries.len().checked_sub(n.unsigned_abs()) } else { (n as usize).checked_sub(1) };
+        return index.and_then(|i| entries.get(i)).map(|e| Some((e.as_str(), len))).ok_or_else(|| not_found(len));
+    }
+    let len = spec.find(EVENT_END).unwrap_or(spec.len());
+    // a `!` on its own, as in `! true` or `!= 0`, is not an event
+    if len == 0 || spec.starts_with('=') {
+        return Ok(None);
+    }
+    let prefix = &spec[..len];
+    entries.iter().rev().find(|e| e.starts_with(prefix)).map(|e| Some((e.as_str(), len))).ok_or_else(|| not_found(len))
+}
+
+/// `history [n]` lists the entries, or the last `n`, with their numbers.
+/// `history -m` merges in those other sessions have added.
+pub(crate) struct History;
+
+impl Builtin for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "history [n] or history -m"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let count = match args {
+            [] => shell.history.len(),
+            [flag] if flag == "-m" => {
+                shell.merge_history()?;
+                return Ok(0);
+            }
+            [n] => n.parse().map_err(|_| ShellError::Usage(format!("history: {}: numeric argument required", n)))?,
+            _ => return Err(ShellError::Usage(String::from("history: too many arguments"))),
+        };
+        let skip = shell.history.len().saturating_sub(count);
+        for (i, entry) in shell.history.iter().enumerate().skip(skip) {
+            let _ = writeln!(io.stdout, "{:5}  {}", i + 1, entry);
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Shared, expand, format_entry, parse};
+
+    #[test]
+    fn entries_are_read_with_their_times() {
+        let entries = [(5, String::from("echo one")), (7, String::from("for i in a\n#1 b\ndo :; done"))];
+        let text: String = entries.iter().map(format_entry).collect();
+        assert_eq!(parse(&text), entries);
+        assert_eq!(parse("ls\n#3\ncd\n"), [(0, String::from("ls")), (3, String::from("cd"))]);
+        assert_eq!(parse("#V2\necho a\\nb \\\\\n"), [(0, String::from("echo a\nb \\"))]);
+    }
+
+    #[test]
+    fn merging_skips_entries_already_seen() {
+        let entry = |time, text: &str| (time, String::from(text));
+        let mut shared = Shared::default();
+        shared.read(&[entry(1, "old"), entry(2, "a")]);
+        shared.seen.push(entry(3, "mine"));
+        let file = [entry(1, "old"), entry(2, "a"), entry(2, "b"), entry(3, "mine"), entry(3, "theirs")];
+        assert_eq!(shared.unseen(&file), ["b", "theirs"]);
+        shared.read(&file);
+        assert!(shared.unseen(&file).is_empty());
+    }
+
+    #[test]
+    fn events_expand_from_the_history() {
+        let entries = [String::from("echo one"), String::from("ls -l"), String::from("echo two")];
+        let expand = |line: &str| expand(line, &entries).map_err(|e| e.to_string());
+        assert_eq!(expand("!!"), Ok(Some(String::from("echo two"))));
+        assert_eq!(expand("sudo !! | less"), Ok(Some(String::from("sudo echo two | less"))));
+        assert_eq!(expand("!2; !-3"), Ok(Some(String::from("ls -l; echo one"))));
+        assert_eq!(expand("!ls /tmp"), Ok(Some(String::from("ls -l /tmp"))));
+        assert_eq!(expand("! true; [ a != b ]; echo '!!' \\!!"), Ok(None));
+        assert_eq!(expand("!4"), Err(String::from("!4: event not found")));
+        assert_eq!(expand("!cat"), Err(String::from("!cat: event not found")));
+    }
+}
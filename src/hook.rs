@@ -0,0 +1,141 @@
+//! Hook plugins: executables called `NAME.hook` in `~/.mash/plugins`, which
+//! give prompt segments and completions from outside the shell, so a tool
+//! like `kubectl` can complete its own arguments without mash being rebuilt.
+//!
+//! A hook is run with `prompt` as its argument before each prompt, and with
+//! `complete` whenever Tab is pressed. It finds the line typed so far in
+//! `MASH_LINE`, the byte offset of the cursor in it in `MASH_POINT` and the
+//! working directory, which it also starts in, in `MASH_CWD`. It answers
+//! with a JSON object on standard output:
+//!
+//! ```json
+//! {"segment": "⎈ prod"}
+//! {"start": 8, "completions": ["get ", "describe "]}
+//! ```
+//!
+//! `start` is where the text the completions replace begins, by default
+//! the start of the word at the cursor. A hook with nothing to add prints
+//! nothing or exits with a failure; one that takes longer than
+//! [`TIMEOUT`] is killed. Its standard error is discarded.
+
+use std::{io::Read, path::{Path, PathBuf}, process::{Command, Stdio}, time::{Duration, Instant}};
+
+use serde::Deserialize;
+
+use crate::{Shell, complete::Word, plugin::Plugin};
+
+/// How long a hook may take to answer.
+pub(crate) const TIMEOUT: Duration = Duration::from_millis(500);
+
+pub(crate) const EXTENSION: &str = "hook";
+
+/// What a hook prints.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Reply {
+    segment: Option<String>,
+    start: Option<usize>,
+    completions: Vec<String>,
+}
+
+pub(crate) struct Hook {
+    name: String,
+    program: PathBuf,
+}
+
+impl Hook {
+    pub(crate) fn new(name: &str, program: &Path) -> Self {
+        Hook { name: name.to_string(), program: program.to_path_buf() }
+    }
+
+    /// Run the hook for `request` and read its reply, if it gave one in
+    /// time.
+    fn ask(&self, request: &str, line: &str, pos: usize, cwd: &Path) -> Option<Reply> {
+        let mut child = Command::new(&self.program)
+            .arg(request)
+            .env("MASH_LINE", line)
+            .env("MASH_POINT", pos.to_string())
+            .env("MASH_CWD", cwd)
+            .current_dir(cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .inspect_err(|e| tracing::warn!(hook = self.name, "{}: {}", self.program.display(), e))
+            .ok()?;
+        // read as it is written, so a long reply cannot fill the pipe and
+        // stall the hook
+        let mut stdout = child.stdout.take()?;
+        let reader = std::thread::spawn(move || {
+            let mut out = Vec::new();
+            let _ = stdout.read_to_end(&mut out);
+            out
+        });
+        let deadline = Instant::now() + TIMEOUT;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(5)),
+                _ => {
+                    tracing::warn!(hook = self.name, request, "no answer in time");
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return None;
+                }
+            }
+        };
+        let out = reader.join().ok()?;
+        if !status.success() || out.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        serde_json::from_slice(&out).inspect_err(|e| tracing::warn!(hook = self.name, request, "invalid reply: {}", e)).ok()
+    }
+}
+
+impl Plugin for Hook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn prompt_segment(&self, shell: &Shell) -> Option<String> {
+        self.ask("prompt", "", 0, &shell.path)?.segment.filter(|s| !s.is_empty())
+    }
+
+    fn complete(&self, line: &str, pos: usize) -> Option<(usize, Vec<String>)> {
+        let cwd = std::env::current_dir().ok()?;
+        let reply = self.ask("complete", line, pos, &cwd)?;
+        let start = reply.start.unwrap_or_else(|| Word::at(line, pos).start);
+        if reply.completions.is_empty() || start > pos || !line.is_char_boundary(start) {
+            return None;
+        }
+        Some((start, reply.completions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::Hook;
+    use crate::plugin::Plugin;
+
+    fn hook(dir: &std::path::Path, script: &str) -> Hook {
+        let program = dir.join("test.hook");
+        std::fs::write(&program, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Hook::new("test", &program)
+    }
+
+    #[test]
+    fn completions_come_from_the_reply() {
+        let dir = tempfile::tempdir().unwrap();
+        let words = hook(dir.path(), r#"[ "$1" = complete ] && printf '{"completions": ["%s-done"]}' "${MASH_LINE#kubectl }""#);
+        assert_eq!(words.complete("kubectl ge", 10), Some((8, vec![String::from("ge-done")])));
+        let offset = hook(dir.path(), r#"echo '{"start": 2, "completions": ["x"]}'"#);
+        assert_eq!(offset.complete("abcd", 4), Some((2, vec![String::from("x")])));
+        assert_eq!(offset.complete("a", 1), None);
+        assert_eq!(hook(dir.path(), "exit 1").complete("a", 1), None);
+        assert_eq!(hook(dir.path(), "echo not json").complete("a", 1), None);
+        assert_eq!(hook(dir.path(), "exec sleep 5").complete("a", 1), None);
+    }
+}
@@ -0,0 +1,260 @@
+//! The interactive read-eval loop, built on rustyline.
+
+use std::{panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}};
+
+use rustyline::{Editor, error::ReadlineError, EditMode, Event, EventHandler, config::Configurer, history::FileHistory};
+
+use crate::{Shell, config::{self, Config}, dirs, error, editor::{self, MashHelper}, history, parser, shutdown, signals, term, timeout::{IdleTimer, Printer}, timing::Clock};
+
+/// The startup file an interactive shell reads before its first prompt.
+pub enum RcFile {
+    /// ~/.mashrc, if it exists.
+    Default,
+    /// A file given with `--rcfile`, which is reported if missing.
+    Path(PathBuf),
+    /// Nothing, as with `--norc`.
+    None,
+}
+
+impl Shell {
+    /// Read commands from the terminal with line editing and history until
+    /// `exit` is run. Plugins and the configuration file are read before the
+    /// startup file.
+    pub fn run_interactive(&mut self, rcfile: RcFile) {
+        // start main loop
+        // print prompt and read input
+        // call fork and exec system calls
+        // wait for child process to finish
+        // repeat
+
+        self.timed("migrate state", |shell| dirs::migrate(&shell.home));
+        let posix = self.options.posix;
+        if !posix {
+            self.timed("plugins", Shell::load_plugins);
+            self.load_dir_history();
+        }
+        self.rcfile = match rcfile {
+            // POSIX shells read the file named by $ENV instead
+            RcFile::Default if posix => self.var("ENV").and_then(|env| self.expand_word(&env).ok()).map(PathBuf::from).filter(|rc| rc.exists()),
+            // a missing ~/.mashrc is fine, a missing --rcfile is worth reporting
+            RcFile::Default => Some(self.home.join(".mashrc")).filter(|rc| rc.exists()),
+            RcFile::Path(path) => Some(path),
+            RcFile::None => None,
+        };
+        let config = match posix {
+            true => Config::default(),
+            false => self.timed("config file", |shell| {
+                let config = Config::load(&Config::path(&shell.home));
+                shell.apply_config(&config);
+                config
+            }),
+        };
+        let rl = self.timed("line editor", |shell| {
+            let mut rl: Editor<MashHelper, FileHistory> = match Editor::new() {
+                Ok(rl) => rl,
+                Err(e) => {
+                    error::warn(format!("cannot start the line editor: {}", e));
+                    return None;
+                }
+            };
+            let helper = MashHelper::new(shell.plugins.native.clone(), shell.completions.clone());
+            let vi_tracker = helper.vi_tracker();
+            rl.set_helper(Some(helper));
+            rl.bind_sequence(Event::Any, EventHandler::Conditional(Box::new(vi_tracker)));
+            Some(rl)
+        });
+        let Some(mut rl) = rl else {
+            self.last_status = 1;
+            return;
+        };
+        let history_file = self.history_file.clone();
+        self.timed("history", |shell| {
+            if let Err(e) = history::load(&mut rl, &history_file, &mut shell.history_shared) {
+                shell.error(e);
+            }
+            history::sync(&mut shell.history, &rl);
+        });
+        self.timed("rc file", Shell::source_rcfile);
+        if self.exiting {
+            return;
+        }
+        if std::mem::take(&mut self.restrict_after_rc) {
+            self.options.restricted = true;
+        }
+        self.editor_config = Some(config);
+        term::report_cwd(&self.path);
+        if !posix {
+            self.timed("directory env", Shell::enter_directory);
+        }
+        self.print_startup_profile();
+        signals::catch_interrupt();
+        signals::ignore_quit();
+        signals::catch_alarm();
+        signals::catch_hangup();
+        term::save_mode();
+        self.start_job_control();
+        shutdown::install_panic_hook();
+
+        // a crash still saves what it can and leaves the terminal usable
+        let crashed = panic::catch_unwind(AssertUnwindSafe(|| self.read_eval_loop(&mut rl, &history_file))).is_err();
+        if crashed {
+            self.last_status = 101;
+        }
+        if let Some(signal) = signals::hung_up() {
+            self.last_status = 128 + signal;
+        }
+        self.shutdown();
+    }
+
+    /// Read and run command lines until `exit`, end of input or a hangup.
+    fn read_eval_loop(&mut self, rl: &mut Editor<MashHelper, FileHistory>, history_file: &Path) {
+        // keys bound by the configuration file and `bind`, undone when they
+        // are bound again
+        let mut bound = Vec::new();
+        // the configuration file's editor settings, to bind its keys again
+        // when `bind` changes
+        let mut editor_config = Config::default();
+        // created the first time `TMOUT` is set, for the logout warning
+        let mut printer: Option<Printer> = None;
+        // `HISTSIZE` and `HISTCONTROL` as applied, reapplied when they change
+        let mut history_variables = None;
+
+        // input collected so far while a compound command is left open
+        let mut buffer = String::new();
+        loop {
+            if buffer.is_empty() && self.options.autoreload && self.startup_files_changed() {
+                self.reload();
+            }
+            let reloaded = self.editor_config.take().map(|config| editor_config = config).is_some();
+            if reloaded || std::mem::take(&mut self.bindings_changed) {
+                for key in bound.drain(..) {
+                    rl.unbind_sequence(key);
+                }
+                bind_defaults(rl);
+                bound = config::configure_editor(rl, &editor_config);
+                for (key, _, command) in &self.bindings {
+                    if let Some(command) = editor::parse_command(command) {
+                        rl.bind_sequence(*key, command);
+                        bound.push(*key);
+                    }
+                }
+                history_variables = None;
+            }
+            history::take_merged(rl, self);
+            let variables = self.history_variables();
+            if history_variables.as_ref() != Some(&variables) {
+                history::apply_variables(rl, &variables);
+                history_variables = Some(variables);
+            }
+            let edit_mode = if self.options.vi { EditMode::Vi } else { EditMode::Emacs };
+            if rl.config_mut().edit_mode() != edit_mode {
+                rl.set_edit_mode(edit_mode);
+            }
+            if let Some(helper) = rl.helper() {
+                helper.reset_vi_mode();
+                helper.set_highlighting(!self.options.nohighlight);
+            }
+
+            let prompt = if buffer.is_empty() {
+                self.reap_jobs();
+                self.run_signal_traps();
+                self.run_precmd_plugins();
+                self.update_completion_names();
+                term::prompt_start();
+                self.editor_prompt()
+            } else {
+                self.continuation_prompt()
+            };
+            let timer = self.idle_timeout().and_then(|seconds| {
+                if printer.is_none() {
+                    printer = rl.create_external_printer().ok().map(|p| -> Printer { std::sync::Arc::new(std::sync::Mutex::new(Box::new(p))) });
+                }
+                IdleTimer::start(seconds, printer.clone())
+            });
+            let readline = rl.readline(&prompt);
+            if timer.is_some_and(IdleTimer::stop) {
+                error::warn("timed out waiting for input: auto-logout");
+                break;
+            }
+            if signals::hung_up().is_some() {
+                break;
+            }
+            match readline {
+                Ok(line) => {
+                    if buffer.is_empty() && line.trim().is_empty() {
+                        continue;
+                    }
+                    // expanded lines are shown as they will run
+                    let line = match history::expand(&line, &self.history) {
+                        Ok(Some(expanded)) => {
+                            println!("{}", expanded);
+                            expanded
+                        }
+                        Ok(None) => line,
+                        Err(e) => {
+                            self.report(e);
+                            buffer.clear();
+                            continue;
+                        }
+                    };
+                    if self.options.verbose {
+                        eprintln!("{}", line);
+                    }
+                    buffer.push_str(&line);
+                    buffer.push('\n');
+
+                    let list = match tracing::debug_span!("parse").in_scope(|| parser::parse(&buffer)) {
+                        Ok(list) => list,
+                        Err(e) if e.is_incomplete() => continue,
+                        Err(e) => {
+                            self.report(e.into());
+                            buffer.clear();
+                            continue;
+                        }
+                    };
+
+                    // recorded before running, so the line is kept even if
+                    // the shell does not survive it
+                    if let Err(e) = history::add(rl, history_file, &mut self.history_shared, buffer.trim_end()) {
+                        self.error(e);
+                    }
+                    history::sync(&mut self.history, rl);
+                    self.run_preexec_plugins(buffer.trim_end());
+                    term::command_start();
+                    signals::clear_interrupt();
+                    let clock = Clock::start();
+                    self.run_list(&list);
+                    let times = clock.stop();
+                    term::command_end(self.last_status);
+                    self.report_time(buffer.trim_end(), &times);
+                    self.notify_finished(buffer.trim_end(), times.real);
+                    buffer.clear();
+
+                    if self.exiting || signals::hung_up().is_some() {
+                        break;
+                    }
+                },
+                // Ctrl-C abandons the line, and any half-entered compound command
+                Err(ReadlineError::Interrupted) => buffer.clear(),
+                // Ctrl-D at an empty prompt leaves, as `exit` does
+                Err(ReadlineError::Eof) => break,
+                Err(e) => {
+                    error::warn(e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// The keys mash binds differently from rustyline, before the configuration
+/// file and `bind` have their say.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[("ctrl-r", "history-search-backward"), ("ctrl-i", "complete")];
+
+fn bind_defaults(rl: &mut Editor<MashHelper, FileHistory>) {
+    for (key, command) in DEFAULT_BINDINGS {
+        if let (Some(key), Some(command)) = (editor::parse_key(key), editor::parse_command(command)) {
+            rl.bind_sequence(key, command);
+        }
+    }
+}
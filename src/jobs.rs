@@ -0,0 +1,765 @@
+//! Jobs: commands followed by `&`, which run in a child process while the
+//! shell carries on, and foreground commands stopped with Ctrl-Z. Finished
+//! jobs are reaped without blocking, and an interactive shell reports them
+//! before the next prompt.
+//!
+//! When an interactive shell reads from a terminal it does job control: each
+//! job gets a process group of its own, the one in the foreground is given
+//! the terminal, and `jobs`, `fg` and `bg` manage the rest.
+//!
+//! `kill` signals jobs and processes and `wait` waits for them, with or
+//! without job control. `disown` forgets a job, so that it is not hung up
+//! with the shell.
+
+use std::{io::{IsTerminal, Write}, time::Duration};
+
+use nix::{errno::Errno, sys::{signal::{Signal, kill, killpg}, wait::{WaitPidFlag, WaitStatus, waitpid}}, unistd::{ForkResult, Pid, fork, getpgrp, setpgid, tcgetpgrp, tcsetpgrp}};
+
+use crate::{Builtin, Io, Shell, ShellError, error, parser::Node, signals, term};
+
+/// How many statuses of finished jobs are kept for `wait`, which POSIX asks
+/// to be at least `CHILD_MAX`.
+const REAPED_MAX: usize = 1024;
+
+/// The status of a job stopped with Ctrl-Z.
+const STOPPED: i32 = 128 + Signal::SIGTSTP as i32;
+
+pub(crate) struct Job {
+    /// The job number, as in `[1]`, or 0 while it is not in the table.
+    pub(crate) id: usize,
+    pub(crate) pgid: Pid,
+    /// The processes that have not finished yet.
+    pids: Vec<Pid>,
+    /// The last process, whose status is the job's.
+    last: Pid,
+    status: i32,
+    /// The signal that killed the last process, if one did, which a status
+    /// over 128 alone does not tell from `exit 137`.
+    signal: Option<Signal>,
+    /// Whether the last process dumped core when a signal killed it.
+    core_dumped: bool,
+    stopped: bool,
+    pub(crate) command: String,
+}
+
+impl Job {
+    fn new(pgid: Pid, pids: Vec<Pid>, command: String) -> Self {
+        let last = *pids.last().unwrap_or(&pgid);
+        Job { id: 0, pgid, pids, last, status: 0, signal: None, core_dumped: false, stopped: false, command }
+    }
+
+    fn finished(&self) -> bool {
+        self.pids.is_empty()
+    }
+
+    /// Collect what happened to the job's processes. Without `WNOHANG` in
+    /// `flags` this blocks until they have all finished, or one has stopped.
+    fn wait(&mut self, flags: WaitPidFlag) -> Result<(), ShellError> {
+        let mut i = 0;
+        while i < self.pids.len() {
+            let pid = self.pids[i];
+            let status = match waitpid(pid, Some(flags)) {
+                Ok(WaitStatus::Exited(_, code)) => code,
+                Ok(WaitStatus::Signaled(_, signal, core_dumped)) => {
+                    if pid == self.last {
+                        self.signal = Some(signal);
+                        self.core_dumped = core_dumped;
+                    }
+                    128 + signal as i32
+                }
+                Ok(WaitStatus::Stopped(..)) => {
+                    self.stopped = true;
+                    if !flags.contains(WaitPidFlag::WNOHANG) {
+                        return Ok(());
+                    }
+                    i += 1;
+                    continue;
+                }
+                Ok(_) => {
+                    i += 1;
+                    continue;
+                }
+                Err(Errno::EINTR) => continue,
+                // already reaped elsewhere; nothing more will be heard of it
+                Err(Errno::ECHILD) => self.status,
+                Err(e) => return Err(ShellError::sys("waitpid", e)),
+            };
+            if pid == self.last {
+                self.status = status;
+            }
+            self.pids.remove(i);
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> String {
+        match (self.finished(), self.status) {
+            (false, _) if self.stopped => String::from("Stopped"),
+            (false, _) => String::from("Running"),
+            (true, 0) => String::from("Done"),
+            (true, status) => format!("Exit {}", status),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct JobTable {
+    jobs: Vec<Job>,
+    /// The process started most recently in the background, for `$!`.
+    pub(crate) last_pid: Option<Pid>,
+    /// The shell's process group while it does job control.
+    shell_pgid: Option<Pid>,
+    /// The process group that had the terminal before the shell took it.
+    original_pgid: Option<Pid>,
+    /// The last process and status of each job removed once it finished,
+    /// for a later `wait`, the most recent [`REAPED_MAX`] of them.
+    reaped: Vec<(Pid, i32)>,
+    /// The processes of disowned jobs, only waited for so that they do not
+    /// linger as zombies.
+    disowned: Vec<Pid>,
+}
+
+impl JobTable {
+    /// Track a job, numbered one above the highest in use unless it already
+    /// has a number. The job is added last, making it the current one if it
+    /// is stopped or no job is.
+    fn add(&mut self, mut job: Job) -> usize {
+        if job.id == 0 {
+            job.id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        }
+        let id = job.id;
+        self.jobs.push(job);
+        id
+    }
+
+    /// The current job, `%+`, which `fg` and `bg` act on by default: the
+    /// last one stopped, or else the last one started. The previous job,
+    /// `%-`, is the one that would be current without it.
+    fn current(&self, skip: usize) -> Option<&Job> {
+        let mut ranked = self.jobs.iter().rev().filter(|job| job.stopped).chain(self.jobs.iter().rev().filter(|job| !job.stopped));
+        ranked.nth(skip)
+    }
+
+    fn mark(&self, job: &Job) -> char {
+        if self.current(0).is_some_and(|current| current.id == job.id) {
+            '+'
+        } else if self.current(1).is_some_and(|previous| previous.id == job.id) {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    /// A job's line in `jobs` and in the reports of stopped and finished
+    /// jobs.
+    fn describe(&self, job: &Job, pid: bool) -> String {
+        let background = if !job.finished() && !job.stopped { " &" } else { "" };
+        let pid = if pid { format!("{} ", job.pgid) } else { String::new() };
+        format!("[{}]{}  {}{:<24}{}{}", job.id, self.mark(job), pid, job.state(), job.command, background)
+    }
+
+    /// Check on every job without blocking. Those that have finished are
+    /// removed and returned with their descriptions.
+    fn update(&mut self) -> Vec<String> {
+        self.reap_disowned();
+        let flags = self.wait_flags();
+        for job in &mut self.jobs {
+            if let Err(e) = job.wait(flags) {
+                // it cannot be waited for, so it is as good as gone
+                error::warn(e);
+                job.pids.clear();
+            }
+        }
+        let finished: Vec<_> = self.jobs.iter().filter(|job| job.finished()).map(|job| self.describe(job, false)).collect();
+        self.reaped.extend(self.jobs.iter().filter(|job| job.finished()).map(|job| (job.last, job.status)));
+        let excess = self.reaped.len().saturating_sub(REAPED_MAX);
+        self.reaped.drain(..excess);
+        self.jobs.retain(|job| !job.finished());
+        finished
+    }
+
+    /// Collect the disowned processes that have finished, without blocking.
+    fn reap_disowned(&mut self) {
+        self.disowned.retain(|&pid| loop {
+            match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::Exited(..) | WaitStatus::Signaled(..)) | Err(Errno::ECHILD) => break false,
+                Err(Errno::EINTR) => continue,
+                _ => break true,
+            }
+        });
+    }
+
+    /// Take job `i` out of the table, leaving its processes to run on.
+    fn disown(&mut self, i: usize) {
+        let job = self.jobs.remove(i);
+        self.disowned.extend(job.pids);
+    }
+
+    /// The flags to check on jobs with: stops are only seen with job control.
+    fn wait_flags(&self) -> WaitPidFlag {
+        match self.shell_pgid {
+            Some(_) => WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED,
+            None => WaitPidFlag::WNOHANG,
+        }
+    }
+
+    /// Send `signal` to job `i`: to its process group with job control, and
+    /// to each of its processes without, when they share the shell's. A
+    /// stopped job is continued to see a hangup or termination.
+    fn signal(&self, i: usize, signal: Option<Signal>) -> nix::Result<()> {
+        let job = &self.jobs[i];
+        let send = |signal: Option<Signal>| match self.shell_pgid {
+            Some(_) => killpg(job.pgid, signal),
+            None => job.pids.iter().try_for_each(|&pid| kill(pid, signal)),
+        };
+        send(signal)?;
+        if job.stopped && matches!(signal, Some(Signal::SIGHUP | Signal::SIGTERM)) {
+            send(Some(Signal::SIGCONT))?;
+        }
+        Ok(())
+    }
+
+    /// The index of the job named by `spec`: `%n` or `n`, `%+` or `%%` for
+    /// the current job, `%-` for the previous one, `%name` for the one
+    /// whose command starts with `name` and `%?text` for the one containing
+    /// `text`. Without a spec, the current job.
+    fn find(&self, builtin: &str, spec: Option<&str>) -> Result<usize, ShellError> {
+        let no_such_job = |spec: &str| ShellError::Failed(format!("{}: {}: no such job", builtin, spec));
+        let job = match spec.map(|spec| spec.strip_prefix('%').unwrap_or(spec)) {
+            None | Some("" | "+" | "%") => self.current(0).ok_or_else(|| no_such_job("current"))?,
+            Some("-") => self.current(1).ok_or_else(|| no_such_job(spec.unwrap()))?,
+            Some(n) if n.bytes().all(|b| b.is_ascii_digit()) => self.jobs.iter().find(|job| job.id.to_string() == n).ok_or_else(|| no_such_job(spec.unwrap()))?,
+            Some(name) => {
+                let mut matching = self.jobs.iter().filter(|job| match name.strip_prefix('?') {
+                    Some(text) => job.command.contains(text),
+                    None => job.command.starts_with(name),
+                });
+                let job = matching.next().ok_or_else(|| no_such_job(spec.unwrap()))?;
+                if matching.next().is_some() {
+                    return Err(ShellError::Failed(format!("{}: {}: ambiguous job spec", builtin, spec.unwrap())));
+                }
+                job
+            }
+        };
+        Ok(self.jobs.iter().position(|j| j.id == job.id).unwrap())
+    }
+
+    /// In the parent, put a process just forked for a job in the job's
+    /// process group, `pgid` or a new one if `None`. The child does the same,
+    /// whichever of them gets there first.
+    pub(crate) fn place(&self, child: Pid, pgid: Option<Pid>) {
+        if self.shell_pgid.is_some() {
+            let _ = setpgid(child, pgid.unwrap_or(child));
+        }
+    }
+
+    /// In a process just forked for a job, join its process group and take
+    /// the terminal if it runs in the foreground. The process starts with the
+    /// default signal actions, no jobs of its own and no job control.
+    pub(crate) fn enter_child(&mut self, pgid: Option<Pid>, foreground: bool) {
+        if self.shell_pgid.is_some() {
+            let _ = setpgid(Pid::from_raw(0), pgid.unwrap_or(Pid::from_raw(0)));
+            if foreground {
+                let _ = tcsetpgrp(0, getpgrp());
+            }
+        }
+        signals::default_signals();
+        *self = JobTable::default();
+    }
+}
+
+impl Shell {
+    /// Take charge of the terminal for job control, if standard input is one,
+    /// once the shell is in the foreground.
+    pub(crate) fn start_job_control(&mut self) {
+        if !std::io::stdin().is_terminal() {
+            return;
+        }
+        loop {
+            match tcgetpgrp(0) {
+                Ok(foreground) if foreground == getpgrp() => break,
+                // started in the background: wait to be brought to the front
+                Ok(_) => {
+                    let _ = killpg(getpgrp(), Signal::SIGTTIN);
+                }
+                Err(_) => return,
+            }
+        }
+        signals::ignore_job_signals();
+        let original = getpgrp();
+        // a session leader already leads its group and cannot move
+        let _ = setpgid(Pid::from_raw(0), Pid::from_raw(0));
+        if let Err(e) = tcsetpgrp(0, getpgrp()) {
+            error::warn(ShellError::sys("cannot set terminal process group", e));
+            signals::default_job_signals();
+            return;
+        }
+        self.jobs.shell_pgid = Some(getpgrp());
+        self.jobs.original_pgid = Some(original);
+    }
+
+    /// Give the terminal back to the process group that had it, when the
+    /// shell leaves.
+    pub(crate) fn stop_job_control(&mut self) {
+        if let Some(original) = self.jobs.original_pgid.take() {
+            let _ = tcsetpgrp(0, original);
+        }
+        self.jobs.shell_pgid = None;
+    }
+
+    /// Start `node` in a child process without waiting for it.
+    pub(crate) fn run_background(&mut self, node: &Node) -> Result<(), ShellError> {
+        self.reap_jobs();
+        let _ = std::io::stdout().flush();
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                self.jobs.place(child, None);
+                self.jobs.last_pid = Some(child);
+                let id = self.jobs.add(Job::new(child, vec![child], node.to_string()));
+                if self.interactive {
+                    eprintln!("[{}] {}", id, child);
+                }
+                self.last_status = 0;
+                Ok(())
+            }
+            Ok(ForkResult::Child) => {
+                // without job control a background job must not take the
+                // terminal's input from the shell
+                if self.jobs.shell_pgid.is_none() {
+                    if let Err(e) = redirect_stdin_to_null() {
+                        error::warn(e);
+                    }
+                }
+                self.jobs.enter_child(None, false);
+                signals::default_pipe_signal();
+                self.exec_in_place = matches!(node, Node::Simple(_));
+                self.run_node(node);
+                let _ = std::io::stdout().flush();
+                std::process::exit(self.last_status);
+            }
+            Err(e) => Err(ShellError::sys("fork", e)),
+        }
+    }
+
+    /// Wait for the processes of a job just started in the foreground, in
+    /// process group `pgid`, returning its status. If it is stopped it goes
+    /// in the table, described by `command`.
+    pub(crate) fn wait_foreground(&mut self, pgid: Pid, pids: Vec<Pid>, command: impl FnOnce() -> String) -> Result<i32, ShellError> {
+        let mut job = Job::new(pgid, pids, String::new());
+        self.wait_job(&mut job)?;
+        self.jobs.reap_disowned();
+        if job.stopped {
+            job.command = command();
+            self.stopped(job);
+            return Ok(STOPPED);
+        }
+        self.report_signal(&job);
+        Ok(job.status)
+    }
+
+    /// Let `job` have the terminal while waiting for it to finish or stop.
+    fn wait_job(&mut self, job: &mut Job) -> Result<(), ShellError> {
+        let Some(shell) = self.jobs.shell_pgid else {
+            return job.wait(WaitPidFlag::empty());
+        };
+        let _ = tcsetpgrp(0, job.pgid);
+        let result = job.wait(WaitPidFlag::WUNTRACED);
+        let _ = tcsetpgrp(0, shell);
+        if job.stopped {
+            // the job may have left the terminal in its own mode
+            term::restore_mode();
+        } else if job.finished() && job.status == 128 + Signal::SIGINT as i32 {
+            // Ctrl-C went to the job alone; the shell stops what it was
+            // running as if it had been interrupted too
+            signals::set_interrupted();
+        }
+        result
+    }
+
+    /// Say why a foreground job was killed by a signal, as in `Killed` or
+    /// `Quit (core dumped)`. Ctrl-C just moves the prompt to a new line, and
+    /// a closed pipe is not worth mentioning.
+    fn report_signal(&self, job: &Job) {
+        let Some(signal) = job.signal else {
+            return;
+        };
+        if !self.interactive || signal == Signal::SIGPIPE {
+            return;
+        }
+        if signal == Signal::SIGINT {
+            eprintln!();
+            return;
+        }
+        // SAFETY: strsignal returns a valid string for any signal number
+        let description = unsafe { std::ffi::CStr::from_ptr(nix::libc::strsignal(signal as i32)) };
+        eprintln!("{}{}", description.to_string_lossy(), if job.core_dumped { " (core dumped)" } else { "" });
+    }
+
+    /// Put a job stopped in the foreground in the table and say so.
+    fn stopped(&mut self, job: Job) {
+        let id = self.jobs.add(job);
+        let job = self.jobs.jobs.iter().find(|job| job.id == id).unwrap();
+        eprintln!();
+        eprintln!("{}", self.jobs.describe(job, false));
+    }
+
+    /// Wait for job `i` to finish and take it out of the table, returning its
+    /// status. The job is checked on every few milliseconds rather than
+    /// waited for outright, so Ctrl-C or a trapped signal can end the wait,
+    /// which then returns 128 plus the signal's number.
+    fn wait_for(&mut self, i: usize) -> Result<i32, ShellError> {
+        let flags = self.jobs.wait_flags();
+        loop {
+            let job = &mut self.jobs.jobs[i];
+            job.wait(flags)?;
+            if job.finished() {
+                return Ok(self.jobs.jobs.remove(i).status);
+            }
+            if job.stopped {
+                return Ok(STOPPED);
+            }
+            if signals::interrupted() {
+                return Ok(128 + Signal::SIGINT as i32);
+            }
+            if let Some(signal) = signals::pending_trap() {
+                return Ok(128 + signal as i32);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Hang up the jobs still in the table, when the shell itself is.
+    pub(crate) fn hang_up_jobs(&self) {
+        if self.jobs.shell_pgid.is_none() {
+            return;
+        }
+        for i in 0..self.jobs.jobs.len() {
+            let _ = self.jobs.signal(i, Some(Signal::SIGHUP));
+        }
+    }
+
+    /// Reap finished jobs, reporting them if the shell is interactive. Run
+    /// before each prompt.
+    pub(crate) fn reap_jobs(&mut self) {
+        for finished in self.jobs.update() {
+            if self.interactive {
+                eprintln!("{}", finished);
+            }
+        }
+    }
+}
+
+fn redirect_stdin_to_null() -> Result<(), ShellError> {
+    let null = std::fs::File::open("/dev/null").map_err(|e| ShellError::io("/dev/null", e))?;
+    nix::unistd::dup2(std::os::fd::AsRawFd::as_raw_fd(&null), 0).map_err(|e| ShellError::sys("/dev/null", e))?;
+    Ok(())
+}
+
+pub(crate) struct Jobs;
+
+impl Builtin for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "jobs [-lp] [job ...]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (mut pids, mut only_pids) = (false, false);
+        let mut specs = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-l" => pids = true,
+                "-p" => only_pids = true,
+                flag if flag.starts_with('-') && flag.len() > 1 => return Err(ShellError::Usage(format!("jobs: {}: invalid option", flag))),
+                spec => specs.push(spec),
+            }
+        }
+        let finished = shell.jobs.update();
+        let table = &shell.jobs;
+        let listed = match specs.is_empty() {
+            true => (0..table.jobs.len()).collect(),
+            false => specs.iter().map(|spec| table.find("jobs", Some(spec))).collect::<Result<Vec<_>, _>>()?,
+        };
+        for i in listed {
+            let job = &table.jobs[i];
+            let _ = match only_pids {
+                true => writeln!(io.stdout, "{}", job.pgid),
+                false => writeln!(io.stdout, "{}", table.describe(job, pids)),
+            };
+        }
+        if specs.is_empty() && !only_pids {
+            for finished in finished {
+                let _ = writeln!(io.stdout, "{}", finished);
+            }
+        }
+        Ok(0)
+    }
+}
+
+pub(crate) struct Fg;
+
+impl Builtin for Fg {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "fg [job]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if shell.jobs.shell_pgid.is_none() {
+            return Err(ShellError::Failed(String::from("fg: no job control")));
+        }
+        shell.reap_jobs();
+        let i = shell.jobs.find("fg", args.first().map(String::as_str))?;
+        let mut job = shell.jobs.jobs.remove(i);
+        let _ = writeln!(io.stdout, "{}", job.command);
+        let _ = io.stdout.flush();
+        job.stopped = false;
+        let _ = tcsetpgrp(0, job.pgid);
+        if let Err(e) = killpg(job.pgid, Signal::SIGCONT) {
+            shell.jobs.add(job);
+            return Err(ShellError::sys("fg", e));
+        }
+        let result = shell.wait_job(&mut job);
+        if job.stopped {
+            shell.stopped(job);
+            return result.map(|_| STOPPED);
+        }
+        shell.report_signal(&job);
+        result.map(|_| job.status)
+    }
+}
+
+pub(crate) struct Bg;
+
+impl Builtin for Bg {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "bg [job ...]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if shell.jobs.shell_pgid.is_none() {
+            return Err(ShellError::Failed(String::from("bg: no job control")));
+        }
+        shell.reap_jobs();
+        let specs: Vec<Option<&str>> = match args.is_empty() {
+            true => vec![None],
+            false => args.iter().map(|arg| Some(arg.as_str())).collect(),
+        };
+        for spec in specs {
+            let i = shell.jobs.find("bg", spec)?;
+            let job = &mut shell.jobs.jobs[i];
+            if !job.stopped {
+                return Err(ShellError::Failed(format!("bg: job {} already in background", job.id)));
+            }
+            killpg(job.pgid, Signal::SIGCONT).map_err(|e| ShellError::sys("bg", e))?;
+            job.stopped = false;
+            let job = &shell.jobs.jobs[i];
+            let _ = writeln!(io.stdout, "[{}]{} {} &", job.id, shell.jobs.mark(job), job.command);
+        }
+        Ok(0)
+    }
+}
+
+pub(crate) struct Kill;
+
+impl Builtin for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "kill [-s signal | -signal] pid | job ... or kill -l [status ...]"
+    }
+
+    /// Send a signal, `TERM` unless another is named, to processes and
+    /// jobs. `-l` lists the signals, or names those that ended commands
+    /// with the statuses given.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let mut signal = Some(Signal::SIGTERM);
+        let mut targets = args;
+        match args.first().map(String::as_str) {
+            Some("-l" | "-L") => return Ok(list_signals(&args[1..], io)),
+            Some("-s" | "-n") => {
+                let name = args.get(1).ok_or_else(|| ShellError::Usage(format!("kill: {}: option requires an argument", args[0])))?;
+                signal = parse_signal(name)?;
+                targets = &args[2..];
+            }
+            Some("--") => targets = &args[1..],
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                signal = parse_signal(&flag[1..])?;
+                targets = &args[1..];
+            }
+            _ => {}
+        }
+        if targets.first().is_some_and(|arg| arg == "--") {
+            targets = &targets[1..];
+        }
+        if targets.is_empty() {
+            return Err(ShellError::Usage(format!("kill: usage: {}", self.synopsis())));
+        }
+        let mut status = 0;
+        for target in targets {
+            let sent = match target.parse::<i32>() {
+                _ if target.starts_with('%') => shell.jobs.find("kill", Some(target)).and_then(|i| shell.jobs.signal(i, signal).map_err(|e| ShellError::sys(format!("kill: {}", target), e))),
+                Ok(pid) => kill(Pid::from_raw(pid), signal).map_err(|e| ShellError::sys(format!("kill: ({})", pid), e)),
+                Err(_) => Err(ShellError::Failed(format!("kill: {}: arguments must be process or job IDs", target))),
+            };
+            if let Err(e) = sent {
+                let _ = writeln!(io.stderr, "{}", e);
+                status = 1;
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// The signal for `kill` to send; `0` sends none, only checking the
+/// target exists.
+fn parse_signal(name: &str) -> Result<Option<Signal>, ShellError> {
+    if name == "0" {
+        return Ok(None);
+    }
+    signals::parse(name).map(Some).ok_or_else(|| ShellError::Failed(format!("kill: {}: invalid signal specification", name)))
+}
+
+/// `kill -l`: every signal's name, or the name for each status or the
+/// number for each name in `args`.
+fn list_signals(args: &[String], io: &mut Io) -> i32 {
+    if args.is_empty() {
+        for signal in Signal::iterator() {
+            let _ = writeln!(io.stdout, "{}", signals::name(signal));
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in args {
+        let _ = match arg.parse::<i32>() {
+            // a status of a command killed by a signal, or the number itself
+            Ok(number) => match Signal::try_from(if number > 128 { number - 128 } else { number }) {
+                Ok(signal) => writeln!(io.stdout, "{}", signals::name(signal)),
+                Err(_) => {
+                    status = 1;
+                    writeln!(io.stderr, "kill: {}: invalid signal specification", arg)
+                }
+            },
+            Err(_) => match signals::parse(arg) {
+                Some(signal) => writeln!(io.stdout, "{}", signal as i32),
+                None => {
+                    status = 1;
+                    writeln!(io.stderr, "kill: {}: invalid signal specification", arg)
+                }
+            },
+        };
+    }
+    status
+}
+
+pub(crate) struct Wait;
+
+impl Builtin for Wait {
+    fn name(&self) -> &'static str {
+        "wait"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "wait [pid | job ...]"
+    }
+
+    /// Wait for the jobs and processes named, returning the status of the
+    /// last, or for every running job, returning 0.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.is_empty() {
+            shell.jobs.reaped.clear();
+            while let Some(i) = shell.jobs.jobs.iter().position(|job| !job.stopped) {
+                let status = shell.wait_for(i)?;
+                if signals::interrupted() || signals::pending_trap().is_some() {
+                    return Ok(status);
+                }
+            }
+            return Ok(0);
+        }
+        let mut status = 0;
+        for arg in args {
+            let pid = arg.parse::<i32>().ok().map(Pid::from_raw);
+            let job = match pid {
+                _ if arg.starts_with('%') => match shell.jobs.find("wait", Some(arg)) {
+                    Ok(i) => Some(i),
+                    Err(e) => {
+                        let _ = writeln!(io.stderr, "{}", e);
+                        status = 127;
+                        continue;
+                    }
+                },
+                Some(pid) => shell.jobs.jobs.iter().position(|job| job.pgid == pid || job.last == pid || job.pids.contains(&pid)),
+                None => return Err(ShellError::Usage(format!("wait: {}: not a pid or valid job spec", arg))),
+            };
+            if let Some(i) = job {
+                status = shell.wait_for(i)?;
+                continue;
+            }
+            // finished already, and reaped before anything waited for it
+            let reaped = pid.and_then(|pid| shell.jobs.reaped.iter().position(|&(last, _)| last == pid));
+            status = match reaped {
+                Some(i) => shell.jobs.reaped.remove(i).1,
+                None => {
+                    let _ = writeln!(io.stderr, "wait: pid {} is not a child of this shell", arg);
+                    127
+                }
+            };
+        }
+        Ok(status)
+    }
+}
+
+pub(crate) struct Disown;
+
+impl Builtin for Disown {
+    fn name(&self) -> &'static str {
+        "disown"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "disown [-a] [job ...]"
+    }
+
+    /// Remove jobs from the table, the current one unless others are named
+    /// or `-a` removes them all. They keep running, but are no longer
+    /// listed, waited for or hung up by the shell.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.first().is_some_and(|arg| arg == "-a") {
+            while !shell.jobs.jobs.is_empty() {
+                shell.jobs.disown(0);
+            }
+            return Ok(0);
+        }
+        if let Some(flag) = args.first().filter(|arg| arg.starts_with('-')) {
+            return Err(ShellError::Usage(format!("disown: {}: invalid option", flag)));
+        }
+        shell.reap_jobs();
+        let specs: Vec<Option<&str>> = match args.is_empty() {
+            true => vec![None],
+            false => args.iter().map(|arg| Some(arg.as_str())).collect(),
+        };
+        let mut status = 0;
+        for spec in specs {
+            match shell.jobs.find("disown", spec) {
+                Ok(i) => shell.jobs.disown(i),
+                Err(e) => {
+                    let _ = writeln!(io.stderr, "{}", e);
+                    status = 1;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,576 @@
+//! Splits input into words and control operators.
+
+use std::{iter::Peekable, str::Chars};
+
+/// A lexical token. Reserved words such as `if` are ordinary words here; the
+/// parser decides whether they are keywords based on their position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Word(String),
+    Semi,
+    DSemi,
+    Newline,
+    Pipe,
+    /// `&`, which runs the command before it in the background.
+    Amp,
+    /// `&&`
+    AndIf,
+    /// `||`
+    OrIf,
+    LParen,
+    RParen,
+    /// A redirection operator, with the file descriptor written right before
+    /// it, as in `2>`.
+    Redirect(Option<i32>, RedirectOp),
+    /// The body of a here-document, placed after its delimiter word although
+    /// it is read from the lines after the command.
+    HereDoc(String),
+    /// `((expression))` where a command starts, holding the expression.
+    Arith(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectOp {
+    /// `<`
+    Read,
+    /// `>`
+    Write,
+    /// `>>`
+    Append,
+    /// `<&`
+    DupRead,
+    /// `>&`
+    DupWrite,
+    /// `<<` or `<<-`, a here-document.
+    HereDoc,
+    /// `<<<`, a here-string.
+    HereString,
+}
+
+impl RedirectOp {
+    /// The descriptor redirected when none is given.
+    pub fn default_fd(self) -> i32 {
+        match self {
+            RedirectOp::Read | RedirectOp::DupRead | RedirectOp::HereDoc | RedirectOp::HereString => 0,
+            _ => 1,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RedirectOp::Read => "<",
+            RedirectOp::Write => ">",
+            RedirectOp::Append => ">>",
+            RedirectOp::DupRead => "<&",
+            RedirectOp::DupWrite => ">&",
+            RedirectOp::HereDoc => "<<",
+            RedirectOp::HereString => "<<<",
+        }
+    }
+}
+
+/// Pair each token with the line it starts on, counting from `first_line`.
+pub fn number_lines(tokens: Vec<Token>, first_line: usize) -> Vec<(Token, usize)> {
+    let mut line = first_line;
+    tokens.into_iter().map(|token| {
+        let start = line;
+        line += match &token {
+            Token::Newline => 1,
+            // newlines inside quotes or after a backslash stay in the word
+            Token::Word(w) | Token::HereDoc(w) => w.matches('\n').count(),
+            _ => 0,
+        };
+        (token, start)
+    }).collect()
+}
+
+/// Tokenize `src`. Words are separated by blanks and by the operators `;`,
+/// `;;`, `|`, `&`, `(`, `)`, newline and the redirections `<`, `>`, `>>`, `<&`,
+/// `>&`, `<<`, `<<-` and `<<<`, except inside quotes, command substitutions
+/// or after a backslash; a `#` at the start of a word comments out the rest
+/// of the line. The lines after one with `<<` are the here-document, up to
+/// the delimiter; `<<-` strips their leading tabs.
+pub fn tokenize(src: &str) -> Vec<Token> {
+    lex(src).0
+}
+
+/// Tokenize `src`, also returning the quote left open at the end, `(` for
+/// an unfinished `$(`, `{` for an unfinished `${`, `<` for a here-document missing its delimiter, or
+/// `\` if it ends with a backslash that continues the line. The last word
+/// then holds everything after the opening quote.
+pub fn lex(src: &str) -> (Vec<Token>, Option<char>) {
+    let mut tokens = Vec::new();
+    let mut open = None;
+    let mut word = String::new();
+    let mut chars = src.chars().peekable();
+    // here-documents whose bodies start on the next line: the index of the
+    // operator and whether `<<-` strips tabs
+    let mut here_docs = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            // otherwise a subshell in a subshell, which is written `( (`
+            '(' if word.is_empty() && chars.peek() == Some(&'(') && starts_command(&tokens) => {
+                let mut ahead = chars.clone();
+                ahead.next();
+                if let Some(expression) = read_arithmetic(&mut ahead) {
+                    chars = ahead;
+                    tokens.push(Token::Arith(expression));
+                    continue;
+                }
+                tokens.push(Token::LParen);
+            }
+            ' ' | '\t' | ';' | '\n' | '|' | '&' | '(' | ')' => {
+                if !word.is_empty() {
+                    tokens.push(Token::Word(std::mem::take(&mut word)));
+                }
+                match c {
+                    ';' if chars.next_if_eq(&';').is_some() => tokens.push(Token::DSemi),
+                    ';' => tokens.push(Token::Semi),
+                    '\n' => {
+                        tokens.push(Token::Newline);
+                        if !read_here_docs(&mut chars, &mut tokens, std::mem::take(&mut here_docs)) {
+                            open = Some('<');
+                        }
+                    }
+                    '|' if chars.next_if_eq(&'|').is_some() => tokens.push(Token::OrIf),
+                    '|' => tokens.push(Token::Pipe),
+                    '&' if chars.next_if_eq(&'&').is_some() => tokens.push(Token::AndIf),
+                    '&' => tokens.push(Token::Amp),
+                    '(' => tokens.push(Token::LParen),
+                    ')' => tokens.push(Token::RParen),
+                    _ => {}
+                }
+            }
+            // `<(commands)` and `>(commands)`, a process substitution
+            '<' | '>' if chars.peek() == Some(&'(') => {
+                chars.next();
+                word.push(c);
+                if !lex_substitution(&mut chars, &mut word) {
+                    open = Some('(');
+                }
+            }
+            '<' | '>' => {
+                // a number right before the operator is the descriptor redirected
+                let fd = match word.parse() {
+                    Ok(fd) if word.bytes().all(|b| b.is_ascii_digit()) => {
+                        word.clear();
+                        Some(fd)
+                    }
+                    _ => None,
+                };
+                if !word.is_empty() {
+                    tokens.push(Token::Word(std::mem::take(&mut word)));
+                }
+                let op = match c {
+                    '<' if chars.next_if_eq(&'<').is_some() => match chars.next_if(|&c| c == '<' || c == '-') {
+                        Some('<') => RedirectOp::HereString,
+                        strip => {
+                            here_docs.push((tokens.len(), strip.is_some()));
+                            RedirectOp::HereDoc
+                        }
+                    },
+                    '<' if chars.next_if_eq(&'&').is_some() => RedirectOp::DupRead,
+                    '<' => RedirectOp::Read,
+                    _ if chars.next_if_eq(&'>').is_some() => RedirectOp::Append,
+                    _ if chars.next_if_eq(&'&').is_some() => RedirectOp::DupWrite,
+                    _ => RedirectOp::Write,
+                };
+                tokens.push(Token::Redirect(fd, op));
+            }
+            '#' if word.is_empty() => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            // quotes are kept in the word for the expander to interpret
+            '\'' => {
+                word.push(c);
+                open = Some(c);
+                for c in chars.by_ref() {
+                    word.push(c);
+                    if c == '\'' {
+                        open = None;
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                word.push(c);
+                open = Some(c);
+                while let Some(c) = chars.next() {
+                    word.push(c);
+                    match c {
+                        '\\' => word.extend(chars.next()),
+                        '"' => {
+                            open = None;
+                            break;
+                        }
+                        '$' if chars.next_if_eq(&'(').is_some() && !lex_substitution(&mut chars, &mut word) => {
+                            open = Some('(');
+                            break;
+                        }
+                        '$' if chars.next_if_eq(&'{').is_some() && !lex_braced(&mut chars, &mut word) => {
+                            open = Some('{');
+                            break;
+                        }
+                        '`' if !lex_backquoted(&mut chars, &mut word) => {
+                            open = Some('`');
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            '$' if chars.next_if_eq(&'(').is_some() => {
+                word.push('$');
+                if !lex_substitution(&mut chars, &mut word) {
+                    open = Some('(');
+                }
+            }
+            // blanks and operators inside `${...}` are part of the word
+            '$' if chars.next_if_eq(&'{').is_some() => {
+                word.push('$');
+                if !lex_braced(&mut chars, &mut word) {
+                    open = Some('{');
+                }
+            }
+            '`' => {
+                word.push(c);
+                if !lex_backquoted(&mut chars, &mut word) {
+                    open = Some('`');
+                }
+            }
+            '\\' => {
+                word.push(c);
+                // a backslash-newline joins lines, so at the end it needs another
+                match chars.next() {
+                    Some(c) => {
+                        word.push(c);
+                        if c == '\n' && chars.peek().is_none() {
+                            open = Some('\\');
+                        }
+                    }
+                    None => open = Some('\\'),
+                }
+            }
+            _ => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(Token::Word(word));
+    }
+    if !here_docs.is_empty() && open.is_none() {
+        open = Some('<');
+    }
+    (tokens, open)
+}
+
+/// Whether a command can start after `tokens`.
+fn starts_command(tokens: &[Token]) -> bool {
+    match tokens.last() {
+        None | Some(Token::Semi | Token::DSemi | Token::Newline | Token::Pipe | Token::Amp | Token::AndIf | Token::OrIf | Token::LParen) => true,
+        Some(Token::Word(w)) => matches!(w.as_str(), "if" | "then" | "elif" | "else" | "while" | "until" | "do" | "{" | "!" | "time"),
+        _ => false,
+    }
+}
+
+/// Read an arithmetic expression, just after its `((`, up to the `))` that
+/// closes it; `None` if the parentheses close some other way or not at all.
+fn read_arithmetic(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut expression = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => return chars.next_if_eq(&')').map(|_| expression),
+            _ => {}
+        }
+        expression.push(c);
+    }
+    None
+}
+
+/// Read the bodies of the here-documents started on the line just ended,
+/// one after another, each up to the line holding its delimiter. A body
+/// goes right after the delimiter word. Returns whether they were all
+/// finished.
+fn read_here_docs(chars: &mut Peekable<Chars>, tokens: &mut Vec<Token>, here_docs: Vec<(usize, bool)>) -> bool {
+    for (n, (operator, strip)) in here_docs.into_iter().enumerate() {
+        // each body already inserted moves the later operators along
+        let operator = operator + n;
+        let Some(Token::Word(delimiter)) = tokens.get(operator + 1) else {
+            // the parser rejects an operator without a word
+            continue;
+        };
+        let delimiter = here_doc_delimiter(delimiter);
+        let mut body = String::new();
+        loop {
+            if chars.peek().is_none() {
+                return false;
+            }
+            let mut line: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+            if strip {
+                line = line.trim_start_matches('\t').to_string();
+            }
+            if line == delimiter {
+                break;
+            }
+            body.push_str(&line);
+            body.push('\n');
+        }
+        tokens.insert(operator + 2, Token::HereDoc(body));
+    }
+    true
+}
+
+/// The delimiter a here-document's word stands for, with its quotes removed.
+pub(crate) fn here_doc_delimiter(word: &str) -> String {
+    let mut delimiter = String::new();
+    let mut chars = word.chars();
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', None | Some('"')) => delimiter.extend(chars.next()),
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (c, _) => delimiter.push(c),
+        }
+    }
+    delimiter
+}
+
+/// Copy a `$(...)` substitution into `word`, after its `$` and up to and
+/// including the matching `)`, returning whether there was one.
+fn lex_substitution(chars: &mut Peekable<Chars>, word: &mut String) -> bool {
+    word.push('(');
+    match read_substitution(chars) {
+        Some(body) => {
+            word.push_str(&body);
+            word.push(')');
+            true
+        }
+        None => false,
+    }
+}
+
+/// Copy a `${...}` parameter into `word`, after its `$` and up to and
+/// including the `}` that closes it, skipping quoted text and nested braces,
+/// returning whether there was one.
+fn lex_braced(chars: &mut Peekable<Chars>, word: &mut String) -> bool {
+    word.push('{');
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        word.push(c);
+        match c {
+            '\\' => word.extend(chars.next()),
+            '\'' | '"' => {
+                while let Some(q) = chars.next() {
+                    word.push(q);
+                    match q {
+                        '\\' if c == '"' => word.extend(chars.next()),
+                        q if q == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return true,
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Copy a backquoted substitution into `word`, after its opening `` ` `` and
+/// up to and including the closing one, returning whether there was one.
+fn lex_backquoted(chars: &mut Peekable<Chars>, word: &mut String) -> bool {
+    while let Some(c) = chars.next() {
+        word.push(c);
+        match c {
+            '\\' => word.extend(chars.next()),
+            '`' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Read the commands of a `$(...)` substitution, just after the `(`, up to
+/// the matching `)`; `None` if there is none. Parentheses nest, except
+/// inside quotes or after a backslash.
+pub(crate) fn read_substitution(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut body = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            ')' if depth == 0 => return Some(body),
+            ')' => depth -= 1,
+            '(' => depth += 1,
+            '\\' => {
+                body.push(c);
+                body.push(chars.next()?);
+                continue;
+            }
+            '\'' | '"' => {
+                body.push(c);
+                while let Some(q) = chars.next() {
+                    body.push(q);
+                    if q == '\\' && c == '"' {
+                        body.push(chars.next()?);
+                    } else if q == c {
+                        break;
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+        body.push(c);
+    }
+    None
+}
+
+/// Read the commands of a backquoted substitution, just after the opening
+/// `` ` ``, up to the closing one, removing the backslashes before `$`,
+/// `` ` `` and `\\`; `None` if it is not closed.
+pub(crate) fn read_backquoted(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut body = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => return Some(body),
+            '\\' => match chars.next()? {
+                c @ ('$' | '`' | '\\') => body.push(c),
+                c => {
+                    body.push('\\');
+                    body.push(c);
+                }
+            },
+            _ => body.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Shell, parser::{ParseError, parse}};
+
+    fn words(src: &str) -> Vec<String> {
+        tokenize(src).into_iter().map(|token| match token {
+            Token::Word(w) => w,
+            token => panic!("not a word: {:?}", token),
+        }).collect()
+    }
+
+    /// The arguments `src` expands to.
+    fn fields(src: &str) -> Vec<String> {
+        Shell::new().expand_words(&words(src)).unwrap()
+    }
+
+    #[test]
+    fn quotes_keep_blanks_and_operators() {
+        assert_eq!(fields(r#"echo "hello world" 'a;b|c' x\ y"#), ["echo", "hello world", "a;b|c", "x y"]);
+    }
+
+    #[test]
+    fn quoted_pieces_join_into_one_word() {
+        assert_eq!(words(r#"a"b c"'d'e"#), [r#"a"b c"'d'e"#]);
+        assert_eq!(fields(r#"a"b c"'d'e"#), ["ab cde"]);
+    }
+
+    #[test]
+    fn braced_parameters_are_one_word_part() {
+        assert_eq!(words("echo ${x:-hello world} ${y:-a;b|c}x"), ["echo", "${x:-hello world}", "${y:-a;b|c}x"]);
+        assert_eq!(words(r#"${x:-'}'} "${y:-"}"}""#), [r#"${x:-'}'}"#, r#""${y:-"}"}""#]);
+        assert_eq!(parse("echo ${x").unwrap_err(), ParseError::UnterminatedQuote('{'));
+    }
+
+    #[test]
+    fn empty_quotes_are_an_empty_argument() {
+        assert_eq!(fields(r#"printf '' "" x"#), ["printf", "", "", "x"]);
+    }
+
+    #[test]
+    fn quotes_nest_only_as_literal_characters() {
+        assert_eq!(fields(r#"echo "it's" 'say "hi"'"#), ["echo", "it's", r#"say "hi""#]);
+        // backslash is literal inside single quotes, an escape inside double
+        assert_eq!(fields(r#"'a\"b' "a\"b" "a\b""#), [r#"a\"b"#, r#"a"b"#, r"a\b"]);
+    }
+
+    #[test]
+    fn backslash_escapes_quotes_and_itself() {
+        assert_eq!(fields(r#"\"a\" \'b\' c\\d"#), [r#""a""#, "'b'", r"c\d"]);
+    }
+
+    #[test]
+    fn comments_only_start_words() {
+        assert_eq!(fields("echo a#b '#c' # d"), ["echo", "a#b", "#c"]);
+    }
+
+    #[test]
+    fn backslash_newline_continues_the_line() {
+        assert_eq!(fields("echo one \\\ntwo"), ["echo", "one", "two"]);
+        assert_eq!(parse("echo one \\\n").unwrap_err(), ParseError::Incomplete);
+    }
+
+    #[test]
+    fn unterminated_quotes_need_more_input() {
+        assert_eq!(lex("echo 'abc").1, Some('\''));
+        assert_eq!(lex("echo \"a'b").1, Some('"'));
+        assert_eq!(lex(r#"echo "a\""#).1, Some('"'));
+        assert_eq!(lex("echo 'a\"b'").1, None);
+        assert_eq!(parse("echo 'abc").unwrap_err(), ParseError::UnterminatedQuote('\''));
+        assert!(parse("echo \"abc\n").unwrap_err().is_incomplete());
+    }
+
+    #[test]
+    fn quotes_span_lines() {
+        assert!(parse("echo 'a\nb'").is_ok());
+        assert_eq!(fields("echo \"a\nb\""), ["echo", "a\nb"]);
+    }
+
+    #[test]
+    fn redirections_take_a_leading_number() {
+        assert_eq!(tokenize("a 2>>b"), [Token::Word("a".into()), Token::Redirect(Some(2), RedirectOp::Append), Token::Word("b".into())]);
+        assert_eq!(tokenize("a2>b"), [Token::Word("a2".into()), Token::Redirect(None, RedirectOp::Write), Token::Word("b".into())]);
+        assert_eq!(tokenize("'2'>b"), [Token::Word("'2'".into()), Token::Redirect(None, RedirectOp::Write), Token::Word("b".into())]);
+    }
+
+    #[test]
+    fn unquoted_tilde_is_the_home_directory() {
+        let mut shell = Shell::new();
+        let home = shell.home.display().to_string();
+        assert_eq!(shell.expand_words(&words("echo ~ ~/a '~' \\~ a~ ~/'b c'")).unwrap(), ["echo".to_string(), home.clone(), format!("{}/a", home), "~".into(), "~".into(), "a~".into(), format!("{}/b c", home)]);
+    }
+
+    #[test]
+    fn doubled_operators_are_one_token() {
+        assert_eq!(tokenize("a&&b||c|d&"), [Token::Word("a".into()), Token::AndIf, Token::Word("b".into()), Token::OrIf, Token::Word("c".into()), Token::Pipe, Token::Word("d".into()), Token::Amp]);
+        assert_eq!(tokenize("a & & b"), [Token::Word("a".into()), Token::Amp, Token::Amp, Token::Word("b".into())]);
+    }
+
+    #[test]
+    fn here_document_bodies_follow_their_delimiter() {
+        assert_eq!(tokenize("cat <<a <<-\"b\" x\none\na\n\ttwo\n\tb\nls"), [
+            Token::Word("cat".into()), Token::Redirect(None, RedirectOp::HereDoc), Token::Word("a".into()), Token::HereDoc("one\n".into()),
+            Token::Redirect(None, RedirectOp::HereDoc), Token::Word("\"b\"".into()), Token::HereDoc("two\n".into()),
+            Token::Word("x".into()), Token::Newline, Token::Word("ls".into()),
+        ]);
+        assert_eq!(tokenize("a <<<b"), [Token::Word("a".into()), Token::Redirect(None, RedirectOp::HereString), Token::Word("b".into())]);
+        assert_eq!(here_doc_delimiter("'E'O\"F\"\\!"), "EOF!");
+        assert_eq!(parse("cat <<EOF\nbody\n").unwrap_err(), ParseError::Incomplete);
+    }
+
+    #[test]
+    fn substitutions_stay_in_their_word() {
+        assert_eq!(words("echo $(a | b; c) x`d e`\"$(f \")\")\""), ["echo", "$(a | b; c)", "x`d e`\"$(f \")\")\""]);
+        assert_eq!(words("echo $(a $(b) ')')"), ["echo", "$(a $(b) ')')"]);
+        assert_eq!(fields("echo $(echo a   b) \"$(echo a   b)\""), ["echo", "a", "b", "a b"]);
+        assert_eq!(parse("echo $(a").unwrap_err(), ParseError::UnterminatedQuote('('));
+        assert_eq!(words("diff <(sort a) >(tee b)"), ["diff", "<(sort a)", ">(tee b)"]);
+        assert_eq!(tokenize("((i < (2))); x ((y))"), [Token::Arith("i < (2)".into()), Token::Semi, Token::Word("x".into()), Token::LParen, Token::LParen, Token::Word("y".into()), Token::RParen, Token::RParen]);
+        assert_eq!(tokenize("((a) )"), [Token::LParen, Token::LParen, Token::Word("a".into()), Token::RParen, Token::RParen]);
+        assert_eq!(tokenize("cat < <(ls)"), [Token::Word("cat".into()), Token::Redirect(None, RedirectOp::Read), Token::Word("<(ls)".into())]);
+    }
+}
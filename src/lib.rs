@@ -0,0 +1,99 @@
+//! mash, a shell.
+//!
+//! The `mash` binary is a thin wrapper around [`Shell`], which can also be
+//! embedded to run shell commands from another program:
+//!
+//! ```no_run
+//! let mut shell = mash::Shell::new();
+//! let status = shell.eval("for f in a b; do echo $f; done").unwrap();
+//! std::process::exit(status);
+//! ```
+
+mod alias;
+mod arith;
+mod builtins;
+pub mod complete;
+mod config;
+mod debug;
+mod dirhistory;
+mod dirs;
+mod dirstack;
+mod editor;
+mod envfile;
+mod error;
+mod exec;
+mod expand;
+mod git;
+mod hash;
+mod highlight;
+mod history;
+mod hook;
+mod interactive;
+mod jobs;
+mod lexer;
+mod logging;
+mod lookup;
+mod notify;
+mod parser;
+mod pattern;
+pub mod plugin;
+mod posix;
+mod process;
+mod prompt;
+mod read;
+mod redirect;
+mod restricted;
+mod shell;
+mod shutdown;
+mod signals;
+mod startup;
+mod substitution;
+mod suggest;
+mod term;
+mod timeout;
+mod timing;
+mod trap;
+mod vars;
+
+pub use builtins::{Builtin, Io};
+pub use complete::{Completion, Names};
+pub use error::ShellError;
+pub use interactive::RcFile;
+pub use logging::init_logging;
+pub use parser::ParseError;
+pub use plugin::Plugin;
+pub use shell::{ExecResult, Shell};
+
+/// Hooks for the benchmarks in `benches/`, which can only reach the public
+/// API. Not meant for other use.
+#[doc(hidden)]
+pub mod bench {
+    use std::path::Path;
+
+    use rustyline::{Editor, Helper, history::FileHistory};
+
+    pub use crate::exec::find_program;
+
+    pub fn tokenize(source: &str) -> usize {
+        crate::lexer::tokenize(source).len()
+    }
+
+    pub fn parse(source: &str) -> Result<(), crate::ParseError> {
+        crate::check_syntax(source)
+    }
+
+    /// Recompute the prompt for the working directory, as after `cd`.
+    pub fn render_prompt(shell: &mut crate::Shell) -> String {
+        let _ = shell.update_prompt();
+        shell.editor_prompt()
+    }
+
+    pub fn append_history<H: Helper>(rl: &mut Editor<H, FileHistory>, file: &Path, entry: &str) {
+        let _ = crate::history::add(rl, file, &mut crate::history::Shared::default(), entry);
+    }
+}
+
+/// Parse `source` without running it, reporting the first syntax error.
+pub fn check_syntax(source: &str) -> Result<(), ParseError> {
+    parser::parse(source).map(|_| ())
+}
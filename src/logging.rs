@@ -0,0 +1,46 @@
+//! Internal diagnostics, written to stderr through `tracing`. Nothing is
+//! shown unless asked for with `--log-level`, `RUST_LOG` or `set -o debug`;
+//! messages meant for users are printed directly instead.
+//!
+//! Spans mark the phases of running a command (`parse`, `expand`, `exec`,
+//! `builtin`, `external`), so each event says which command it came from.
+
+use std::{io::IsTerminal, sync::OnceLock};
+
+use tracing_subscriber::{EnvFilter, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt};
+
+struct Filter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// The filter given at startup, restored by `set +o debug`.
+    initial: String,
+}
+
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+/// Send diagnostics to stderr. `level` is a level such as `debug`, or any
+/// `RUST_LOG`-style filter, and takes precedence over `RUST_LOG`; without
+/// either only errors are shown.
+pub fn init_logging(level: Option<&str>) -> Result<(), String> {
+    let initial = match level {
+        Some(level) => level.to_string(),
+        None => std::env::var("RUST_LOG").unwrap_or_else(|_| String::from("error")),
+    };
+    let filter = EnvFilter::try_new(&initial).map_err(|e| format!("{}: {}", initial, e))?;
+    let (filter, handle) = reload::Layer::new(filter);
+    let format = tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_ansi(std::io::stderr().is_terminal()).with_target(false);
+    tracing_subscriber::registry().with(filter).with(format).try_init().map_err(|e| e.to_string())?;
+    let _ = FILTER.set(Filter { handle, initial });
+    Ok(())
+}
+
+/// `set -o debug`: show all of mash's diagnostics, or go back to the filter
+/// given at startup.
+pub(crate) fn set_debug(enable: bool) {
+    let Some(filter) = FILTER.get() else {
+        return;
+    };
+    let directive = if enable { "mash=trace" } else { &filter.initial };
+    if let Ok(new) = EnvFilter::try_new(directive) {
+        let _ = filter.handle.reload(new);
+    }
+}
@@ -0,0 +1,231 @@
+//! What a command name stands for: `type` says, `which` finds programs on
+//! `PATH`, and `command` runs a name skipping aliases and functions.
+//!
+//! A name is looked up the way the shell runs it: as an alias, a reserved
+//! word, a function, a builtin and then a program, the first of these
+//! winning.
+
+use std::{os::unix::fs::PermissionsExt, path::PathBuf};
+
+use crate::{Builtin, Io, Shell, ShellError, exec::{find_program, quote}, parser};
+
+/// The `PATH` for `command -p`, where the standard utilities are found
+/// whatever the user's is.
+const DEFAULT_PATH: &str = "/usr/bin:/bin:/usr/sbin:/sbin";
+
+/// One of the things a name can refer to.
+enum Kind {
+    Alias(String),
+    Keyword,
+    Function,
+    Builtin,
+    File(PathBuf),
+}
+
+impl Kind {
+    /// The word `type -t` prints.
+    fn word(&self) -> &'static str {
+        match self {
+            Kind::Alias(_) => "alias",
+            Kind::Keyword => "keyword",
+            Kind::Function => "function",
+            Kind::Builtin => "builtin",
+            Kind::File(_) => "file",
+        }
+    }
+}
+
+impl Shell {
+    /// What `name` refers to, in the order they are tried. Only the first is
+    /// returned unless `all` is set, which also lists every program of that
+    /// name on `PATH`.
+    fn lookup(&self, name: &str, all: bool) -> Vec<Kind> {
+        let mut kinds = Vec::new();
+        if let Some(value) = self.aliases.get(name) {
+            kinds.push(Kind::Alias(value.clone()));
+        }
+        // `time` is a keyword only where a pipeline starts, so the parser
+        // does not reserve it
+        if parser::RESERVED.contains(&name) || name == "time" {
+            kinds.push(Kind::Keyword);
+        }
+        if self.functions.contains_key(name) {
+            kinds.push(Kind::Function);
+        }
+        if self.builtins.get(name).is_some() && self.builtin_enabled(name) {
+            kinds.push(Kind::Builtin);
+        }
+        if all {
+            kinds.extend(path_programs(name, &self.var("PATH").unwrap_or_default(), self).into_iter().map(Kind::File));
+        } else if kinds.is_empty() {
+            kinds.extend(find_program(name, self.var("PATH").as_deref(), &self.path).ok().map(Kind::File));
+        }
+        if !all {
+            kinds.truncate(1);
+        }
+        kinds
+    }
+
+    /// Describe `kind` as `type` does.
+    fn describe_kind(&self, name: &str, kind: &Kind) -> String {
+        match kind {
+            Kind::Alias(value) => format!("{} is aliased to `{}'", name, value),
+            Kind::Keyword => format!("{} is a shell keyword", name),
+            Kind::Function => format!("{} is a function", name),
+            Kind::Builtin => format!("{} is a shell builtin", name),
+            Kind::File(path) => format!("{} is {}", name, path.display()),
+        }
+    }
+}
+
+/// Every executable file called `name` in the directories of `path`, or
+/// `name` itself if it has a slash.
+fn path_programs(name: &str, path: &str, shell: &Shell) -> Vec<PathBuf> {
+    if name.contains('/') {
+        return find_program(name, None, &shell.path).into_iter().collect();
+    }
+    path.split(':').map(|dir| shell.path.join(if dir.is_empty() { "." } else { dir }).join(name))
+        .filter(|program| program.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0))
+        .collect()
+}
+
+/// Split leading single-letter options off `args`, checking them against
+/// `allowed`.
+fn options<'a>(builtin: &str, allowed: &str, args: &'a [String]) -> Result<(Vec<char>, &'a [String]), ShellError> {
+    let mut flags = Vec::new();
+    let mut rest = args;
+    while let Some(arg) = rest.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+        rest = &rest[1..];
+        if arg == "--" {
+            break;
+        }
+        for c in arg[1..].chars() {
+            if !allowed.contains(c) {
+                return Err(ShellError::Usage(format!("{}: -{}: invalid option", builtin, c)));
+            }
+            flags.push(c);
+        }
+    }
+    Ok((flags, rest))
+}
+
+pub(crate) struct Type;
+
+impl Builtin for Type {
+    fn name(&self) -> &'static str {
+        "type"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "type [-apt] name ..."
+    }
+
+    /// Say what each name is. `-a` lists everything it could be, `-t`
+    /// prints just the kind and `-p` just the path of a program.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (flags, names) = options("type", "apt", args)?;
+        let kind_only = flags.contains(&'t');
+        let path_only = flags.contains(&'p');
+        let mut status = 0;
+        for name in names {
+            let kinds = shell.lookup(name, flags.contains(&'a'));
+            if kinds.is_empty() {
+                if !kind_only && !path_only {
+                    let _ = writeln!(io.stderr, "type: {}: not found", name);
+                }
+                status = 1;
+            }
+            for kind in &kinds {
+                let _ = match kind {
+                    _ if kind_only => writeln!(io.stdout, "{}", kind.word()),
+                    Kind::File(path) if path_only => writeln!(io.stdout, "{}", path.display()),
+                    _ if path_only => Ok(()),
+                    kind => writeln!(io.stdout, "{}", shell.describe_kind(name, kind)),
+                };
+            }
+        }
+        Ok(status)
+    }
+}
+
+pub(crate) struct Which;
+
+impl Builtin for Which {
+    fn name(&self) -> &'static str {
+        "which"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "which [-a] name ..."
+    }
+
+    /// Print the program on `PATH` each name runs, or with `-a` all of
+    /// them. Fails if any name has none.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (flags, names) = options("which", "a", args)?;
+        let path = shell.var("PATH").unwrap_or_default();
+        let mut status = 0;
+        for name in names {
+            let mut programs = path_programs(name, &path, shell);
+            if !flags.contains(&'a') {
+                programs.truncate(1);
+            }
+            if programs.is_empty() {
+                status = 1;
+            }
+            for program in programs {
+                let _ = writeln!(io.stdout, "{}", program.display());
+            }
+        }
+        Ok(status)
+    }
+}
+
+pub(crate) struct Command;
+
+impl Builtin for Command {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "command [-pvV] name [arg ...]"
+    }
+
+    /// Run a builtin or program, bypassing any alias or function of that
+    /// name; `-p` searches a default `PATH`. `-v` prints how the name would
+    /// be run instead, and `-V` describes it as `type` does.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (flags, words) = options("command", "pvV", args)?;
+        let Some(name) = words.first() else {
+            return Ok(0);
+        };
+        if flags.contains(&'v') || flags.contains(&'V') {
+            let Some(kind) = shell.lookup(name, false).pop() else {
+                if flags.contains(&'V') {
+                    let _ = writeln!(io.stderr, "command: {}: not found", name);
+                }
+                return Ok(1);
+            };
+            let _ = match (&kind, flags.contains(&'V')) {
+                (_, true) => writeln!(io.stdout, "{}", shell.describe_kind(name, &kind)),
+                (Kind::Alias(value), false) => writeln!(io.stdout, "alias {}={}", name, quote(value)),
+                (Kind::File(path), false) => writeln!(io.stdout, "{}", path.display()),
+                (_, false) => writeln!(io.stdout, "{}", name),
+            };
+            return Ok(0);
+        }
+        if let Some(builtin) = shell.builtins.get(name).filter(|_| shell.builtin_enabled(name)) {
+            return builtin.run(shell, &words[1..], io);
+        }
+        let program = match flags.contains(&'p') {
+            true => {
+                shell.restrict("command -p")?;
+                find_program(name, Some(DEFAULT_PATH), &shell.path)?
+            }
+            false => shell.hashed_program(name)?,
+        };
+        let _ = io.stdout.flush();
+        shell.execute_external(&program, words.to_vec(), &[])
+    }
+}
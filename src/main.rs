@@ -1,178 +1,115 @@
-use std::{path::PathBuf, ffi::CString};
-
-use nix::{unistd::{ForkResult, fork, execvp, chdir}, sys::wait::wait};
-use rustyline::{DefaultEditor, KeyEvent, Cmd};
-
-struct Shell {
-    prompt: String,
-    path: PathBuf,
-    current_command: Option<Command>,
-    home: PathBuf,
-}
-
-impl Default for Shell {
-    fn default() -> Self {
-        let home = std::env::var("HOME").unwrap_or(String::from("/"));
-        Self {
-            prompt: format!("{} % ", home),
-            path: PathBuf::from(&home),
-            current_command: None,
-            home: PathBuf::from(home),
-        }
-    }
-}
-
-impl Shell {
-    fn execute(&mut self) -> Result<(), String> {
-        if let Some(command) = &self.current_command {
-            match command.name.as_str() {
-                "cd" => {
-                    if let Some(path) = command.args.get(0) {
-                        let path = PathBuf::from(path);
-                        if path.is_relative() {
-                            self.path.push(path);
-                        } else {
-                            self.path = path;
-                        }
-                        self.prompt = format!("{} % ", self.path.to_str().ok_or("Unable to convert path to str")?);
-                    } else {
-                        self.path = self.home.clone();
-                    }
-                    self.prompt = format!("{} % ", self.path.canonicalize().map_err(|e| e.to_string())?.display());
-                    chdir(self.path.as_os_str()).map_err(|e| e.to_string())?;
-                },
-                _ => {
-                    command.execute_external(&self.path)?;
-                }
-            }
-        }
-        Ok(())
-    }
-}
-
-struct Command {
-    name: String,
+use std::{io::IsTerminal, path::{Path, PathBuf}};
+
+use clap::Parser;
+use mash::{RcFile, Shell};
+
+/// mash, a shell
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Read commands from COMMAND; any further arguments set $0, $1, ...
+    #[arg(short = 'c', value_name = "COMMAND")]
+    command: Option<String>,
+
+    /// Force an interactive shell even if stdin is not a terminal
+    #[arg(short = 'i')]
+    interactive: bool,
+
+    /// Act as a login shell
+    #[arg(short = 'l', long)]
+    login: bool,
+
+    /// Do not read the interactive startup file
+    #[arg(long)]
+    norc: bool,
+
+    /// Read FILE instead of ~/.mashrc in interactive shells
+    #[arg(long, value_name = "FILE")]
+    rcfile: Option<PathBuf>,
+
+    /// Do not read the login profile files
+    #[arg(long)]
+    noprofile: bool,
+
+    /// Check the script or COMMAND for syntax errors without running it
+    #[arg(short = 'n')]
+    noexec: bool,
+
+    /// Run the script or COMMAND under the interactive debugger
+    #[arg(long)]
+    debug: bool,
+
+    /// Show internal diagnostics at LEVEL (error, warn, info, debug or trace)
+    /// or matching a RUST_LOG-style filter
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Turn off mash extensions and follow POSIX sh more closely
+    #[arg(long)]
+    posix: bool,
+
+    /// Keep the shell to its directory and PATH once its startup files have
+    /// been read
+    #[arg(short = 'r', long)]
+    restricted: bool,
+
+    /// Print how long each part of starting an interactive shell took
+    #[arg(long)]
+    profile_startup: bool,
+
+    /// Script to run, followed by its arguments
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "SCRIPT [ARGS]")]
     args: Vec<String>,
 }
 
-impl Command {
-    fn new(name: String, args: Vec<String>) -> Self {
-        Self {
-            name,
-            args,
-        }
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = mash::init_logging(cli.log_level.as_deref()) {
+        eprintln!("mash: --log-level: {}", e);
+        std::process::exit(2);
     }
-
-    fn parse(line: &str) -> Self {
-        let mut parts = line.split_whitespace();
-        let name = parts.next().unwrap_or("").to_string();
-        let args = parts.map(|s| s.to_string()).collect();
-        Self::new(name, args)
+    let arg0 = std::env::args().next().unwrap_or_default();
+    // like other shells, a leading `-` in argv[0] means we were started by login(1)
+    let login = cli.login || arg0.starts_with('-');
+    // run as `sh`, behave like one
+    let posix = cli.posix || Path::new(arg0.trim_start_matches('-')).file_name().is_some_and(|name| name == "sh");
+    let interactive = cli.command.is_none() && cli.args.is_empty() && (cli.interactive || std::io::stdin().is_terminal());
+
+    let mut shell = if interactive { Shell::new_interactive() } else { Shell::new() };
+    if cli.profile_startup {
+        shell.profile_startup();
     }
-
-    fn execute_external(&self, workdir: &PathBuf) -> Result<(), String> {
-        match unsafe { fork() } {
-            Ok(ForkResult::Parent { .. }) => {
-                // parent process
-                // wait for child process to finish
-                wait().map_err(|e| e.to_string())?;
-            }
-
-            Ok(ForkResult::Child) => {
-                chdir(workdir.as_os_str()).map_err(|e| e.to_string())?;
-                let cmd = CString::new(self.name.clone()).map_err(|e| e.to_string())?;
-                let mut args = self.args.iter().map(|arg| CString::new(arg.clone()).log_expect("Failed to create CString for args")).collect::<Vec<_>>();
-                args.insert(0, cmd.clone());
-                execvp(&cmd, &args).map_err(|e| e.to_string())?;
-            }
-
-            Err(_) => {
-                return Err(String::from("Failed to fork process"));
-            }
-        }
-        Ok(())
+    shell.set_option("noexec", cli.noexec);
+    shell.set_option("posix", posix);
+    if cli.debug && !interactive {
+        shell.enable_debugger();
     }
-}
-
-fn main() -> Result<(), ()> {
-    // start main loop
-    // print prompt and read input
-    // call fork and exec system calls
-    // wait for child process to finish
-    // repeat
-
-    env_logger::init();
-
-    let mut rl = DefaultEditor::new().log_expect("Failed to create editor");
-    if rl.load_history(".mash_history").is_err() {
-        std::fs::File::create(".mash_history").log_expect("Failed to create history file");
+    if login && !cli.noprofile {
+        shell.source_profile();
     }
-    let mut shell = Shell::default();
-    rl.bind_sequence(KeyEvent::ctrl('r'), Cmd::HistorySearchBackward);
-    // tab completion
-    rl.bind_sequence(KeyEvent::ctrl('i'), Cmd::Complete);
-
-    loop {
-        let readline = rl.readline(shell.prompt.as_str());
-        match readline {
-            Ok(line) => {
-                if line.is_empty() {
-                    continue;
-                }
-
-                if line.trim() == "exit" {
-                    break;
-                }
-
-                shell.current_command = Some(Command::parse(&line));
-
-                if let Err(e) = shell.execute() {
-                    log::error!("{}", e);
-                } else {
-                    rl.add_history_entry(line.as_str()).log_expect("Failed to add history entry");
-                    rl.save_history(".mash_history").log_expect("Failed to save history file");
-                }
-            },
-            Err(e) => {
-                log::error!("{}", e);
-            }
-        }
+    // run as `rmash`, restricted like `rbash`
+    if cli.restricted || Path::new(arg0.trim_start_matches('-')).file_name().is_some_and(|name| name == "rmash") {
+        shell.restrict_after_startup();
     }
-    Ok(())
-}
-
-pub trait LogExpect<T> {
-    fn log_expect(self, msg: &str) -> T;
-}
-
-impl<T> LogExpect<T> for Option<T> {
-    fn log_expect(self, msg: &str) -> T {
-        match self {
-            Some(val) => val,
-            None => {
-                log::error!("{}", msg);
-                std::process::exit(1);
-            }
+    if !shell.exiting() {
+        if let Some(command) = &cli.command {
+            shell.run_command_string(command, &cli.args);
+        } else if let Some((script, args)) = cli.args.split_first() {
+            shell.run_script(script, args);
+        } else if interactive {
+            let rcfile = match (cli.norc, cli.rcfile) {
+                (true, _) => RcFile::None,
+                (false, Some(path)) => RcFile::Path(path),
+                (false, None) => RcFile::Default,
+            };
+            shell.run_interactive(rcfile);
+        } else {
+            shell.run_stdin();
         }
     }
-}
-
-impl<T, E> LogExpect<T> for Result<T, E> 
-where E: std::fmt::Display
-{
-    fn log_expect(self, msg: &str) -> T {
-        match self {
-            Ok(val) => val,
-            Err(e) => {
-                if msg.is_empty() {
-                    log::error!("{}", e);
-                } else {
-                    log::error!("{}", msg);
-                    log::error!("{}", e);
-                }
-                
-                std::process::exit(1);
-            }
-        }
+    shell.run_exit_trap();
+    if login {
+        shell.logout();
     }
-}
\ No newline at end of file
+    std::process::exit(shell.last_status());
+}
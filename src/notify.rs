@@ -0,0 +1,82 @@
+//! Desktop notifications for long-running commands. When a command line that
+//! took at least `after` seconds finishes and the terminal does not seem to
+//! have focus, a notification says what finished and how:
+//!
+//! ```toml
+//! [notify]
+//! after = 30
+//! ```
+//!
+//! Notifications go through `osascript` on macOS and `notify-send`
+//! elsewhere.
+
+use std::{process::{Command, Stdio}, time::Duration};
+
+use serde::Deserialize;
+
+use crate::Shell;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Notify {
+    /// Seconds a command must run for; unset turns notifications off.
+    pub(crate) after: Option<u64>,
+}
+
+impl Shell {
+    /// Called after each command line typed at the prompt, with how long it
+    /// took to run.
+    pub(crate) fn notify_finished(&self, command: &str, elapsed: Duration) {
+        let Some(after) = self.notify_after else {
+            return;
+        };
+        if elapsed < after || terminal_focused() == Some(true) {
+            return;
+        }
+        let title = match self.last_status {
+            0 => String::from("Command finished"),
+            status => format!("Command failed with status {}", status),
+        };
+        send(&title, &format!("{} ({}s)", command, elapsed.as_secs()));
+    }
+}
+
+/// Whether the terminal window has focus, if that can be found out: on X11
+/// by comparing the active window to `WINDOWID`, and on macOS by comparing
+/// the frontmost application to `TERM_PROGRAM`.
+fn terminal_focused() -> Option<bool> {
+    if cfg!(target_os = "macos") {
+        let terminal = std::env::var("TERM_PROGRAM").ok()?;
+        let front = output("osascript", &["-e", "tell application \"System Events\" to get name of first application process whose frontmost is true"])?;
+        let normalize = |name: &str| name.trim_start_matches("Apple_").trim_end_matches(".app").to_lowercase();
+        let (terminal, front) = (normalize(&terminal), normalize(&front));
+        Some(front.contains(&terminal) || terminal.contains(&front))
+    } else {
+        let window: u64 = std::env::var("WINDOWID").ok()?.parse().ok()?;
+        let active: u64 = output("xdotool", &["getactivewindow"])?.parse().ok()?;
+        Some(active == window)
+    }
+}
+
+/// The trimmed output of a successful command.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn send(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!("display notification {} with title {}", quote(body), quote(title)));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=mash", title, body]);
+        command
+    };
+    let sent = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status();
+    if let Err(e) = sent {
+        tracing::debug!("sending a notification failed: {}", e);
+    }
+}
@@ -0,0 +1,894 @@
+//! Builds an AST out of the token stream.
+
+use std::{fmt, rc::Rc};
+
+use crate::lexer::{self, RedirectOp, Token};
+
+/// Words that begin or end a compound command when they appear where a
+/// command name is expected.
+pub(crate) const RESERVED: &[&str] = &["if", "then", "elif", "else", "fi", "for", "while", "until", "do", "done", "case", "esac", "select", "{", "}"];
+
+/// A simple command: variable assignments followed by the command name and
+/// its arguments, either of which may be empty, and any redirections.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<String>,
+    /// Compound assignments given as arguments to `declare` or `typeset`,
+    /// as in `declare -A m=([k]=v)`, with the index of the word holding the
+    /// name in their place.
+    pub arrays: Vec<(usize, Assignment)>,
+    pub redirects: Vec<Redirect>,
+    /// The line the command starts on.
+    pub line: usize,
+}
+
+impl fmt::Display for Command {
+    /// The command as written, before expansion.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::with_capacity(self.assignments.len() + self.words.len());
+        parts.extend(self.assignments.iter().map(|a| a.to_string()));
+        parts.extend(self.words.iter().enumerate().map(|(i, word)| match self.arrays.iter().find(|(at, _)| *at == i) {
+            Some((_, a)) => a.to_string(),
+            None => word.clone(),
+        }));
+        parts.extend(self.redirects.iter().map(|r| r.to_string()));
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// `[fd]op target`, as in `2>>log`. The target is a word, unexpanded; for a
+/// here-document it is the delimiter and the body comes with it.
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: i32,
+    pub op: RedirectOp,
+    pub target: String,
+    pub here_doc: Option<String>,
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.fd, self.op.as_str(), self.target)
+    }
+}
+
+/// `name=value`, `name[index]=value` or `name=(words)`.
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub name: String,
+    pub index: Option<String>,
+    pub value: AssignValue,
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(index) = &self.index {
+            write!(f, "[{}]", index)?;
+        }
+        match &self.value {
+            AssignValue::Scalar(value) => write!(f, "={}", value),
+            AssignValue::Array(words) => write!(f, "=({})", words.join(" ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AssignValue {
+    Scalar(String),
+    /// The words of a compound assignment, unexpanded.
+    Array(Vec<String>),
+}
+
+/// A node in the syntax tree.
+#[derive(Debug, Clone)]
+pub enum Node {
+    Simple(Command),
+    If(If),
+    For(For),
+    /// `select`, which shares the shape of a `for` loop.
+    Select(For),
+    While(While),
+    Case(Case),
+    Function(Function),
+    Pipeline(Pipeline),
+    AndOr(AndOr),
+    /// A command followed by `&`, run without waiting for it.
+    Background(Box<Node>),
+    /// `( list )`, run in a child process so that nothing it changes is
+    /// seen by the shell afterwards.
+    Subshell(Vec<Node>),
+    /// `{ list; }`, run in the shell itself.
+    Group(Vec<Node>),
+    /// A compound command with redirections that apply to all of it, as in
+    /// `while read line; do ...; done <file`.
+    Redirected(Box<Node>, Vec<Redirect>),
+    /// `! pipeline`, which succeeds if the pipeline fails and fails if it
+    /// succeeds.
+    Not(Box<Node>),
+    /// `time pipeline`, which reports how long the pipeline took; with `-p`
+    /// in the format POSIX gives.
+    Timed(Box<Node>, bool),
+    /// `((expression))`, which succeeds if the expression is not zero.
+    Arith(String),
+}
+
+impl fmt::Display for Node {
+    /// The command as it could be written, for listing jobs and functions.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Simple(command) => write!(f, "{}", command),
+            Node::Pipeline(pipeline) => write!(f, "{}", pipeline),
+            Node::AndOr(and_or) => {
+                write!(f, "{}", and_or.first)?;
+                for (connector, node) in &and_or.rest {
+                    write!(f, " {} {}", if *connector == Connector::And { "&&" } else { "||" }, node)?;
+                }
+                Ok(())
+            }
+            Node::Background(node) => write!(f, "{} &", node),
+            Node::Subshell(list) => write!(f, "( {} )", List(list)),
+            Node::Group(list) => write!(f, "{{ {}; }}", List(list)),
+            Node::Redirected(node, redirects) => {
+                write!(f, "{}", node)?;
+                redirects.iter().try_for_each(|redirect| write!(f, " {}", redirect))
+            }
+            Node::Not(node) => write!(f, "! {}", node),
+            Node::Timed(node, posix) => write!(f, "time {}{}", if *posix { "-p " } else { "" }, node),
+            Node::Arith(expression) => write!(f, "(({}))", expression),
+            Node::If(clause) => {
+                for (i, (condition, body)) in clause.branches.iter().enumerate() {
+                    write!(f, "{} {}; then {}; ", if i == 0 { "if" } else { "elif" }, List(condition), List(body))?;
+                }
+                if let Some(body) = &clause.else_body {
+                    write!(f, "else {}; ", List(body))?;
+                }
+                write!(f, "fi")
+            }
+            Node::For(lp) | Node::Select(lp) => {
+                write!(f, "{} {}", if matches!(self, Node::For(_)) { "for" } else { "select" }, lp.var)?;
+                if let Some(words) = &lp.words {
+                    write!(f, " in {}", words.join(" "))?;
+                }
+                write!(f, "; do {}; done", List(&lp.body))
+            }
+            Node::While(lp) => write!(f, "{} {}; do {}; done", if lp.until { "until" } else { "while" }, List(&lp.condition), List(&lp.body)),
+            Node::Case(case) => {
+                write!(f, "case {} in", case.word)?;
+                for arm in &case.arms {
+                    write!(f, " {}) {};;", arm.patterns.join("|"), List(&arm.body))?;
+                }
+                write!(f, " esac")
+            }
+            Node::Function(function) => write!(f, "{}() {{ {}; }}", function.name, List(&function.body)),
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commands: Vec<_> = self.commands.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", commands.join(" | "))
+    }
+}
+
+/// Displays a list of commands on one line.
+struct List<'a>(&'a [Node]);
+
+impl fmt::Display for List<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, node) in self.0.iter().enumerate() {
+            if i > 0 {
+                // `&` already separates a command from the next
+                write!(f, "{}", if matches!(self.0[i - 1], Node::Background(_)) { " " } else { "; " })?;
+            }
+            write!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+/// `cmd1 | cmd2 | ...`: two or more commands run at the same time, the
+/// output of each going to the input of the next.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub commands: Vec<Node>,
+}
+
+/// `a && b || c`: each pipeline after the first runs depending on the status
+/// of the one before, `&&` if it succeeded and `||` if it failed.
+#[derive(Debug, Clone)]
+pub struct AndOr {
+    pub first: Box<Node>,
+    pub rest: Vec<(Connector, Node)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+/// `if`/`elif` branches are tried in order; the body of the first one whose
+/// condition exits with status 0 runs, otherwise `else_body` does.
+#[derive(Debug, Clone)]
+pub struct If {
+    pub branches: Vec<(Vec<Node>, Vec<Node>)>,
+    pub else_body: Option<Vec<Node>>,
+}
+
+/// `for var in words; do body; done`. Without `in`, the loop runs over the
+/// positional parameters.
+#[derive(Debug, Clone)]
+pub struct For {
+    pub var: String,
+    pub words: Option<Vec<String>>,
+    pub body: Vec<Node>,
+}
+
+/// `while condition; do body; done`, or `until` when `until` is set, which
+/// loops while the condition fails instead.
+#[derive(Debug, Clone)]
+pub struct While {
+    pub condition: Vec<Node>,
+    pub body: Vec<Node>,
+    pub until: bool,
+}
+
+/// `case word in pattern|pattern) body ;; ... esac`. The first arm with a
+/// pattern matching the word runs.
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub word: String,
+    pub arms: Vec<CaseArm>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseArm {
+    pub patterns: Vec<String>,
+    pub body: Vec<Node>,
+}
+
+/// `name() { body; }`. The body is shared so that calling a function does not
+/// copy it, and redefining it while it runs is harmless.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub body: Rc<Vec<Node>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ended inside a compound command; more lines are needed.
+    Incomplete,
+    /// The input ended inside quotes; more lines are needed.
+    UnterminatedQuote(char),
+    /// A token appeared where it is not allowed.
+    Unexpected(String),
+    /// A name that must be an identifier is not one.
+    InvalidName(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Incomplete => write!(f, "syntax error: unexpected end of file"),
+            ParseError::UnterminatedQuote(quote) => write!(f, "unexpected end of file while looking for matching `{}'", quote),
+            ParseError::Unexpected(token) => write!(f, "syntax error near unexpected token `{}'", token),
+            ParseError::InvalidName(name) => write!(f, "`{}': not a valid identifier", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Whether the input may just be unfinished, so that reading more lines
+    /// could complete it.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, ParseError::Incomplete | ParseError::UnterminatedQuote(_))
+    }
+}
+
+/// Parse a complete program.
+pub fn parse(src: &str) -> Result<Vec<Node>, ParseError> {
+    parse_from(src, 1)
+}
+
+/// Parse a program that starts on line `first_line` of its file.
+pub fn parse_from(src: &str, first_line: usize) -> Result<Vec<Node>, ParseError> {
+    let (tokens, open) = lexer::lex(src);
+    match open {
+        Some('\\' | '<') => return Err(ParseError::Incomplete),
+        Some(quote) => return Err(ParseError::UnterminatedQuote(quote)),
+        None => {}
+    }
+    let (tokens, lines) = lexer::number_lines(tokens, first_line).into_iter().unzip();
+    let mut parser = Parser { tokens, lines, pos: 0 };
+    let list = parser.list(&[])?;
+    match parser.peek() {
+        Some(token) => Err(ParseError::Unexpected(describe(token))),
+        None => Ok(list),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(w) => w.clone(),
+        Token::Semi => String::from(";"),
+        Token::DSemi => String::from(";;"),
+        Token::Newline => String::from("newline"),
+        Token::Pipe => String::from("|"),
+        Token::Amp => String::from("&"),
+        Token::AndIf => String::from("&&"),
+        Token::OrIf => String::from("||"),
+        Token::LParen => String::from("("),
+        Token::RParen => String::from(")"),
+        Token::Redirect(fd, op) => format!("{}{}", fd.map(|fd| fd.to_string()).unwrap_or_default(), op.as_str()),
+        Token::HereDoc(_) => String::from("here-document"),
+        Token::Arith(expression) => format!("(({}))", expression),
+    }
+}
+
+/// Whether `word` is a valid variable name.
+pub fn is_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Split an assignment word into its name, optional subscript and value.
+pub(crate) fn split_assignment(word: &str) -> Option<(&str, Option<&str>, &str)> {
+    let (target, value) = word.split_once('=')?;
+    let (name, index) = match target.strip_suffix(']').and_then(|t| t.split_once('[')) {
+        Some((name, index)) => (name, Some(index)),
+        None => (target, None),
+    };
+    is_name(name).then_some((name, index, value))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    /// The line each token starts on.
+    lines: Vec<usize>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(w)) => Some(w),
+            _ => None,
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume the reserved word `keyword` or fail.
+    fn expect(&mut self, keyword: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(Token::Word(w)) if w == keyword => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(ParseError::Unexpected(describe(token))),
+            None => Err(ParseError::Incomplete),
+        }
+    }
+
+    /// Parse commands separated by `;` or newlines, stopping before one of
+    /// the reserved words in `terminators`, or before `)` if that is one of
+    /// them. An empty `terminators` means the top level, where the list runs
+    /// to the end of input.
+    fn list(&mut self, terminators: &[&str]) -> Result<Vec<Node>, ParseError> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                None if terminators.is_empty() => break,
+                None => return Err(ParseError::Incomplete),
+                Some(Token::Word(w)) if terminators.contains(&w.as_str()) => break,
+                Some(Token::RParen) if terminators.contains(&")") => break,
+                // ends a case arm; anywhere else the caller rejects it
+                Some(Token::DSemi) => break,
+                Some(Token::Word(_) | Token::Redirect(..) | Token::LParen | Token::Arith(_)) => {}
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+            }
+            let mut node = self.and_or()?;
+            let last = match &node {
+                Node::AndOr(and_or) => and_or.rest.last().map_or(&*and_or.first, |(_, node)| node),
+                node => node,
+            };
+            let compound = match last {
+                Node::Pipeline(pipeline) => !matches!(pipeline.commands.last(), Some(Node::Simple(_))),
+                node => !matches!(node, Node::Simple(_)),
+            };
+            if self.peek() == Some(&Token::Amp) {
+                self.pos += 1;
+                node = Node::Background(Box::new(node));
+                nodes.push(node);
+                continue;
+            }
+            nodes.push(node);
+            match self.peek() {
+                Some(Token::Semi) | Some(Token::Newline) => self.pos += 1,
+                None | Some(Token::DSemi) => {}
+                Some(Token::RParen) if terminators.contains(&")") => {}
+                // a compound command may be directly followed by the keyword closing its parent
+                Some(Token::Word(w)) if compound && terminators.contains(&w.as_str()) => {}
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Like [`Parser::list`], but at least one command is required.
+    fn compound_list(&mut self, terminators: &[&str]) -> Result<Vec<Node>, ParseError> {
+        let list = self.list(terminators)?;
+        if list.is_empty() {
+            return Err(match self.peek() {
+                Some(token) => ParseError::Unexpected(describe(token)),
+                None => ParseError::Incomplete,
+            });
+        }
+        Ok(list)
+    }
+
+    /// Pipelines joined by `&&` and `||`, after which a line may end too.
+    fn and_or(&mut self) -> Result<Node, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek() {
+                Some(Token::AndIf) => Connector::And,
+                Some(Token::OrIf) => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+            match self.peek() {
+                Some(Token::Word(_) | Token::Redirect(..) | Token::LParen | Token::Arith(_)) => rest.push((connector, self.pipeline()?)),
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                None => return Err(ParseError::Incomplete),
+            }
+        }
+        if rest.is_empty() {
+            return Ok(first);
+        }
+        Ok(Node::AndOr(AndOr { first: Box::new(first), rest }))
+    }
+
+    /// A command, or several joined by `|`. A line may end after a `|`.
+    fn pipeline(&mut self) -> Result<Node, ParseError> {
+        if self.peek_word() == Some("!") {
+            self.pos += 1;
+            return match self.peek() {
+                Some(Token::Word(_) | Token::Redirect(..) | Token::LParen | Token::Arith(_)) => Ok(Node::Not(Box::new(self.pipeline()?))),
+                Some(token) => Err(ParseError::Unexpected(describe(token))),
+                None => Err(ParseError::Incomplete),
+            };
+        }
+        if self.peek_word() == Some("time") {
+            let start = self.pos;
+            self.pos += 1;
+            let posix = self.peek_word() == Some("-p");
+            if posix {
+                self.pos += 1;
+            }
+            match self.peek() {
+                Some(Token::Word(_) | Token::Redirect(..) | Token::LParen | Token::Arith(_)) => return Ok(Node::Timed(Box::new(self.pipeline()?), posix)),
+                // with nothing to time, `time` is an ordinary command
+                _ => self.pos = start,
+            }
+        }
+        let first = self.command()?;
+        if self.peek() != Some(&Token::Pipe) {
+            return Ok(first);
+        }
+        let mut commands = vec![first];
+        while self.peek() == Some(&Token::Pipe) {
+            self.pos += 1;
+            self.skip_newlines();
+            match self.peek() {
+                Some(Token::Word(_) | Token::Redirect(..) | Token::LParen | Token::Arith(_)) => commands.push(self.command()?),
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                None => return Err(ParseError::Incomplete),
+            }
+        }
+        Ok(Node::Pipeline(Pipeline { commands }))
+    }
+
+    fn command(&mut self) -> Result<Node, ParseError> {
+        let compound = match self.peek_word() {
+            None if matches!(self.peek(), Some(Token::Arith(_))) => {
+                let Some(Token::Arith(expression)) = self.peek().cloned() else { unreachable!() };
+                self.pos += 1;
+                Node::Arith(expression)
+            }
+            None if self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                let list = self.compound_list(&[")"])?;
+                match self.peek() {
+                    Some(Token::RParen) => self.pos += 1,
+                    Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                    None => return Err(ParseError::Incomplete),
+                }
+                Node::Subshell(list)
+            }
+            Some("{") => {
+                self.pos += 1;
+                let list = self.compound_list(&["}"])?;
+                self.expect("}")?;
+                Node::Group(list)
+            }
+            Some("if") => self.if_clause()?,
+            Some("for" | "select") => self.for_clause()?,
+            Some("while") => self.while_clause(false)?,
+            Some("until") => self.while_clause(true)?,
+            Some("case") => self.case_clause()?,
+            Some(w) if RESERVED.contains(&w) => return Err(ParseError::Unexpected(w.to_string())),
+            Some(w) if split_assignment(w).is_none() && self.tokens.get(self.pos + 1) == Some(&Token::LParen) => return self.function_def(),
+            _ => return self.simple_command(),
+        };
+        let mut redirects = Vec::new();
+        while self.redirect(&mut redirects)? {}
+        if redirects.is_empty() {
+            return Ok(compound);
+        }
+        Ok(Node::Redirected(Box::new(compound), redirects))
+    }
+
+    fn function_def(&mut self) -> Result<Node, ParseError> {
+        let name = self.word()?;
+        if !is_name(&name) {
+            return Err(ParseError::InvalidName(name));
+        }
+        self.pos += 1;
+        match self.peek() {
+            Some(Token::RParen) => self.pos += 1,
+            Some(token) => return Err(ParseError::Unexpected(describe(token))),
+            None => return Err(ParseError::Incomplete),
+        }
+        self.skip_newlines();
+        let body = match self.peek_word() {
+            Some("{") => {
+                self.pos += 1;
+                let body = self.compound_list(&["}"])?;
+                self.expect("}")?;
+                body
+            }
+            Some("if" | "for" | "while" | "until" | "case") => vec![self.command()?],
+            Some(w) => return Err(ParseError::Unexpected(w.to_string())),
+            None if self.peek() == Some(&Token::LParen) => vec![self.command()?],
+            None => match self.peek() {
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                None => return Err(ParseError::Incomplete),
+            },
+        };
+        Ok(Node::Function(Function { name, body: Rc::new(body) }))
+    }
+
+    fn simple_command(&mut self) -> Result<Node, ParseError> {
+        let line = self.lines.get(self.pos).copied().unwrap_or_default();
+        let mut assignments = Vec::new();
+        let mut redirects = Vec::new();
+        loop {
+            if self.redirect(&mut redirects)? {
+                continue;
+            }
+            let Some((name, index, value)) = self.peek_word().and_then(split_assignment) else {
+                break;
+            };
+            let (name, index, value) = (name.to_string(), index.map(String::from), value.to_string());
+            self.pos += 1;
+            let value = if value.is_empty() && self.peek() == Some(&Token::LParen) {
+                AssignValue::Array(self.array_words()?)
+            } else {
+                AssignValue::Scalar(value)
+            };
+            assignments.push(Assignment { name, index, value });
+        }
+        let mut words = Vec::new();
+        let mut arrays = Vec::new();
+        loop {
+            if self.redirect(&mut redirects)? {
+                continue;
+            }
+            let Some(Token::Word(w)) = self.peek().cloned() else {
+                break;
+            };
+            self.pos += 1;
+            let declaration = matches!(words.first().map(String::as_str), Some("declare" | "typeset"));
+            match split_assignment(&w) {
+                Some((name, None, "")) if declaration && self.peek() == Some(&Token::LParen) => {
+                    let value = AssignValue::Array(self.array_words()?);
+                    arrays.push((words.len(), Assignment { name: name.to_string(), index: None, value }));
+                    words.push(name.to_string());
+                }
+                _ => words.push(w),
+            }
+        }
+        if assignments.is_empty() && words.is_empty() && redirects.is_empty() {
+            return Err(ParseError::Incomplete);
+        }
+        Ok(Node::Simple(Command { assignments, words, arrays, redirects, line }))
+    }
+
+    /// Parse a redirection and its target if one is next, returning whether
+    /// there was one.
+    fn redirect(&mut self, redirects: &mut Vec<Redirect>) -> Result<bool, ParseError> {
+        let Some(&Token::Redirect(fd, op)) = self.peek() else {
+            return Ok(false);
+        };
+        self.pos += 1;
+        let target = self.word()?;
+        let here_doc = match (op, self.peek()) {
+            (RedirectOp::HereDoc, Some(Token::HereDoc(body))) => {
+                let body = body.clone();
+                self.pos += 1;
+                Some(body)
+            }
+            (RedirectOp::HereDoc, _) => return Err(ParseError::Incomplete),
+            _ => None,
+        };
+        redirects.push(Redirect { fd: fd.unwrap_or(op.default_fd()), op, target, here_doc });
+        Ok(true)
+    }
+
+    /// The parenthesised words of a compound assignment, which may span
+    /// several lines.
+    fn array_words(&mut self) -> Result<Vec<String>, ParseError> {
+        self.pos += 1;
+        let mut words = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Word(w)) => words.push(w.clone()),
+                Some(Token::Newline) => {}
+                Some(Token::RParen) => {
+                    self.pos += 1;
+                    return Ok(words);
+                }
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                None => return Err(ParseError::Incomplete),
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn if_clause(&mut self) -> Result<Node, ParseError> {
+        self.expect("if")?;
+        let mut branches = Vec::new();
+        let mut else_body = None;
+        loop {
+            let condition = self.compound_list(&["then"])?;
+            self.expect("then")?;
+            let body = self.compound_list(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            match self.peek_word() {
+                Some("elif") => self.pos += 1,
+                Some("else") => {
+                    self.pos += 1;
+                    else_body = Some(self.compound_list(&["fi"])?);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.expect("fi")?;
+        Ok(Node::If(If { branches, else_body }))
+    }
+
+    /// `for` and `select`, which only differ in their keyword.
+    fn for_clause(&mut self) -> Result<Node, ParseError> {
+        let select = self.peek_word() == Some("select");
+        self.pos += 1;
+        let var = match self.peek() {
+            Some(Token::Word(w)) if is_name(w) => w.clone(),
+            Some(Token::Word(w)) => return Err(ParseError::InvalidName(w.clone())),
+            Some(token) => return Err(ParseError::Unexpected(describe(token))),
+            None => return Err(ParseError::Incomplete),
+        };
+        self.pos += 1;
+        self.skip_newlines();
+        let mut words = None;
+        if self.peek_word() == Some("in") {
+            self.pos += 1;
+            let mut list = Vec::new();
+            while let Some(Token::Word(w)) = self.peek() {
+                list.push(w.clone());
+                self.pos += 1;
+            }
+            words = Some(list);
+            match self.peek() {
+                Some(Token::Semi) | Some(Token::Newline) => self.pos += 1,
+                Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                None => return Err(ParseError::Incomplete),
+            }
+        } else if self.peek() == Some(&Token::Semi) {
+            self.pos += 1;
+        }
+        let body = self.do_group()?;
+        let lp = For { var, words, body };
+        Ok(if select { Node::Select(lp) } else { Node::For(lp) })
+    }
+
+    fn while_clause(&mut self, until: bool) -> Result<Node, ParseError> {
+        self.pos += 1;
+        let condition = self.compound_list(&["do"])?;
+        let body = self.do_group()?;
+        Ok(Node::While(While { condition, body, until }))
+    }
+
+    fn case_clause(&mut self) -> Result<Node, ParseError> {
+        self.expect("case")?;
+        let word = self.word()?;
+        self.skip_newlines();
+        self.expect("in")?;
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.peek_word() == Some("esac") {
+                break;
+            }
+            if self.peek() == Some(&Token::LParen) {
+                self.pos += 1;
+            }
+            let mut patterns = vec![self.word()?];
+            loop {
+                match self.peek() {
+                    Some(Token::Pipe) => {
+                        self.pos += 1;
+                        patterns.push(self.word()?);
+                    }
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(token) => return Err(ParseError::Unexpected(describe(token))),
+                    None => return Err(ParseError::Incomplete),
+                }
+            }
+            let body = self.list(&["esac"])?;
+            arms.push(CaseArm { patterns, body });
+            // the last arm does not need a `;;`
+            if self.peek() == Some(&Token::DSemi) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.skip_newlines();
+        self.expect("esac")?;
+        Ok(Node::Case(Case { word, arms }))
+    }
+
+    /// A single word operand.
+    fn word(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Word(w)) => {
+                let w = w.clone();
+                self.pos += 1;
+                Ok(w)
+            }
+            Some(token) => Err(ParseError::Unexpected(describe(token))),
+            None => Err(ParseError::Incomplete),
+        }
+    }
+
+    /// `do list done`, the body shared by all loops.
+    fn do_group(&mut self) -> Result<Vec<Node>, ParseError> {
+        self.skip_newlines();
+        self.expect("do")?;
+        let body = self.compound_list(&["done"])?;
+        self.expect("done")?;
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{Shell, exec::quote, lexer::{number_lines, tokenize}};
+
+    /// How a token is written in source.
+    fn render(token: &Token) -> String {
+        match token {
+            Token::Word(w) => w.clone(),
+            Token::Newline => String::from("\n"),
+            _ => describe(token),
+        }
+    }
+
+    const RESERVED: &[&str] = &["if", "then", "elif", "else", "fi", "for", "select", "while", "until", "do", "done", "case", "esac", "in", "function", "time"];
+
+    fn word() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-zA-Z0-9_./=+-][a-zA-Z0-9_./=+#-]{0,8}",
+            "'[^']{0,8}'",
+            "\"[^\"\\\\$`]{0,8}\"",
+            "[a-z]{1,4}\\\\[;|() ]",
+        ]
+    }
+
+    fn token() -> impl Strategy<Value = Token> {
+        prop_oneof![
+            4 => word().prop_map(Token::Word),
+            1 => Just(Token::Semi),
+            1 => Just(Token::DSemi),
+            1 => Just(Token::Newline),
+            1 => Just(Token::Pipe),
+            1 => Just(Token::Amp),
+            1 => Just(Token::AndIf),
+            1 => Just(Token::OrIf),
+            1 => Just(Token::LParen),
+            1 => Just(Token::RParen),
+            1 => (prop::option::of(0..10i32), prop_oneof![Just(RedirectOp::Read), Just(RedirectOp::Write), Just(RedirectOp::Append), Just(RedirectOp::DupRead), Just(RedirectOp::DupWrite)]).prop_map(|(fd, op)| Token::Redirect(fd, op)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(src in "\\PC{0,64}") {
+            let _ = tokenize(&src);
+            let _ = parse(&src);
+        }
+
+        #[test]
+        fn never_panics_on_shell_syntax(src in "[a-z ;|()'\"\\\\$#{}\\[\\]=\n]{0,64}") {
+            let _ = parse(&src);
+        }
+
+        #[test]
+        fn tokens_round_trip(tokens in prop::collection::vec(token(), 0..16)) {
+            let src: Vec<String> = tokens.iter().map(render).collect();
+            prop_assert_eq!(tokenize(&src.join(" ")), tokens);
+        }
+
+        #[test]
+        fn lines_count_newlines(src in "[a-z ;\n'\"]{0,64}") {
+            let newlines = src.matches('\n').count();
+            let tokens = number_lines(tokenize(&src), 1);
+            prop_assert!(tokens.windows(2).all(|w| w[0].1 <= w[1].1));
+            prop_assert!(tokens.last().is_none_or(|(_, line)| *line <= newlines + 1));
+        }
+
+        #[test]
+        fn quoted_words_expand_to_themselves(word in "\\PC{0,16}") {
+            let quoted = quote(&word);
+            prop_assert_eq!(tokenize(&quoted), vec![Token::Word(quoted.clone())]);
+            prop_assert_eq!(Shell::new().expand_word(&quoted).unwrap(), word);
+        }
+
+        #[test]
+        fn complete_commands_parse(words in prop::collection::vec("[a-z][a-z0-9]{0,6}".prop_filter("reserved word", |w| !RESERVED.contains(&w.as_str())), 1..6)) {
+            let line = words.join(" ");
+            let list = parse(&line).unwrap();
+            prop_assert_eq!(list.len(), 1);
+            match &list[0] {
+                Node::Simple(command) => prop_assert_eq!(&command.words, &words),
+                _ => prop_assert!(false, "not a simple command: {}", line),
+            }
+        }
+
+        #[test]
+        fn pipelines_parse(commands in prop::collection::vec("[a-z][a-z0-9]{0,6}( [a-z0-9]{1,4}){0,2}".prop_filter("reserved word", |c| !c.split(' ').any(|w| RESERVED.contains(&w))), 2..5)) {
+            let line = commands.join(" | ");
+            let list = parse(&line).unwrap();
+            prop_assert_eq!(list.len(), 1);
+            match &list[0] {
+                Node::Pipeline(pipeline) => prop_assert_eq!(pipeline.commands.len(), commands.len()),
+                _ => prop_assert!(false, "not a pipeline: {}", line),
+            }
+        }
+    }
+}
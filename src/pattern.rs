@@ -0,0 +1,150 @@
+//! Glob-style pattern matching, shared by `case` and filename expansion.
+//!
+//! Supports `*`, `?`, bracket expressions (`[abc]`, `[a-z]`, `[!x]` or
+//! `[^x]`) and backslash escapes.
+
+use std::path::Path;
+
+/// Whether `text` matches `pattern` in its entirety.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // where to resume after the most recent `*`: (pattern index, text index)
+    let mut backtrack = None;
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match bracket(&pattern[p..], text[t]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                // an unterminated bracket is an ordinary character
+                None => (text[t] == '[').then_some(1),
+            },
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(2),
+            Some(&c) => (c == text[t]).then_some(1),
+            None => None,
+        };
+        match step {
+            Some(len) => {
+                p += len;
+                t += 1;
+            }
+            None => match backtrack {
+                // let the last `*` swallow one more character and retry
+                Some((star, start)) => {
+                    p = star + 1;
+                    t = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the bracket expression at the start of `pattern`,
+/// returning whether it matched and the length of the expression, or `None`
+/// if the bracket is never closed.
+fn bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut lo = *pattern.get(i)?;
+        if lo == ']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
+        if lo == '\\' {
+            i += 1;
+            lo = *pattern.get(i)?;
+        }
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&hi| hi != ']') {
+            let hi = pattern[i + 2];
+            matched |= lo <= c && c <= hi;
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+}
+
+/// Whether `pattern` has a `*`, `?` or `[` that is not escaped, so that it
+/// must be matched rather than compared.
+fn is_wild(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn unescape(pattern: &str) -> String {
+    let mut text = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        text.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+    }
+    text
+}
+
+/// The paths matching `pattern`, sorted, with relative ones looked up from
+/// `cwd`. Each `/`-separated part is matched against the names in one
+/// directory, and a name starting with `.` only matches a part that does
+/// too.
+pub fn glob(pattern: &str, cwd: &Path) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec![String::from("/")], rest),
+        None => (vec![String::new()], pattern),
+    };
+    let parts: Vec<&str> = rest.split('/').collect();
+    for (i, part) in parts.iter().enumerate() {
+        let slash = if i + 1 < parts.len() { "/" } else { "" };
+        let mut next = Vec::new();
+        for path in paths {
+            if part.is_empty() {
+                // a doubled or trailing slash, after which only directories remain
+                if cwd.join(&path).is_dir() {
+                    next.push(path);
+                }
+            } else if !is_wild(part) {
+                let candidate = format!("{}{}", path, unescape(part));
+                if cwd.join(&candidate).symlink_metadata().is_ok() {
+                    next.push(candidate + slash);
+                }
+            } else if let Ok(entries) = cwd.join(if path.is_empty() { "." } else { &path }).read_dir() {
+                let dotted = part.starts_with('.') || part.starts_with("\\.");
+                for name in entries.flatten().filter_map(|entry| entry.file_name().into_string().ok()) {
+                    if (dotted || !name.starts_with('.')) && matches(part, &name) {
+                        next.push(format!("{}{}{}", path, name, slash));
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+    paths.sort();
+    paths
+}
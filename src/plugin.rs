@@ -0,0 +1,248 @@
+//! Plugins found in `~/.mash/plugins`, which extend the shell without
+//! changing it.
+//!
+//! A script plugin `NAME.mash` is sourced the first time the command `NAME`
+//! is looked up and not found, so it usually defines a function of that
+//! name; `plugin load NAME` sources it straight away. An executable
+//! `NAME.hook` is a hook program, run for prompt segments and completions
+//! as they are needed and answering in JSON. With the `dylib-plugins`
+//! feature, shared libraries in the same directory are loaded at startup:
+//! they implement [`Plugin`] and export it with
+//! [`declare_plugin!`](crate::declare_plugin), and must be built against the
+//! same version of mash with the same compiler.
+//!
+//! Shell functions need no plugin to hook in: those called `precmd` and
+//! `preexec` run at the same points as [`Plugin::precmd`] and
+//! [`Plugin::preexec`], `preexec` with the command line as `$1`.
+
+use std::{collections::BTreeMap, io::Write, path::{Path, PathBuf}, rc::Rc};
+
+use crate::{Builtin, Io, Shell, ShellError, hook::{self, Hook}};
+
+/// Bumped whenever [`Plugin`] changes, so stale libraries are refused
+/// instead of crashing the shell.
+pub const ABI_VERSION: u32 = 1;
+
+/// An extension compiled into a shared library, or registered directly by a
+/// program embedding the shell. Every method has a default that does
+/// nothing.
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    /// Builtins added to the shell when the plugin is loaded.
+    fn builtins(&self) -> Vec<Rc<dyn Builtin>> {
+        Vec::new()
+    }
+
+    /// Called before each interactive prompt is shown.
+    fn precmd(&self, _shell: &mut Shell) {}
+
+    /// Called with each interactive command line before it runs.
+    fn preexec(&self, _shell: &mut Shell, _line: &str) {}
+
+    /// Text shown before the prompt, such as the state of a repository.
+    fn prompt_segment(&self, _shell: &Shell) -> Option<String> {
+        None
+    }
+
+    /// Completions for the word ending at `pos` in `line`: the byte offset
+    /// the word starts at and its replacements. The first plugin to offer
+    /// completions wins.
+    fn complete(&self, _line: &str, _pos: usize) -> Option<(usize, Vec<String>)> {
+        None
+    }
+}
+
+/// Export a [`Plugin`] from a shared library, given an expression that
+/// creates it:
+///
+/// ```ignore
+/// mash::declare_plugin!(Weather::default());
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($create:expr) => {
+        #[no_mangle]
+        pub static MASH_PLUGIN_ABI: u32 = $crate::plugin::ABI_VERSION;
+
+        #[no_mangle]
+        pub fn mash_plugin_create() -> Box<dyn $crate::plugin::Plugin> {
+            Box::new($create)
+        }
+    };
+}
+
+/// The plugins a shell knows about.
+#[derive(Default)]
+pub(crate) struct Plugins {
+    /// Script plugins that have not been sourced yet, by name.
+    pending: BTreeMap<String, PathBuf>,
+    /// Script plugins that have been sourced.
+    sourced: Vec<String>,
+    pub(crate) native: Vec<Rc<dyn Plugin>>,
+    /// The native plugins that are hook programs.
+    hooks: Vec<String>,
+}
+
+impl Shell {
+    /// Find the plugins in `~/.mash/plugins`. Script plugins are only noted
+    /// here; hook programs are registered, and shared libraries loaded if
+    /// the `dylib-plugins` feature is enabled.
+    pub fn load_plugins(&mut self) {
+        let dir = self.home.join(".mash/plugins");
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        paths.sort();
+        for path in paths {
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("mash") => {
+                    self.plugins.pending.insert(stem.to_string(), path.clone());
+                }
+                Some(std::env::consts::DLL_EXTENSION) => self.load_library(&path),
+                Some(hook::EXTENSION) => {
+                    self.plugins.hooks.push(stem.to_string());
+                    self.register_plugin(Hook::new(stem, &path));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[cfg(feature = "dylib-plugins")]
+    fn load_library(&mut self, path: &Path) {
+        match unsafe { load_library(path) } {
+            Ok(plugin) => self.register_plugin_rc(plugin.into()),
+            Err(e) => crate::error::warn(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    #[cfg(not(feature = "dylib-plugins"))]
+    fn load_library(&mut self, path: &Path) {
+        tracing::debug!("{}: shared library plugins are not supported by this build", path.display());
+    }
+
+    /// Add a plugin, registering its builtins.
+    pub fn register_plugin(&mut self, plugin: impl Plugin + 'static) {
+        self.register_plugin_rc(Rc::new(plugin));
+    }
+
+    fn register_plugin_rc(&mut self, plugin: Rc<dyn Plugin>) {
+        for builtin in plugin.builtins() {
+            self.builtins.insert(builtin);
+        }
+        self.plugins.native.push(plugin);
+    }
+
+    /// Source the script plugin called `name` if it has not been yet,
+    /// returning whether it was.
+    pub(crate) fn autoload(&mut self, name: &str) -> bool {
+        if self.options.posix {
+            return false;
+        }
+        let Some(path) = self.plugins.pending.remove(name) else {
+            return false;
+        };
+        self.plugins.sourced.push(name.to_string());
+        if let Err(e) = self.source_file(&path) {
+            self.report(e);
+        }
+        true
+    }
+
+    pub(crate) fn run_precmd_plugins(&mut self) {
+        for plugin in self.plugins.native.clone() {
+            plugin.precmd(self);
+        }
+        self.run_hook("precmd", Vec::new());
+    }
+
+    pub(crate) fn run_preexec_plugins(&mut self, line: &str) {
+        for plugin in self.plugins.native.clone() {
+            plugin.preexec(self, line);
+        }
+        self.run_hook("preexec", vec![line.to_string()]);
+    }
+
+    /// Call the hook function `name` if there is one. `$?` is left as it
+    /// was, for the prompt and the command line about to run.
+    fn run_hook(&mut self, name: &str, args: Vec<String>) {
+        if self.options.posix {
+            return;
+        }
+        let Some(function) = self.functions.get(name).cloned() else {
+            return;
+        };
+        let status = self.last_status;
+        self.call_function(name, &function, args);
+        self.last_status = status;
+    }
+
+    /// The prompt segments of all plugins, each followed by a space.
+    pub(crate) fn prompt_segments(&self) -> String {
+        self.plugins.native.iter().filter_map(|p| p.prompt_segment(self)).map(|s| s + " ").collect()
+    }
+}
+
+#[cfg(feature = "dylib-plugins")]
+unsafe fn load_library(path: &Path) -> Result<Box<dyn Plugin>, String> {
+    let library = libloading::Library::new(path).map_err(|e| e.to_string())?;
+    let abi = **library.get::<*const u32>(b"MASH_PLUGIN_ABI\0").map_err(|e| e.to_string())?;
+    if abi != ABI_VERSION {
+        return Err(format!("plugin ABI version {} does not match {}", abi, ABI_VERSION));
+    }
+    let create = library.get::<fn() -> Box<dyn Plugin>>(b"mash_plugin_create\0").map_err(|e| e.to_string())?;
+    let plugin = create();
+    // the plugin's code must stay mapped for as long as the plugin exists,
+    // which is the rest of the process
+    std::mem::forget(library);
+    Ok(plugin)
+}
+
+/// `plugin [list]` shows each plugin and whether it is loaded; `plugin load
+/// NAME...` sources script plugins now rather than on first use.
+pub(crate) struct PluginBuiltin;
+
+impl Builtin for PluginBuiltin {
+    fn name(&self) -> &'static str {
+        "plugin"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "plugin [list | load name ...]"
+    }
+
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        match args.split_first() {
+            None => list(shell, io.stdout),
+            Some((command, _)) if command == "list" => list(shell, io.stdout),
+            Some((command, names)) if command == "load" => {
+                let mut status = 0;
+                for name in names {
+                    if !shell.autoload(name) && !shell.plugins.sourced.contains(name) {
+                        let _ = writeln!(io.stderr, "plugin: {}: not found", name);
+                        status = 1;
+                    }
+                }
+                return Ok(status);
+            }
+            Some((command, _)) => return Err(ShellError::Usage(format!("plugin: {}: invalid command", command))),
+        }
+        Ok(0)
+    }
+}
+
+fn list(shell: &Shell, out: &mut dyn Write) {
+    let mut lines: Vec<(String, &str)> = Vec::new();
+    lines.extend(shell.plugins.pending.keys().map(|n| (n.clone(), "script, not loaded")));
+    lines.extend(shell.plugins.sourced.iter().map(|n| (n.clone(), "script, loaded")));
+    lines.extend(shell.plugins.native.iter().map(|p| (p.name().to_string(), if shell.plugins.hooks.iter().any(|h| h == p.name()) { "hook" } else { "native" })));
+    lines.sort();
+    for (name, state) in lines {
+        let _ = writeln!(out, "{}\t{}", name, state);
+    }
+}
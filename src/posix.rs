@@ -0,0 +1,41 @@
+//! `set -o posix`, also turned on by `--posix` or by running mash as `sh`:
+//! mash's extensions are switched off and behaviour follows POSIX sh more
+//! closely, so that mash can run scripts written for `/bin/sh`.
+//!
+//! In this mode arrays, `select`, process substitution, `((...))` and
+//! builtins POSIX does not define are unavailable; an error in a special builtin such as `shift` or `.` makes a
+//! non-interactive shell exit; and an interactive shell reads the file named
+//! by `$ENV` instead of ~/.mashrc, skipping the configuration file, plugins
+//! and per-directory environment files.
+
+use crate::{Shell, ShellError};
+
+/// Builtins whose errors end a non-interactive POSIX shell.
+const SPECIAL: &[&str] = &[".", ":", "break", "continue", "eval", "exec", "exit", "export", "readonly", "return", "set", "shift", "times", "trap", "unset"];
+
+/// The other builtins POSIX describes.
+const REGULAR: &[&str] = &[
+    "alias", "bg", "cd", "command", "false", "fc", "fg", "getopts", "hash", "jobs", "kill", "newgrp", "pwd", "read", "true", "type", "ulimit",
+    "umask", "unalias", "wait",
+];
+
+impl Shell {
+    /// Whether the builtin `name` may be used, which in POSIX mode is only so
+    /// for the builtins POSIX defines.
+    pub(crate) fn builtin_enabled(&self, name: &str) -> bool {
+        !self.options.posix || SPECIAL.contains(&name) || REGULAR.contains(&name)
+    }
+
+    /// Whether a failure of the builtin `name` should end the shell.
+    pub(crate) fn fatal_builtin_error(&self, name: &str) -> bool {
+        self.options.posix && !self.interactive && SPECIAL.contains(&name)
+    }
+
+    /// Fail if POSIX mode is on, since `feature` is an extension.
+    pub(crate) fn extension(&self, feature: &str) -> Result<(), ShellError> {
+        if self.options.posix {
+            return Err(ShellError::Failed(format!("{}: not supported in POSIX mode", feature)));
+        }
+        Ok(())
+    }
+}
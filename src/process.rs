@@ -0,0 +1,231 @@
+//! Builtins that change the shell process itself: `umask` and `ulimit` set
+//! what every command it starts inherits, and `exec` replaces the shell with
+//! a program or makes redirections its own.
+
+use nix::sys::{resource::{RLIM_INFINITY, Resource, getrlimit, rlim_t, setrlimit}, stat::{Mode, mode_t, umask}};
+
+use crate::{Builtin, Io, Shell, ShellError, exec::exec_in_place, signals};
+
+pub(crate) struct Umask;
+
+impl Builtin for Umask {
+    fn name(&self) -> &'static str {
+        "umask"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "umask [-S] [mode]"
+    }
+
+    /// Print the file creation mask, in octal or with `-S` as the
+    /// permissions it leaves, or set it from an octal or symbolic mode.
+    fn run(&self, _: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (symbolic, args) = match args.first().map(String::as_str) {
+            Some("-S") => (true, &args[1..]),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => return Err(ShellError::Usage(format!("umask: {}: invalid option", flag))),
+            _ => (false, args),
+        };
+        let old = umask(Mode::empty());
+        umask(old);
+        let current = old.bits() & 0o777;
+        let Some(mode) = args.first() else {
+            let _ = match symbolic {
+                true => writeln!(io.stdout, "{}", permissions(!current & 0o777)),
+                false => writeln!(io.stdout, "{:04o}", current),
+            };
+            return Ok(0);
+        };
+        let mask = parse_mask(mode, current).ok_or_else(|| ShellError::Failed(format!("umask: {}: invalid mode", mode)))?;
+        umask(Mode::from_bits_truncate(mask));
+        Ok(0)
+    }
+}
+
+/// The mask `mode` gives: an octal number, or clauses like `u=rwx,go-w`
+/// changing the permissions `current` leaves.
+fn parse_mask(mode: &str, current: mode_t) -> Option<mode_t> {
+    if mode.bytes().all(|b| b.is_ascii_digit()) {
+        return mode_t::from_str_radix(mode, 8).ok().filter(|&mask| mask <= 0o777);
+    }
+    let mut allowed = !current & 0o777;
+    for clause in mode.split(',') {
+        let op = clause.find(['=', '+', '-'])?;
+        let who = clause[..op].chars().try_fold(0, |who, c| match c {
+            'u' => Some(who | 0o700),
+            'g' => Some(who | 0o070),
+            'o' => Some(who | 0o007),
+            'a' => Some(who | 0o777),
+            _ => None,
+        })?;
+        let who = if who == 0 { 0o777 } else { who };
+        let bits = clause[op + 1..].chars().try_fold(0, |bits, c| match c {
+            'r' => Some(bits | 0o444),
+            'w' => Some(bits | 0o222),
+            'x' => Some(bits | 0o111),
+            _ => None,
+        })? & who;
+        match &clause[op..op + 1] {
+            "=" => allowed = allowed & !who | bits,
+            "+" => allowed |= bits,
+            _ => allowed &= !bits,
+        }
+    }
+    Some(!allowed & 0o777)
+}
+
+/// `allowed` as `umask -S` shows it.
+fn permissions(allowed: mode_t) -> String {
+    let class = |shift: u32| ["r", "w", "x"].iter().zip([4, 2, 1]).filter(|&(_, bit)| allowed >> shift & bit != 0).map(|(c, _)| *c).collect::<String>();
+    format!("u={},g={},o={}", class(6), class(3), class(0))
+}
+
+/// A resource `ulimit` knows, by its option letter.
+struct Limit {
+    flag: char,
+    resource: Resource,
+    name: &'static str,
+    /// What its values are counted in, and how many bytes that is.
+    unit: Option<(&'static str, rlim_t)>,
+}
+
+const LIMITS: &[Limit] = &[
+    Limit { flag: 'c', resource: Resource::RLIMIT_CORE, name: "core file size", unit: Some(("blocks", 1024)) },
+    Limit { flag: 'd', resource: Resource::RLIMIT_DATA, name: "data seg size", unit: Some(("kbytes", 1024)) },
+    Limit { flag: 'f', resource: Resource::RLIMIT_FSIZE, name: "file size", unit: Some(("blocks", 1024)) },
+    Limit { flag: 'l', resource: Resource::RLIMIT_MEMLOCK, name: "max locked memory", unit: Some(("kbytes", 1024)) },
+    Limit { flag: 'm', resource: Resource::RLIMIT_RSS, name: "max memory size", unit: Some(("kbytes", 1024)) },
+    Limit { flag: 'n', resource: Resource::RLIMIT_NOFILE, name: "open files", unit: None },
+    Limit { flag: 's', resource: Resource::RLIMIT_STACK, name: "stack size", unit: Some(("kbytes", 1024)) },
+    Limit { flag: 't', resource: Resource::RLIMIT_CPU, name: "cpu time", unit: Some(("seconds", 1)) },
+    Limit { flag: 'u', resource: Resource::RLIMIT_NPROC, name: "max user processes", unit: None },
+    Limit { flag: 'v', resource: Resource::RLIMIT_AS, name: "virtual memory", unit: Some(("kbytes", 1024)) },
+];
+
+impl Limit {
+    fn scale(&self) -> rlim_t {
+        self.unit.map_or(1, |(_, bytes)| bytes)
+    }
+
+    fn show(&self, value: rlim_t) -> String {
+        match value {
+            RLIM_INFINITY => String::from("unlimited"),
+            value => (value / self.scale()).to_string(),
+        }
+    }
+}
+
+pub(crate) struct Ulimit;
+
+impl Builtin for Ulimit {
+    fn name(&self) -> &'static str {
+        "ulimit"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "ulimit [-HSa] [-cdflmnstuv] [limit]"
+    }
+
+    /// Print or set the limits on the resources of the shell and the
+    /// commands it starts, the file size if none is named. `-a` prints all
+    /// of them. `-H` is for the hard limit and `-S` the soft one; a new
+    /// limit sets both unless one is given.
+    fn run(&self, _: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (mut hard, mut soft, mut all) = (false, false, false);
+        let mut limits = Vec::new();
+        let mut rest = args;
+        while let Some(flag) = rest.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+            rest = &rest[1..];
+            if flag == "--" {
+                break;
+            }
+            for c in flag[1..].chars() {
+                match c {
+                    'H' => hard = true,
+                    'S' => soft = true,
+                    'a' => all = true,
+                    c => limits.push(LIMITS.iter().find(|limit| limit.flag == c).ok_or_else(|| ShellError::Usage(format!("ulimit: -{}: invalid option", c)))?),
+                }
+            }
+        }
+        if all {
+            limits = LIMITS.iter().collect();
+        } else if limits.is_empty() {
+            limits.push(&LIMITS[2]);
+        }
+        if let Some(value) = rest.first().filter(|_| !all) {
+            for limit in limits {
+                let (old_soft, old_hard) = getrlimit(limit.resource).map_err(|e| ShellError::sys("ulimit", e))?;
+                let new = match value.as_str() {
+                    "unlimited" => RLIM_INFINITY,
+                    value => value.parse::<rlim_t>().ok().and_then(|n| n.checked_mul(limit.scale())).ok_or_else(|| ShellError::Failed(format!("ulimit: {}: invalid number", value)))?,
+                };
+                let (new_soft, new_hard) = match (hard, soft) {
+                    (true, false) => (old_soft, new),
+                    (false, true) => (new, old_hard),
+                    _ => (new, new),
+                };
+                setrlimit(limit.resource, new_soft, new_hard).map_err(|e| ShellError::sys(format!("ulimit: {}", limit.name), e))?;
+            }
+            return Ok(0);
+        }
+        let labelled = limits.len() > 1;
+        for limit in limits {
+            let (current_soft, current_hard) = getrlimit(limit.resource).map_err(|e| ShellError::sys("ulimit", e))?;
+            let value = limit.show(if hard && !soft { current_hard } else { current_soft });
+            let _ = match (labelled, limit.unit) {
+                (false, _) => writeln!(io.stdout, "{}", value),
+                (true, Some((unit, _))) => writeln!(io.stdout, "{:<20}{:>16} {}", limit.name, format!("({}, -{})", unit, limit.flag), value),
+                (true, None) => writeln!(io.stdout, "{:<20}{:>16} {}", limit.name, format!("(-{})", limit.flag), value),
+            };
+        }
+        Ok(0)
+    }
+}
+
+pub(crate) struct Exec;
+
+impl Builtin for Exec {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "exec [command [arg ...]]"
+    }
+
+    /// Replace the shell with the command; a non-interactive shell exits if
+    /// it cannot be found. Without one, the redirections given with `exec`
+    /// stay in place for the rest of the shell.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let args = match args.first() {
+            Some(first) if first == "--" => &args[1..],
+            _ => args,
+        };
+        let Some(name) = args.first() else {
+            shell.keep_redirections = true;
+            return Ok(0);
+        };
+        shell.restrict("exec")?;
+        let program = shell.hashed_program(name).inspect_err(|_| shell.exiting |= !shell.interactive)?;
+        let _ = io.stdout.flush();
+        signals::default_signals();
+        exec_in_place(&program, args.to_vec(), &[], &shell.path)?;
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mask, permissions};
+
+    #[test]
+    fn masks_are_octal_or_symbolic() {
+        assert_eq!(parse_mask("027", 0o022), Some(0o027));
+        assert_eq!(parse_mask("u=rwx,g=rx,o=", 0o022), Some(0o027));
+        assert_eq!(parse_mask("go-w", 0o002), Some(0o022));
+        assert_eq!(parse_mask("a+rwx", 0o022), Some(0));
+        assert_eq!(parse_mask("999", 0), None);
+        assert_eq!(parse_mask("u=z", 0), None);
+        assert_eq!(permissions(0o755), "u=rwx,g=rx,o=rx");
+    }
+}
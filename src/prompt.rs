@@ -0,0 +1,196 @@
+//! The interactive prompt.
+//!
+//! By default it shows the working directory and the theme's symbol. Setting
+//! `PS1` (or `PROMPT`) replaces that with a template of text and escapes,
+//! expanded again before every line is read:
+//!
+//! | escape | expands to |
+//! |--------|------------|
+//! | `\u` | the user name |
+//! | `\h`, `\H` | the host name up to the first `.`, or all of it |
+//! | `\w`, `\W` | the working directory with `~` for home, or its last part |
+//! | `\t` | the time as `HH:MM:SS` |
+//! | `\$` | `#` for root, otherwise `$`, in red after a failed command |
+//! | `\?` | the last exit status |
+//! | `\g` | the git branch, with `*` if there are uncommitted changes |
+//! | `\n`, `\e`, `\\` | a newline, an escape character, a backslash |
+//! | `\[`, `\]` | nothing; bash uses them to bracket escape sequences |
+//!
+//! While a command is left unfinished, by an open quote, a trailing `\`,
+//! `|` or `&&`, or a compound command or here-document still to be closed,
+//! more lines are read after `PS2`, a template of the same kind, or `> `.
+
+use nix::{libc, unistd::{Uid, User, gethostname}};
+use serde::Deserialize;
+
+use crate::{Shell, ShellError, editor};
+
+/// How the prompt looks, set in the `[prompt]` table of the configuration
+/// file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Theme {
+    /// Shown after the working directory.
+    pub(crate) symbol: String,
+    /// The color of the working directory.
+    pub(crate) color: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self { symbol: String::from("%"), color: None }
+    }
+}
+
+const COLORS: &[&str] = &["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+impl Theme {
+    pub(crate) fn check(&self) -> Result<(), String> {
+        match &self.color {
+            Some(color) if color_code(color).is_none() => Err(format!("{}: unknown color", color)),
+            _ => Ok(()),
+        }
+    }
+
+    fn render(&self, path: &str) -> String {
+        match self.color.as_deref().and_then(color_code) {
+            Some(code) => format!("\x1b[{}m{}\x1b[0m {} ", code, path, self.symbol),
+            None => format!("{} {} ", path, self.symbol),
+        }
+    }
+}
+
+/// The SGR code for a color name, which may be prefixed with `bright-` or
+/// `bold-`.
+fn color_code(name: &str) -> Option<String> {
+    let (prefix, base) = match name.split_once('-') {
+        Some((prefix, base)) => (Some(prefix), base),
+        None => (None, name),
+    };
+    let n = COLORS.iter().position(|&c| c == base)?;
+    match prefix {
+        None => Some(format!("3{}", n)),
+        Some("bright") => Some(format!("9{}", n)),
+        Some("bold") => Some(format!("1;3{}", n)),
+        Some(_) => None,
+    }
+}
+
+impl Shell {
+    /// Show the working directory in the prompt, resolving symlinks and `..`.
+    pub(crate) fn update_prompt(&mut self) -> Result<(), ShellError> {
+        let path = self.path.canonicalize().map_err(|e| ShellError::io(self.path.display().to_string(), e))?;
+        self.prompt = self.theme.render(&path.display().to_string());
+        Ok(())
+    }
+
+    /// The prompt to hand to the line editor, with the vi mode indicator and
+    /// any plugin segments.
+    pub(crate) fn editor_prompt(&self) -> String {
+        let prompt = match self.var("PS1").or_else(|| self.var("PROMPT")) {
+            Some(template) => self.expand_prompt(&template),
+            None => self.prompt.clone(),
+        };
+        let prompt = format!("{}{}", self.prompt_segments(), prompt);
+        if self.options.vi {
+            format!("{}{}", editor::VI_INSERT_INDICATOR, prompt)
+        } else {
+            prompt
+        }
+    }
+
+    /// The prompt for another line of an unfinished command.
+    pub(crate) fn continuation_prompt(&self) -> String {
+        match self.var("PS2") {
+            Some(template) => self.expand_prompt(&template),
+            None => String::from("> "),
+        }
+    }
+}
+
+impl Shell {
+    /// Expand the escapes in a `PS1` or `PS2` template.
+    pub(crate) fn expand_prompt(&self, template: &str) -> String {
+        let mut prompt = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                prompt.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('u') => prompt.push_str(&user_name()),
+                Some('h') => prompt.push_str(host_name().split('.').next().unwrap_or_default()),
+                Some('H') => prompt.push_str(&host_name()),
+                Some('w') => prompt.push_str(&self.tilde(&self.path)),
+                Some('W') => match self.tilde(&self.path).as_str() {
+                    "~" | "/" => prompt.push_str(&self.tilde(&self.path)),
+                    dir => prompt.push_str(dir.rsplit('/').next().unwrap_or(dir)),
+                },
+                Some('t') => prompt.push_str(&clock()),
+                Some('$') => {
+                    let symbol = if Uid::effective().is_root() { '#' } else { '$' };
+                    if self.last_status == 0 {
+                        prompt.push(symbol);
+                    } else {
+                        prompt.push_str(&format!("\x1b[31m{}\x1b[0m", symbol));
+                    }
+                }
+                Some('g') => prompt.push_str(&self.git_segment()),
+                Some('?') => prompt.push_str(&self.last_status.to_string()),
+                Some('n') => prompt.push('\n'),
+                Some('e') => prompt.push('\x1b'),
+                Some('[' | ']') => {}
+                Some('\\') | None => prompt.push('\\'),
+                Some(other) => {
+                    prompt.push('\\');
+                    prompt.push(other);
+                }
+            }
+        }
+        prompt
+    }
+}
+
+fn user_name() -> String {
+    std::env::var("USER").ok().filter(|u| !u.is_empty())
+        .or_else(|| User::from_uid(Uid::current()).ok().flatten().map(|u| u.name))
+        .unwrap_or_default()
+}
+
+fn host_name() -> String {
+    gethostname().map(|h| h.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// The local time as `HH:MM:SS`.
+fn clock() -> String {
+    // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes
+    // to the struct it is given
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Shell;
+
+    #[test]
+    fn prompt_escapes() {
+        let mut shell = Shell::new();
+        shell.home = std::path::PathBuf::from("/home/someone");
+        shell.path = std::path::PathBuf::from("/home/someone/src/mash");
+        assert_eq!(shell.expand_prompt(r"\w \W>"), "~/src/mash mash>");
+        assert_eq!(shell.expand_prompt(r"a\\b\[\]\n\q"), "a\\b\n\\q");
+        shell.last_status = 1;
+        assert_eq!(shell.expand_prompt(r"\?"), "1");
+        assert!(shell.expand_prompt(r"\$").contains("\x1b[31m"));
+        let time = shell.expand_prompt(r"\t");
+        assert_eq!(time.len(), 8);
+        assert_eq!(time.matches(':').count(), 2);
+    }
+}
@@ -0,0 +1,184 @@
+//! `read`: take a line from standard input and split it into variables.
+//!
+//! The line is split on the characters of `IFS`, space, tab and newline if
+//! it is unset. Runs of IFS whitespace count as one separator and are
+//! trimmed from both ends; other IFS characters separate a field each. The
+//! last variable gets whatever is left of the line. Unless `-r` is given, a
+//! backslash keeps the next character from separating fields and one at the
+//! end of a line joins the next line to it.
+
+use std::io::IsTerminal;
+
+use nix::sys::termios::{self, LocalFlags, SetArg};
+
+use crate::{Builtin, Io, Shell, ShellError, parser::is_name};
+
+pub(crate) const DEFAULT_IFS: &str = " \t\n";
+
+/// A character of the line read, and whether a backslash quoted it.
+type Char = (char, bool);
+
+pub(crate) struct Read;
+
+impl Builtin for Read {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "read [-rs] [-p prompt] [name ...]"
+    }
+
+    /// Read a line into the named variables, or `REPLY`. `-p` shows a prompt
+    /// when reading from a terminal and `-s` keeps the typed text from being
+    /// echoed there. Fails at the end of input.
+    fn run(&self, shell: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        let (mut raw, mut silent, mut prompt) = (false, false, None);
+        let mut names = args;
+        while let Some(flag) = names.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+            names = &names[1..];
+            if flag == "--" {
+                break;
+            }
+            for c in flag[1..].chars() {
+                match c {
+                    'r' => raw = true,
+                    's' => silent = true,
+                    'p' => {
+                        let Some((text, rest)) = names.split_first() else {
+                            return Err(ShellError::Usage(String::from("read: -p: option requires an argument")));
+                        };
+                        prompt = Some(text.clone());
+                        names = rest;
+                    }
+                    _ => return Err(ShellError::Usage(format!("read: -{}: invalid option", c))),
+                }
+            }
+        }
+        if let Some(name) = names.iter().find(|name| !is_name(name)) {
+            return Err(ShellError::Failed(format!("read: `{}': not a valid identifier", name)));
+        }
+        for name in names {
+            shell.restrict_variable(name)?;
+        }
+        let terminal = std::io::stdin().is_terminal();
+        if let Some(prompt) = prompt.filter(|_| terminal) {
+            let _ = write!(io.stderr, "{}", prompt);
+            let _ = io.stderr.flush();
+        }
+        let echo_off = silent && terminal;
+        let saved = match echo_off {
+            true => termios::tcgetattr(0).ok(),
+            false => None,
+        };
+        if let Some(mode) = &saved {
+            let mut quiet = mode.clone();
+            quiet.local_flags.remove(LocalFlags::ECHO);
+            let _ = termios::tcsetattr(0, SetArg::TCSANOW, &quiet);
+        }
+        let (line, complete) = read_line(raw);
+        if let Some(mode) = &saved {
+            let _ = termios::tcsetattr(0, SetArg::TCSANOW, mode);
+            // the newline typed was not echoed either
+            let _ = writeln!(io.stderr);
+        }
+        let ifs = shell.var("IFS").unwrap_or_else(|| String::from(DEFAULT_IFS));
+        let reply = [String::from("REPLY")];
+        let names = if names.is_empty() { &reply[..] } else { names };
+        let fields = split(&line, &ifs, names.len());
+        for (i, name) in names.iter().enumerate() {
+            shell.set_var(name, fields.get(i).cloned().unwrap_or_default());
+        }
+        Ok(if complete { 0 } else { 1 })
+    }
+}
+
+/// Read a line from standard input a byte at a time, so nothing after it is
+/// taken from a shared input. Returns the line and whether it ended with a
+/// newline rather than the end of input.
+fn read_line(raw: bool) -> (Vec<Char>, bool) {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8];
+    let mut escaped = false;
+    let complete = loop {
+        match nix::unistd::read(0, &mut byte) {
+            Ok(0) | Err(_) => break false,
+            // a line continuation, which leaves nothing behind
+            Ok(_) if escaped && byte[0] == b'\n' => {
+                bytes.pop();
+                escaped = false;
+            }
+            Ok(_) if byte[0] == b'\n' => break true,
+            Ok(_) => {
+                escaped = !raw && !escaped && byte[0] == b'\\';
+                bytes.push(byte[0]);
+            }
+        }
+    };
+    let mut line = Vec::new();
+    let text = String::from_utf8_lossy(&bytes);
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if !raw => line.extend(chars.next().map(|c| (c, true))),
+            c => line.push((c, false)),
+        }
+    }
+    (line, complete)
+}
+
+/// Split `line` into at most `count` fields on the characters of `ifs`.
+fn split(line: &[Char], ifs: &str, count: usize) -> Vec<String> {
+    let separator = |&(c, quoted): &Char| !quoted && ifs.contains(c);
+    let blank = |c: &Char| separator(c) && DEFAULT_IFS.contains(c.0);
+    let text = |s: &[Char]| s.iter().map(|&(c, _)| c).collect::<String>();
+    let mut fields = Vec::new();
+    let mut rest = skip(line, blank);
+    while rest.last().is_some_and(blank) {
+        rest = &rest[..rest.len() - 1];
+    }
+    while fields.len() + 1 < count && !rest.is_empty() {
+        let Some(end) = rest.iter().position(separator) else {
+            break;
+        };
+        fields.push(text(&rest[..end]));
+        // blanks around a separator belong to it, and so does one other
+        // IFS character
+        let after = skip(&rest[end..], blank);
+        rest = match after.first() {
+            Some(c) if separator(c) && !blank(c) => skip(&after[1..], blank),
+            _ => after,
+        };
+    }
+    if !rest.is_empty() {
+        fields.push(text(rest));
+    }
+    fields
+}
+
+/// `s` without the characters at its start that `skipped` matches.
+fn skip(mut s: &[Char], skipped: impl Fn(&Char) -> bool) -> &[Char] {
+    while s.first().is_some_and(&skipped) {
+        s = &s[1..];
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split;
+
+    fn fields(line: &str, ifs: &str, count: usize) -> Vec<String> {
+        split(&line.chars().map(|c| (c, false)).collect::<Vec<_>>(), ifs, count)
+    }
+
+    #[test]
+    fn lines_split_on_ifs() {
+        assert_eq!(fields("  one  two three  ", " \t\n", 2), ["one", "two three"]);
+        assert_eq!(fields("a:b::c", ":", 4), ["a", "b", "", "c"]);
+        assert_eq!(fields("a : b", " :", 3), ["a", "b"]);
+        assert_eq!(fields("  whole line  ", " ", 1), ["whole line"]);
+        let quoted = [('a', false), (' ', true), ('b', false), (' ', false), ('c', false)];
+        assert_eq!(split(&quoted, " ", 2), ["a b", "c"]);
+    }
+}
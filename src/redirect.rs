@@ -0,0 +1,171 @@
+//! Redirections: `<`, `>`, `>>`, `<&` and `>&`, with an optional descriptor
+//! number in front, as in `2>>log` or `2>&1`, and here-documents and
+//! here-strings, `<<` and `<<<`, which are read from a pipe the shell fills.
+//!
+//! They are applied in the shell process itself, so they work the same for
+//! builtins, functions and external programs, which inherit the descriptors.
+//! The descriptors they replace are kept and put back once the command is
+//! done.
+
+use std::{fs::File, io::Write, os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd}};
+
+use nix::{errno::Errno, fcntl::{FcntlArg, OFlag, fcntl, open}, sys::{stat::Mode, wait::waitpid}, unistd::{ForkResult, Pid, close, dup2, fork, pipe2}};
+
+use crate::{Shell, ShellError, error, lexer::RedirectOp, parser::Redirect, signals};
+
+/// Copies of the shell's own descriptors are kept at or above this number, out
+/// of the way of the ones commands use.
+const SAVED_FD_MIN: i32 = 10;
+
+/// Text up to this size is written into a pipe straight away; anything
+/// longer could fill it before the command reads, so a child writes it.
+const PIPE_SIZE: usize = 4096;
+
+/// Descriptors replaced by redirections, restored when this is dropped.
+pub(crate) struct Redirected {
+    /// Each descriptor with a copy of what it was, or `None` if it was not
+    /// open.
+    saved: Vec<(i32, Option<OwnedFd>)>,
+    /// The processes writing long here-documents, waited for at the end.
+    writers: Vec<Pid>,
+}
+
+impl Redirected {
+    /// Make the redirections the shell's own for good, as `exec` does.
+    pub(crate) fn keep(&mut self) {
+        self.saved.clear();
+        // a writer may still be filling a here-document that now stays open
+        self.writers.clear();
+    }
+}
+
+impl Drop for Redirected {
+    fn drop(&mut self) {
+        // output still buffered belongs to the redirected descriptor
+        let _ = std::io::stdout().flush();
+        for (fd, saved) in self.saved.drain(..).rev() {
+            match saved {
+                Some(saved) => {
+                    let _ = dup2(saved.as_raw_fd(), fd);
+                }
+                None => {
+                    let _ = close(fd);
+                }
+            }
+        }
+        // with the pipes closed, a writer the command did not read to the end
+        // stops too
+        for writer in self.writers.drain(..) {
+            while waitpid(writer, None) == Err(Errno::EINTR) {}
+        }
+    }
+}
+
+impl Shell {
+    /// Apply `redirects` in order. If one fails, those already applied are
+    /// undone and the command should not run.
+    pub(crate) fn redirect(&mut self, redirects: &[Redirect]) -> Result<Redirected, ShellError> {
+        let mut redirected = Redirected { saved: Vec::new(), writers: Vec::new() };
+        if redirects.is_empty() {
+            return Ok(redirected);
+        }
+        let _ = std::io::stdout().flush();
+        for redirect in redirects {
+            let target = match &redirect.here_doc {
+                // the delimiter is not expanded, but decides whether the body is
+                Some(body) if redirect.target.contains(['\'', '"', '\\']) => body.clone(),
+                Some(body) => self.expand_here_doc(body)?,
+                None => self.redirect_target(&redirect.target)?,
+            };
+            if !redirected.saved.iter().any(|(fd, _)| *fd == redirect.fd) {
+                redirected.saved.push((redirect.fd, save(redirect.fd)));
+            }
+            match redirect.op {
+                RedirectOp::HereDoc | RedirectOp::HereString => {
+                    let text = match redirect.op {
+                        RedirectOp::HereString => target + "\n",
+                        _ => target,
+                    };
+                    let (read, writer) = fill_pipe(text)?;
+                    redirected.writers.extend(writer);
+                    install(read, redirect.fd).map_err(|e| ShellError::sys("here-document", e))?;
+                }
+                RedirectOp::Read | RedirectOp::Write | RedirectOp::Append => {
+                    let flags = match redirect.op {
+                        RedirectOp::Read => OFlag::O_RDONLY,
+                        RedirectOp::Write => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC,
+                        _ => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_APPEND,
+                    };
+                    if redirect.op != RedirectOp::Read {
+                        self.restrict(&target)?;
+                    }
+                    let path = self.path.join(&target);
+                    let file = open(&path, flags | OFlag::O_CLOEXEC, Mode::from_bits_truncate(0o666)).map_err(|e| ShellError::sys(target.clone(), e))?;
+                    // SAFETY: `open` just returned this descriptor
+                    let file = unsafe { OwnedFd::from_raw_fd(file) };
+                    install(file, redirect.fd).map_err(|e| ShellError::sys(target, e))?;
+                }
+                RedirectOp::DupRead | RedirectOp::DupWrite if target == "-" => {
+                    let _ = close(redirect.fd);
+                }
+                RedirectOp::DupRead | RedirectOp::DupWrite => {
+                    let from: i32 = target.parse().map_err(|_| ShellError::Failed(format!("{}: ambiguous redirect", target)))?;
+                    dup2(from, redirect.fd).map_err(|e| ShellError::sys(target, e))?;
+                }
+            }
+        }
+        Ok(redirected)
+    }
+
+    /// Expand the target of a redirection, which must give a single word.
+    fn redirect_target(&mut self, word: &str) -> Result<String, ShellError> {
+        match <[String; 1]>::try_from(self.expand_words(&[word.to_string()])?) {
+            Ok([target]) => Ok(target),
+            Err(_) => Err(ShellError::Failed(format!("{}: ambiguous redirect", word))),
+        }
+    }
+}
+
+/// Make `fd` refer to what `file` does, leaving it open across exec.
+fn install(file: OwnedFd, fd: i32) -> nix::Result<()> {
+    if file.as_raw_fd() == fd {
+        // it was free and the file took its place, so keep it open
+        let fd = file.into_raw_fd();
+        fcntl(fd, FcntlArg::F_SETFD(nix::fcntl::FdFlag::empty()))?;
+    } else {
+        dup2(file.as_raw_fd(), fd)?;
+    }
+    Ok(())
+}
+
+/// A pipe that reads `text`, and the child writing it if it is too long to
+/// write now.
+fn fill_pipe(text: String) -> Result<(OwnedFd, Option<Pid>), ShellError> {
+    let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(|e| ShellError::sys("here-document", e))?;
+    // SAFETY: both descriptors were just created and are owned by nothing else
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(read), File::from_raw_fd(write)) };
+    if text.len() <= PIPE_SIZE {
+        (&write).write_all(text.as_bytes()).map_err(|e| ShellError::io("here-document", e))?;
+        return Ok((read, None));
+    }
+    // SAFETY: the child only writes to the pipe before exiting
+    match unsafe { fork() }.map_err(|e| ShellError::sys("fork", e))? {
+        ForkResult::Parent { child } => Ok((read, Some(child))),
+        ForkResult::Child => {
+            drop(read);
+            signals::default_pipe_signal();
+            if let Err(e) = (&write).write_all(text.as_bytes()) {
+                error::warn(ShellError::io("here-document", e));
+            }
+            std::process::exit(0);
+        }
+    }
+}
+
+/// A copy of `fd`, out of the way and closed on exec, or `None` if it is not
+/// open.
+fn save(fd: i32) -> Option<OwnedFd> {
+    let copy = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(SAVED_FD_MIN)).ok()?;
+    // SAFETY: `fcntl` just returned this descriptor
+    Some(unsafe { OwnedFd::from_raw_fd(copy) })
+}
@@ -0,0 +1,39 @@
+//! Restricted mode, `set -r` or `--restricted`, for a constrained login
+//! shell such as a service account's. A restricted shell cannot change
+//! directory; set, export or unset `PATH`, `ENV` or `SHELL`; redirect
+//! output to a file; or run a command, or source a file, named with a `/`.
+//!
+//! So that the startup files can set it up, `--restricted` only takes
+//! effect once they have been read. Once on, it cannot be turned off.
+
+use crate::{Shell, ShellError};
+
+/// Variables a restricted shell keeps as they are.
+const VARIABLES: &[&str] = &["PATH", "ENV", "SHELL"];
+
+impl Shell {
+    /// Fail with "`what`: restricted" if the shell is restricted.
+    pub(crate) fn restrict(&self, what: &str) -> Result<(), ShellError> {
+        match self.options.restricted {
+            true => Err(ShellError::Failed(format!("{}: restricted", what))),
+            false => Ok(()),
+        }
+    }
+
+    /// Fail if the variable `name` may not be changed.
+    pub(crate) fn restrict_variable(&self, name: &str) -> Result<(), ShellError> {
+        match VARIABLES.contains(&name) {
+            true => self.restrict(name),
+            false => Ok(()),
+        }
+    }
+
+    /// Turn on restricted mode once the startup files have been read, or
+    /// straight away if they have been already.
+    pub fn restrict_after_startup(&mut self) {
+        match self.interactive {
+            true => self.restrict_after_rc = true,
+            false => self.options.restricted = true,
+        }
+    }
+}
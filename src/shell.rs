@@ -0,0 +1,498 @@
+//! The [`Shell`] type and the ways of feeding it input: command strings,
+//! script files and standard input.
+
+use std::{collections::{HashMap, HashSet}, os::fd::OwnedFd, path::{Component, Path, PathBuf}};
+
+use nix::unistd::{Pid, chdir};
+use rustyline::KeyEvent;
+
+use crate::{ShellError, builtins::Builtins, complete, config::Config, debug, dirs, envfile, error, git, hash, jobs, logging, history, exec::{self, Flow}, parser::{self, ParseError}, plugin::Plugins, prompt::Theme, startup, term, vars};
+
+/// A shell: its variables, functions, options and working directory.
+///
+/// ```no_run
+/// let mut shell = mash::Shell::new();
+/// shell.eval_line("greeting=hello; echo $greeting").unwrap();
+/// assert_eq!(shell.last_status(), 0);
+/// ```
+pub struct Shell {
+    pub(crate) prompt: String,
+    pub(crate) path: PathBuf,
+    pub(crate) home: PathBuf,
+    pub(crate) last_status: i32,
+    pub(crate) options: Options,
+    pub(crate) interactive: bool,
+    pub(crate) exiting: bool,
+    /// `$0`: the shell or script name.
+    pub(crate) name: String,
+    /// `$1`, `$2`, ...
+    pub(crate) positional: Vec<String>,
+    /// `$$`: the shell's process, which subshells keep.
+    pub(crate) pid: u32,
+    /// Shell variables, which are not passed on to child processes.
+    pub(crate) vars: HashMap<String, vars::Value>,
+    /// Names given to `declare` without a value, declared but still unset.
+    pub(crate) declared: HashSet<String>,
+    pub(crate) functions: HashMap<String, exec::ShellFunction>,
+    pub(crate) flow: Flow,
+    pub(crate) function_depth: usize,
+    /// Loops enclosing the running command within the current function.
+    pub(crate) loop_depth: usize,
+    /// Conditions being evaluated, where a failure does not trigger `set -e`.
+    pub(crate) condition_depth: usize,
+    pub(crate) source_depth: usize,
+    /// The file commands are being read from, empty for `-c` strings and
+    /// standard input.
+    pub(crate) file: String,
+    /// The line of the command being run.
+    pub(crate) lineno: usize,
+    /// Callers of the running functions and sourced files, outermost first.
+    pub(crate) frames: Vec<exec::Frame>,
+    pub(crate) dynamic: vars::Dynamic,
+    pub(crate) debugger: Option<debug::Debugger>,
+    pub(crate) builtins: Builtins,
+    /// Actions set with `trap`, by event name.
+    pub(crate) traps: HashMap<String, String>,
+    /// Whether a trap action is running.
+    pub(crate) in_trap: bool,
+    pub(crate) plugins: Plugins,
+    /// Aliases by name, and those whose expansion is running.
+    pub(crate) aliases: HashMap<String, String>,
+    pub(crate) expanding_aliases: Vec<String>,
+    pub(crate) history_file: PathBuf,
+    /// The entries of the interactive history, oldest first.
+    pub(crate) history: Vec<String>,
+    pub(crate) history_shared: history::Shared,
+    pub(crate) theme: Theme,
+    /// The rc file read at startup, and when it and the configuration file
+    /// were last modified.
+    pub(crate) rcfile: Option<PathBuf>,
+    pub(crate) startup_mtimes: Vec<Option<std::time::SystemTime>>,
+    /// Configuration for the line editor, applied before the next prompt.
+    pub(crate) editor_config: Option<Config>,
+    /// Keys bound with `bind`, with the names they were given and their
+    /// commands, applied over the configuration file's.
+    pub(crate) bindings: Vec<(KeyEvent, String, String)>,
+    /// Whether `bind` has changed them since the editor was last set up.
+    pub(crate) bindings_changed: bool,
+    pub(crate) env_file: Option<envfile::Active>,
+    pub(crate) startup_profile: Option<startup::StartupProfile>,
+    /// Directories visited before this one, most recent first.
+    pub(crate) dir_history: Vec<PathBuf>,
+    /// The directory stack, top last.
+    pub(crate) dir_stack: Vec<PathBuf>,
+    /// How long a command must run to send a notification when it finishes.
+    pub(crate) notify_after: Option<std::time::Duration>,
+    /// Run the next simple command's program in this process instead of
+    /// forking, set in the child forked for a pipeline command.
+    pub(crate) exec_in_place: bool,
+    /// Leave the current command's redirections in place when it is done,
+    /// set by `exec` without a command.
+    pub(crate) keep_redirections: bool,
+    /// Compound assignments among the arguments of the running `declare`.
+    pub(crate) declare_arrays: Vec<parser::Assignment>,
+    /// Turn on restricted mode once the rc file has been read.
+    pub(crate) restrict_after_rc: bool,
+    pub(crate) jobs: jobs::JobTable,
+    /// The status of the last command substitution in the command being
+    /// expanded.
+    pub(crate) substitution_status: Option<i32>,
+    /// The shell's ends of the pipes to running process substitutions,
+    /// and their processes.
+    pub(crate) process_substitutions: Vec<(OwnedFd, Pid)>,
+    pub(crate) git: git::Cache,
+    pub(crate) hash: hash::CommandHash,
+    pub(crate) completions: complete::Shared,
+}
+
+/// What [`Shell::eval`] gives back: the exit status of the last command run,
+/// or the syntax error that kept the input from running.
+pub type ExecResult = Result<i32, ParseError>;
+
+/// Shell options toggled with `set -o` / `set +o`.
+#[derive(Default)]
+pub(crate) struct Options {
+    pub(crate) vi: bool,
+    /// Exit as soon as a command fails.
+    pub(crate) errexit: bool,
+    /// Treat expanding an unset variable as an error.
+    pub(crate) nounset: bool,
+    /// Print each command before running it.
+    pub(crate) xtrace: bool,
+    /// Parse commands without running them, to check scripts for syntax
+    /// errors. Interactive shells ignore it.
+    pub(crate) noexec: bool,
+    /// Echo input lines to stderr as they are read.
+    pub(crate) verbose: bool,
+    /// Reload the configuration and rc files when they change.
+    pub(crate) autoreload: bool,
+    /// Turn off extensions to POSIX sh.
+    pub(crate) posix: bool,
+    /// Push the directory `cd` leaves onto the directory stack.
+    pub(crate) autopushd: bool,
+    /// Show mash's internal diagnostics.
+    pub(crate) debug: bool,
+    /// Leave patterns in commands as they are instead of expanding them to
+    /// file names.
+    pub(crate) noglob: bool,
+    /// Remove patterns that match no files instead of keeping them.
+    pub(crate) nullglob: bool,
+    /// Treat patterns that match no files as an error.
+    pub(crate) failglob: bool,
+    /// Leave the line being typed at the prompt uncoloured and without
+    /// suggestions.
+    pub(crate) nohighlight: bool,
+    /// Keep the shell to its working directory and `PATH`, as a
+    /// constrained login shell.
+    pub(crate) restricted: bool,
+}
+
+impl Options {
+    /// Options that also have a single-letter flag, as in `set -e`.
+    pub(crate) const FLAGS: &'static [(char, &'static str)] = &[('e', "errexit"), ('u', "nounset"), ('x', "xtrace"), ('n', "noexec"), ('v', "verbose"), ('f', "noglob"), ('r', "restricted")];
+
+    /// Turn the option called `name` on or off, returning false if there is
+    /// no such option.
+    pub(crate) fn set(&mut self, name: &str, enable: bool) -> bool {
+        match name {
+            "vi" => self.vi = enable,
+            "emacs" => self.vi = !enable,
+            "errexit" => self.errexit = enable,
+            "nounset" => self.nounset = enable,
+            "xtrace" => self.xtrace = enable,
+            "noexec" => self.noexec = enable,
+            "verbose" => self.verbose = enable,
+            "autoreload" => self.autoreload = enable,
+            "posix" => self.posix = enable,
+            "autopushd" | "auto_pushd" => self.autopushd = enable,
+            "noglob" => self.noglob = enable,
+            "nullglob" => self.nullglob = enable,
+            "failglob" => self.failglob = enable,
+            "nohighlight" => self.nohighlight = enable,
+            // once on, for good
+            "restricted" => self.restricted |= enable,
+            "debug" => {
+                self.debug = enable;
+                logging::set_debug(enable);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shell {
+    /// An interactive shell, starting in the home directory.
+    pub fn new_interactive() -> Self {
+        let home = std::env::var("HOME").unwrap_or(String::from("/"));
+        Self {
+            prompt: format!("{} % ", home),
+            path: PathBuf::from(&home),
+            home: PathBuf::from(&home),
+            last_status: 0,
+            options: Options::default(),
+            interactive: true,
+            exiting: false,
+            name: String::from("mash"),
+            positional: Vec::new(),
+            pid: std::process::id(),
+            vars: HashMap::new(),
+            declared: HashSet::new(),
+            functions: HashMap::new(),
+            flow: Flow::Normal,
+            function_depth: 0,
+            loop_depth: 0,
+            condition_depth: 0,
+            source_depth: 0,
+            file: String::new(),
+            lineno: 0,
+            frames: Vec::new(),
+            dynamic: vars::Dynamic::default(),
+            debugger: None,
+            builtins: Builtins::default(),
+            traps: HashMap::new(),
+            in_trap: false,
+            plugins: Plugins::default(),
+            aliases: HashMap::new(),
+            expanding_aliases: Vec::new(),
+            history_file: dirs::state_file(Path::new(&home), history::HISTORY_FILE),
+            theme: Theme::default(),
+            rcfile: None,
+            startup_mtimes: Vec::new(),
+            editor_config: None,
+            bindings: Vec::new(),
+            bindings_changed: false,
+            env_file: None,
+            startup_profile: None,
+            dir_history: Vec::new(),
+            history: Vec::new(),
+            history_shared: history::Shared::default(),
+            dir_stack: Vec::new(),
+            notify_after: None,
+            exec_in_place: false,
+            keep_redirections: false,
+            declare_arrays: Vec::new(),
+            restrict_after_rc: false,
+            jobs: jobs::JobTable::default(),
+            substitution_status: None,
+            process_substitutions: Vec::new(),
+            git: git::Cache::default(),
+            hash: hash::CommandHash::default(),
+            completions: complete::Shared::default(),
+        }
+    }
+
+    /// A shell for running scripts or embedding: no interactive terminal
+    /// integration, starting in the current directory.
+    pub fn new() -> Self {
+        let path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        Self {
+            path,
+            interactive: false,
+            ..Self::new_interactive()
+        }
+    }
+
+    /// Parse and run `line`, which must be complete: a compound command left
+    /// open is a syntax error. Commands that fail are reported on stderr and
+    /// reflected in [`Shell::last_status`].
+    pub fn eval_line(&mut self, line: &str) -> Result<(), ParseError> {
+        self.eval_at(line, 1)
+    }
+
+    /// The exit status of the last command run.
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    /// Whether `exit` has been run, after which no more input should be fed
+    /// to the shell.
+    pub fn exiting(&self) -> bool {
+        self.exiting
+    }
+
+    /// Turn a `set -o` option on or off, returning false if there is no such
+    /// option.
+    pub fn set_option(&mut self, name: &str, enable: bool) -> bool {
+        self.options.set(name, enable)
+    }
+
+    /// Make `target` the working directory, returning the one left. `dir` is
+    /// the name it was given, for error messages; a failed change leaves the
+    /// shell where it was. `.` and `..` are resolved in the name, so `..`
+    /// leaves a symlink the way it was entered, and `PWD` and `OLDPWD` are
+    /// set.
+    pub(crate) fn change_dir(&mut self, dir: &str, target: PathBuf) -> Result<PathBuf, ShellError> {
+        self.restrict("cd")?;
+        let target = normalize(&self.path.join(target));
+        chdir(target.as_os_str()).map_err(|e| ShellError::sys(format!("cd: {}", dir), e))?;
+        let from = std::mem::replace(&mut self.path, target);
+        self.set_var("OLDPWD", from.display().to_string());
+        self.set_var("PWD", self.path.display().to_string());
+        self.remember_dir(from.clone());
+        self.update_prompt()?;
+        if self.interactive {
+            term::report_cwd(&self.path);
+        }
+        self.enter_directory();
+        Ok(from)
+    }
+
+    /// Where a relative `dir` given to `cd` is found through `CDPATH`, and
+    /// whether that was in one of its directories rather than the working
+    /// one. Names starting with `/`, `.` or `..` are not looked up.
+    pub(crate) fn search_cdpath(&self, dir: &str) -> Option<(PathBuf, bool)> {
+        let cdpath = self.var("CDPATH").filter(|p| !p.is_empty())?;
+        let first = dir.split('/').next().unwrap_or_default();
+        if dir.starts_with('/') || first == "." || first == ".." {
+            return None;
+        }
+        cdpath.split(':').find_map(|base| {
+            let candidate = self.path.join(if base.is_empty() { "." } else { base }).join(dir);
+            candidate.is_dir().then_some((candidate, !base.is_empty()))
+        })
+    }
+
+    /// Stop before the first command and read debugger commands from the
+    /// terminal, as `mash --debug` does.
+    pub fn enable_debugger(&mut self) {
+        self.debugger = Some(debug::Debugger::new());
+    }
+
+    /// Parse and run `source`, returning the exit status of the last command
+    /// run or the syntax error that kept it from running. Runtime errors are
+    /// reported on stderr as they happen.
+    pub fn eval(&mut self, source: &str) -> ExecResult {
+        self.eval_at(source, 1)?;
+        Ok(self.last_status)
+    }
+
+    /// Parse and run a complete piece of input whose first line is numbered
+    /// `first_line`. Runtime errors are reported as they happen; only syntax
+    /// errors are returned.
+    pub(crate) fn eval_at(&mut self, source: &str, first_line: usize) -> Result<(), ParseError> {
+        let list = tracing::debug_span!("parse", first_line).in_scope(|| parser::parse_from(source, first_line))?;
+        self.run_list(&list);
+        Ok(())
+    }
+
+    /// Run every line of `source` until it is exhausted or `exit` is called,
+    /// reporting errors and carrying on like an interactive shell would.
+    pub(crate) fn run_source(&mut self, source: &str) {
+        self.run_lines(source.lines().map(String::from));
+    }
+
+    /// Run the commands in `path` in the current shell, as `source` does.
+    pub(crate) fn source_file(&mut self, path: &Path) -> Result<(), ShellError> {
+        let source = std::fs::read_to_string(path).map_err(|e| ShellError::io(path.display().to_string(), e))?;
+        self.push_frame("source", path.display().to_string());
+        self.source_depth += 1;
+        self.run_source(&source);
+        self.source_depth -= 1;
+        self.pop_frame();
+        if self.flow == Flow::Return {
+            self.flow = Flow::Normal;
+        }
+        Ok(())
+    }
+
+    /// Read /etc/profile and then ~/.mash_profile, as login shells do.
+    pub fn source_profile(&mut self) {
+        let profiles = [PathBuf::from("/etc/profile"), self.home.join(".mash_profile")];
+        self.timed("login profile", |shell| {
+            for profile in profiles.iter().filter(|p| p.exists()) {
+                if let Err(e) = shell.source_file(profile) {
+                    shell.report(e);
+                }
+                if shell.exiting {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Run ~/.mash_logout, as a login shell does when it exits, keeping the exit status
+    /// of whatever made the shell exit.
+    pub fn logout(&mut self) {
+        let logout = self.home.join(".mash_logout");
+        if logout.exists() {
+            let status = self.last_status;
+            if let Err(e) = self.source_file(&logout) {
+                self.report(e);
+            }
+            self.last_status = status;
+        }
+    }
+
+    /// Run lines of input as they arrive. Lines are collected until they
+    /// form a complete command, so compound commands may span several lines.
+    fn run_lines(&mut self, lines: impl Iterator<Item = String>) {
+        let mut buffer = String::new();
+        let mut first_line = 1;
+        // why the input collected in `buffer` is not complete yet
+        let mut incomplete = ParseError::Incomplete;
+        for (n, line) in lines.enumerate() {
+            if self.options.verbose {
+                eprintln!("{}", line);
+            }
+            if buffer.is_empty() {
+                first_line = n + 1;
+            }
+            buffer.push_str(&line);
+            buffer.push('\n');
+            match self.eval_at(&buffer, first_line) {
+                Err(e) if e.is_incomplete() => {
+                    incomplete = e;
+                    continue;
+                }
+                Err(e) => {
+                    self.lineno = first_line;
+                    self.report(e.into());
+                }
+                Ok(()) => {}
+            }
+            buffer.clear();
+            if self.exiting {
+                break;
+            }
+        }
+        if !buffer.is_empty() {
+            self.lineno = first_line;
+            self.report(incomplete.into());
+        }
+    }
+
+    /// `mash -c 'cmd' [name [args...]]`: like `sh -c`, the first argument
+    /// after the command string becomes `$0` and the rest the positional
+    /// parameters.
+    pub fn run_command_string(&mut self, command: &str, args: &[String]) {
+        if let Some((name, positional)) = args.split_first() {
+            self.name = name.clone();
+            self.positional = positional.to_vec();
+        }
+        self.run_source(command);
+    }
+
+    /// Run a script file. A leading `#!` line is skipped like any other
+    /// comment, so `#!/usr/bin/env mash` scripts work when executed directly.
+    pub fn run_script(&mut self, path: &str, args: &[String]) {
+        self.name = path.to_string();
+        self.positional = args.to_vec();
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                self.file = path.to_string();
+                self.run_source(&source);
+            }
+            Err(e) => {
+                error::warn(format!("{}: {}", path, error::describe_io(&e)));
+                self.last_status = 127;
+            }
+        }
+    }
+
+    /// Execute commands read from a non-terminal stdin, without prompts or
+    /// history.
+    pub fn run_stdin(&mut self) {
+        self.run_lines(std::iter::from_fn(read_stdin_line));
+    }
+}
+
+/// Read one line from stdin, without the trailing newline, or `None` at end
+/// of input. Input is read a byte at a time so that commands which read stdin
+/// themselves see the rest of it, the same way `sh` behaves. A signal
+/// arriving while it waits does not end the input.
+pub(crate) fn read_stdin_line() -> Option<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        match nix::unistd::read(0, &mut byte) {
+            Err(nix::errno::Errno::EINTR) => continue,
+            Ok(0) | Err(_) if line.is_empty() => return None,
+            Ok(0) | Err(_) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+        }
+    }
+    Some(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// `path` with `.` and `..` resolved without looking at the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
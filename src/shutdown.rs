@@ -0,0 +1,33 @@
+//! Leaving an interactive shell without losing state, however it ends:
+//! `exit`, end of input, `TMOUT`, a hangup or SIGTERM, or a crash. History is
+//! written as each line is entered, so what is left is the directory history
+//! and the terminal, its mode and its foreground process group.
+
+use std::panic;
+
+use crate::{Shell, signals, term};
+
+impl Shell {
+    /// Save what should outlive the session and restore the terminal.
+    pub(crate) fn shutdown(&mut self) {
+        if !self.options.posix {
+            self.save_dir_history();
+        }
+        if signals::hung_up().is_some() {
+            self.hang_up_jobs();
+        }
+        self.stop_job_control();
+        term::restore_mode();
+    }
+}
+
+/// Restore the terminal before a panic message is printed, so it can be read
+/// and the terminal is usable afterwards.
+pub(crate) fn install_panic_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        term::restore_mode();
+        default(info);
+        eprintln!("mash: internal error, please report this bug");
+    }));
+}
@@ -0,0 +1,218 @@
+//! Signal handling for the shell process itself.
+//!
+//! Signals with a `trap` set are only recorded by their handler; the shell
+//! runs the trap actions between commands, where it is safe to.
+
+use std::{str::FromStr, sync::{Mutex, PoisonError, atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering}}};
+
+use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+
+use crate::term;
+
+/// The signal called `name`, in any case and with or without `SIG`, or
+/// numbered `name`.
+pub fn parse(name: &str) -> Option<Signal> {
+    if let Ok(number) = name.parse::<i32>() {
+        return Signal::try_from(number).ok();
+    }
+    let upper = name.to_ascii_uppercase();
+    Signal::from_str(&format!("SIG{}", upper.strip_prefix("SIG").unwrap_or(&upper))).ok()
+}
+
+/// `signal`'s name without `SIG`, as `trap` and `kill` show it.
+pub fn name(signal: Signal) -> &'static str {
+    &signal.as_str()[3..]
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: nix::libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Catch SIGINT so that Ctrl-C interrupts the running command instead of
+/// killing the shell. Caught signals revert to their default action on exec,
+/// so children are still interrupted normally.
+pub fn catch_interrupt() {
+    let action = SigAction::new(SigHandler::Handler(on_sigint), SaFlags::SA_RESTART, SigSet::empty());
+    if let Err(e) = install(Signal::SIGINT, &action) {
+        tracing::error!("failed to install SIGINT handler: {}", e);
+    }
+}
+
+/// Whether Ctrl-C was pressed since the last [`clear_interrupt`].
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Act as if Ctrl-C was pressed, for a foreground job that was interrupted
+/// while it had the terminal and the shell did not see the signal.
+pub fn set_interrupted() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+extern "C" fn on_sigalrm(_: nix::libc::c_int) {}
+
+/// Catch SIGALRM, which does nothing but interrupt the read the shell is
+/// blocked in. The `TMOUT` timer uses it to end a read from the prompt.
+pub fn catch_alarm() {
+    let action = SigAction::new(SigHandler::Handler(on_sigalrm), SaFlags::empty(), SigSet::empty());
+    if let Err(e) = install(Signal::SIGALRM, &action) {
+        tracing::error!("failed to install SIGALRM handler: {}", e);
+    }
+}
+
+/// The signal that hung up the shell, or 0.
+static HANGUP: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_hangup(signal: nix::libc::c_int) {
+    HANGUP.store(signal, Ordering::SeqCst);
+    term::restore_mode();
+    // the terminal is gone: end the read the shell may be blocked in
+    unsafe {
+        let null = nix::libc::open(c"/dev/null".as_ptr(), nix::libc::O_RDONLY);
+        if null >= 0 {
+            nix::libc::dup2(null, 0);
+            nix::libc::close(null);
+        }
+    }
+}
+
+/// Catch SIGHUP and SIGTERM so that an interactive shell that is hung up or
+/// killed still shuts down properly, saving its state and restoring the
+/// terminal.
+pub fn catch_hangup() {
+    let action = SigAction::new(SigHandler::Handler(on_hangup), SaFlags::SA_RESTART, SigSet::empty());
+    for signal in [Signal::SIGHUP, Signal::SIGTERM] {
+        if let Err(e) = install(signal, &action) {
+            tracing::error!("failed to install {} handler: {}", signal, e);
+        }
+    }
+}
+
+/// The signal the shell was hung up or killed with, if any.
+pub fn hung_up() -> Option<i32> {
+    Some(HANGUP.load(Ordering::SeqCst)).filter(|&signal| signal != 0)
+}
+
+/// Let SIGPIPE kill the process again. Rust programs ignore it, and ignored
+/// signals stay ignored across exec, so without this a program writing to a
+/// pipe whose reader has gone would see errors instead of quietly exiting.
+pub fn default_pipe_signal() {
+    let _ = unsafe { nix::sys::signal::signal(Signal::SIGPIPE, SigHandler::SigDfl) };
+}
+
+/// The signals that stop a process from the terminal. A shell doing job
+/// control ignores them itself and lets them reach its jobs.
+const JOB_SIGNALS: [Signal; 3] = [Signal::SIGTSTP, Signal::SIGTTIN, Signal::SIGTTOU];
+
+/// The signals the shell ignores for itself, one bit per signal number.
+static IGNORED: AtomicU64 = AtomicU64::new(0);
+
+fn ignore(signal: Signal) {
+    let _ = install(signal, &SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty()));
+    IGNORED.fetch_or(1 << signal as i32, Ordering::SeqCst);
+}
+
+fn restore(signal: Signal) {
+    let _ = install(signal, &SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty()));
+    IGNORED.fetch_and(!(1 << signal as i32), Ordering::SeqCst);
+}
+
+/// Ignore Ctrl-Z and the stops for reading or writing the terminal from the
+/// background, so the shell can hand the terminal to its jobs.
+pub fn ignore_job_signals() {
+    JOB_SIGNALS.into_iter().for_each(ignore);
+}
+
+/// Undo [`ignore_job_signals`], when job control cannot be had after all.
+pub fn default_job_signals() {
+    JOB_SIGNALS.into_iter().for_each(restore);
+}
+
+/// Ignore Ctrl-\, which would otherwise make an interactive shell quit and
+/// dump core. It still reaches the command running in the foreground.
+pub fn ignore_quit() {
+    ignore(Signal::SIGQUIT);
+}
+
+/// In a process forked to run a command, give the signals the shell ignores
+/// for itself their default action again, since ignored signals stay
+/// ignored across exec. Safe to call between fork and exec.
+pub fn default_signals() {
+    // traps are not run by the child either
+    let ignored = IGNORED.load(Ordering::SeqCst) | TRAPPED.load(Ordering::SeqCst);
+    for signal in Signal::iterator().filter(|&signal| ignored & (1 << signal as i32) != 0) {
+        let _ = unsafe { nix::sys::signal::signal(signal, SigHandler::SigDfl) };
+    }
+}
+
+/// The signals with a trap action, one bit per signal number.
+static TRAPPED: AtomicU64 = AtomicU64::new(0);
+
+/// The trapped signals received whose action has not run yet.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// How each signal with a trap was handled before it, to go back to when the
+/// trap is removed.
+static SAVED: Mutex<Vec<(Signal, SigAction)>> = Mutex::new(Vec::new());
+
+extern "C" fn on_trapped(signal: nix::libc::c_int) {
+    PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+/// Install `action` for `signal`. While the signal has a trap, the action is
+/// kept for when it is removed instead.
+fn install(signal: Signal, action: &SigAction) -> nix::Result<()> {
+    let mut saved = SAVED.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, old)) = saved.iter_mut().find(|(s, _)| *s == signal) {
+        *old = *action;
+        return Ok(());
+    }
+    unsafe { sigaction(signal, action) }.map(drop)
+}
+
+/// Set a trap on `signal`: from now on it is only noted, for
+/// [`take_trapped`], or ignored altogether if `ignored` is set.
+pub fn trap(signal: Signal, ignored: bool) -> nix::Result<()> {
+    let handler = if ignored { SigHandler::SigIgn } else { SigHandler::Handler(on_trapped) };
+    let old = unsafe { sigaction(signal, &SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty())) }?;
+    let mut saved = SAVED.lock().unwrap_or_else(PoisonError::into_inner);
+    if !saved.iter().any(|(s, _)| *s == signal) {
+        saved.push((signal, old));
+    }
+    let bit = 1 << signal as i32;
+    match ignored {
+        // ignored signals stay ignored for the commands the shell runs
+        true => TRAPPED.fetch_and(!bit, Ordering::SeqCst),
+        false => TRAPPED.fetch_or(bit, Ordering::SeqCst),
+    };
+    Ok(())
+}
+
+/// Remove the trap on `signal`, handling it as the shell did before.
+pub fn untrap(signal: Signal) {
+    let mut saved = SAVED.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(i) = saved.iter().position(|(s, _)| *s == signal) {
+        let (_, old) = saved.remove(i);
+        let _ = unsafe { sigaction(signal, &old) };
+    }
+    TRAPPED.fetch_and(!(1 << signal as i32), Ordering::SeqCst);
+    PENDING.fetch_and(!(1 << signal as i32), Ordering::SeqCst);
+}
+
+/// The trapped signals received since the last call, in signal order.
+pub fn take_trapped() -> Vec<Signal> {
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    Signal::iterator().filter(|&signal| pending & (1 << signal as i32) != 0).collect()
+}
+
+/// A trapped signal that has arrived and whose action has not run yet.
+pub fn pending_trap() -> Option<Signal> {
+    let pending = PENDING.load(Ordering::SeqCst);
+    Signal::iterator().find(|&signal| pending & (1 << signal as i32) != 0)
+}
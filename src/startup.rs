@@ -0,0 +1,48 @@
+//! `mash --profile-startup`: time each phase of starting the shell and print
+//! a breakdown before the first prompt, to find what makes startup slow.
+
+use std::time::{Duration, Instant};
+
+use crate::Shell;
+
+pub(crate) struct StartupProfile {
+    start: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Shell {
+    /// Time the phases of startup from now on, and print them before the
+    /// first interactive prompt.
+    pub fn profile_startup(&mut self) {
+        self.startup_profile = Some(StartupProfile { start: Instant::now(), phases: Vec::new() });
+    }
+
+    /// Run `f`, recording how long it took as `phase` if startup is being
+    /// profiled.
+    pub(crate) fn timed<T>(&mut self, phase: &'static str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let start = Instant::now();
+        let result = f(self);
+        if let Some(profile) = &mut self.startup_profile {
+            profile.phases.push((phase, start.elapsed()));
+        }
+        result
+    }
+
+    /// Print the recorded phases, slowest first, and stop profiling.
+    pub(crate) fn print_startup_profile(&mut self) {
+        let Some(mut profile) = self.startup_profile.take() else {
+            return;
+        };
+        let total = profile.start.elapsed();
+        profile.phases.sort_by_key(|&(_, time)| std::cmp::Reverse(time));
+        eprintln!("{:>10}  {:>6}  phase", "time", "share");
+        for (phase, time) in profile.phases {
+            eprintln!("{:>7.3} ms  {:>5.1}%  {}", ms(time), 100.0 * ms(time) / ms(total).max(f64::EPSILON), phase);
+        }
+        eprintln!("{:>7.3} ms  {:>5.1}%  total", ms(total), 100.0);
+    }
+}
+
+fn ms(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,116 @@
+//! Command substitution: `$(commands)` and `` `commands` `` are replaced by
+//! what the commands write to standard output, less trailing newlines.
+//!
+//! The commands run in a child process, a copy of the shell, so nothing they
+//! change is seen afterwards; the shell reads their output through a pipe.
+//!
+//! Process substitution, `<(commands)` or `>(commands)`, instead leaves the
+//! shell's end of the pipe open and becomes its `/dev/fd` path, for the
+//! command it is an argument of to read the output from or write the input
+//! to. Once that command is done the pipe is closed and the commands are
+//! waited for.
+
+use std::{fs::File, io::{Read, Write}, os::fd::{AsRawFd, FromRawFd, OwnedFd}};
+
+use nix::{errno::Errno, sys::wait::{WaitStatus, waitpid}, unistd::{ForkResult, dup2, fork, pipe}};
+
+use crate::{Shell, ShellError, error, jobs::JobTable, parser, signals};
+
+impl Shell {
+    /// Run `source` and return its output. Its exit status is kept for a
+    /// command consisting only of assignments, whose status it becomes.
+    pub(crate) fn substitute(&mut self, source: &str) -> Result<String, ShellError> {
+        let list = parser::parse(source)?;
+        let _ = std::io::stdout().flush();
+        // SAFETY: both descriptors were just created and are owned by nothing else
+        let (read, write) = pipe().map(|(r, w)| unsafe { (OwnedFd::from_raw_fd(r), OwnedFd::from_raw_fd(w)) }).map_err(|e| ShellError::sys("pipe", e))?;
+        // SAFETY: the child only runs shell code before exiting
+        let child = match unsafe { fork() }.map_err(|e| ShellError::sys("fork", e))? {
+            ForkResult::Parent { child } => child,
+            ForkResult::Child => {
+                drop(read);
+                if let Err(e) = dup2(write.as_raw_fd(), 1) {
+                    error::warn(ShellError::sys("dup2", e));
+                    std::process::exit(1);
+                }
+                drop(write);
+                // the substitution stays in the shell's process group, and
+                // what it runs is not under job control
+                self.interactive = false;
+                self.jobs = JobTable::default();
+                signals::default_signals();
+                signals::default_pipe_signal();
+                self.run_list(&list);
+                let _ = std::io::stdout().flush();
+                std::process::exit(self.last_status);
+            }
+        };
+        drop(write);
+        let mut output = Vec::new();
+        let read = File::from(read).read_to_end(&mut output);
+        let status = loop {
+            match waitpid(child, None) {
+                Ok(WaitStatus::Exited(_, status)) => break status,
+                Ok(WaitStatus::Signaled(_, signal, _)) => break 128 + signal as i32,
+                Ok(_) | Err(Errno::EINTR) => continue,
+                Err(e) => return Err(ShellError::sys("waitpid", e)),
+            }
+        };
+        read.map_err(|e| ShellError::io("command substitution", e))?;
+        self.substitution_status = Some(status);
+        self.last_status = status;
+        let mut output = String::from_utf8_lossy(&output).into_owned();
+        output.truncate(output.trim_end_matches('\n').len());
+        Ok(output)
+    }
+
+    /// Start `source` for `<(source)`, or `>(source)` if `input`, returning
+    /// the path of the shell's end of the pipe to it.
+    pub(crate) fn substitute_process(&mut self, source: &str, input: bool) -> Result<String, ShellError> {
+        self.extension("process substitution")?;
+        let list = parser::parse(source)?;
+        let _ = std::io::stdout().flush();
+        // SAFETY: both descriptors were just created and are owned by nothing else
+        let (read, write) = pipe().map(|(r, w)| unsafe { (OwnedFd::from_raw_fd(r), OwnedFd::from_raw_fd(w)) }).map_err(|e| ShellError::sys("pipe", e))?;
+        let (kept, given, fd) = if input { (write, read, 0) } else { (read, write, 1) };
+        // SAFETY: the child only runs shell code before exiting
+        let child = match unsafe { fork() }.map_err(|e| ShellError::sys("fork", e))? {
+            ForkResult::Parent { child } => child,
+            ForkResult::Child => {
+                // the ends of other substitutions would keep them from
+                // seeing the end of their input
+                drop(kept);
+                self.process_substitutions.clear();
+                if let Err(e) = dup2(given.as_raw_fd(), fd) {
+                    error::warn(ShellError::sys("dup2", e));
+                    std::process::exit(1);
+                }
+                drop(given);
+                self.interactive = false;
+                self.jobs = JobTable::default();
+                signals::default_signals();
+                signals::default_pipe_signal();
+                self.run_list(&list);
+                let _ = std::io::stdout().flush();
+                std::process::exit(self.last_status);
+            }
+        };
+        drop(given);
+        let path = format!("/dev/fd/{}", kept.as_raw_fd());
+        self.process_substitutions.push((kept, child));
+        Ok(path)
+    }
+
+    /// Close the pipes of the process substitutions after the first `keep`
+    /// and wait for their commands, which see the end of their input or
+    /// `SIGPIPE`.
+    pub(crate) fn finish_process_substitutions(&mut self, keep: usize) {
+        if self.process_substitutions.len() <= keep {
+            return;
+        }
+        let children: Vec<_> = self.process_substitutions.drain(keep..).map(|(_, child)| child).collect();
+        for child in children {
+            while let Ok(WaitStatus::StillAlive | WaitStatus::Stopped(..) | WaitStatus::Continued(_)) | Err(Errno::EINTR) = waitpid(child, None) {}
+        }
+    }
+}
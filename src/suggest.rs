@@ -0,0 +1,126 @@
+//! "Did you mean" suggestions for commands that were not found, offered by
+//! interactive shells from the aliases, functions, builtins and programs in
+//! `PATH` with the closest names.
+//!
+//! With `CORRECT` set, the shell asks instead, and runs the suggestion if the
+//! answer is yes. `cd` to a missing directory is corrected the same way from
+//! the directories next to it.
+
+use std::{io::{IsTerminal, Write}, path::Path};
+
+use crate::{Shell, complete};
+
+impl Shell {
+    /// The known command closest to `name`, if any is close enough to be a
+    /// likely typo.
+    pub(crate) fn suggest_command(&self, name: &str) -> Option<String> {
+        if name.contains('/') {
+            return None;
+        }
+        let path = self.var("PATH").unwrap_or_default();
+        let builtins = self.builtins.iter().map(|b| b.name().to_string()).filter(|b| self.builtin_enabled(b));
+        closest(name, self.aliases.keys().chain(self.functions.keys()).cloned().chain(builtins).chain(complete::programs("", &path)).collect())
+    }
+
+    /// Whether mistyped commands and directories are to be corrected: when
+    /// `CORRECT` is set in an interactive shell reading a terminal.
+    pub(crate) fn correcting(&self) -> bool {
+        self.interactive && self.var("CORRECT").is_some_and(|v| !v.is_empty()) && std::io::stdin().is_terminal()
+    }
+
+    /// Offer the command closest to `name`, which was not found, returning
+    /// it if the user takes it. Aliases are not offered, having been
+    /// expanded already.
+    pub(crate) fn correct_command(&self, name: &str) -> Option<String> {
+        if !self.correcting() {
+            return None;
+        }
+        self.suggest_command(name).filter(|c| !self.aliases.contains_key(c)).filter(|c| confirm(c))
+    }
+
+    /// Offer the directory closest to `dir`, which does not exist, from
+    /// those next to it, returning it if the user takes it.
+    pub(crate) fn correct_dir(&self, dir: &str) -> Option<String> {
+        if !self.correcting() || self.path.join(dir).exists() {
+            return None;
+        }
+        let (parent, name) = match dir.trim_end_matches('/').rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((parent, name)) => (parent, name),
+            None => ("", dir.trim_end_matches('/')),
+        };
+        let entries = std::fs::read_dir(self.path.join(if parent.is_empty() { "." } else { parent })).ok()?;
+        let dirs = entries.flatten().filter(|e| e.path().is_dir()).filter_map(|e| e.file_name().into_string().ok());
+        let found = closest(name, dirs.filter(|d| !d.starts_with('.') || name.starts_with('.')).collect())?;
+        let corrected = match parent {
+            "" => found,
+            parent => Path::new(parent).join(found).display().to_string(),
+        };
+        confirm(&corrected).then_some(corrected)
+    }
+}
+
+/// The candidate closest to `name`, if any is close enough to be a likely
+/// typo: one edit away for short names, two for longer ones. Ties go to the
+/// first in sorted order.
+fn closest(name: &str, mut candidates: Vec<String>) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    let limit = if name.chars().count() <= 4 { 1 } else { 2 };
+    candidates.sort();
+    candidates.into_iter().map(|c| (distance(name, &c), c)).filter(|(d, _)| *d <= limit).min_by_key(|(d, _)| *d).map(|(_, c)| c)
+}
+
+/// Ask on the terminal whether `suggestion` was meant, reading the answer a
+/// byte at a time so no typeahead after it is taken.
+fn confirm(suggestion: &str) -> bool {
+    eprint!("mash: did you mean '{}'? [y/N] ", suggestion);
+    let _ = std::io::stderr().flush();
+    let mut answer = Vec::new();
+    let mut byte = [0u8];
+    while let Ok(1) = nix::unistd::read(0, &mut byte) {
+        if byte[0] == b'\n' {
+            break;
+        }
+        answer.push(byte[0]);
+    }
+    matches!(String::from_utf8_lossy(&answer).trim(), "y" | "Y" | "yes")
+}
+
+/// The number of insertions, deletions, substitutions and swaps of adjacent
+/// characters that turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // rows for the prefixes of `a` two, one and zero characters shorter
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (previous[j] + 1).min(row[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::distance;
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one() {
+        assert_eq!(distance("gti", "git"), 1);
+        assert_eq!(distance("sl", "ls"), 1);
+        assert_eq!(distance("grpe", "grep"), 1);
+        assert_eq!(distance("carg", "cargo"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "ls"), 2);
+    }
+}
@@ -0,0 +1,82 @@
+//! Terminal integration escape sequences: OSC 7 working-directory reporting
+//! and OSC 133 semantic prompt marks.
+
+use std::{io::{IsTerminal, Write}, os::unix::ffi::OsStrExt, path::Path, sync::OnceLock};
+
+use nix::{libc, sys::termios, unistd::gethostname};
+
+/// OSC 133;B, marks the end of the prompt and the start of user input. This
+/// is appended to the prompt by the line editor's highlighter so it does not
+/// count towards the prompt width.
+pub const PROMPT_END: &str = "\x1b]133;B\x07";
+
+/// The terminal's mode when the shell started.
+static MODE: OnceLock<libc::termios> = OnceLock::new();
+
+/// Remember the terminal's mode, for [`restore_mode`].
+pub fn save_mode() {
+    if let Ok(mode) = termios::tcgetattr(0) {
+        let _ = MODE.set(mode.into());
+    }
+}
+
+/// Put the terminal back the way it was at startup, in case the shell is
+/// leaving while the line editor has it in raw mode. Safe to call from a
+/// signal handler.
+pub fn restore_mode() {
+    let Some(mode) = MODE.get() else {
+        return;
+    };
+    const BRACKETED_PASTE_OFF: &[u8] = b"\x1b[?2004l";
+    unsafe {
+        libc::tcsetattr(0, libc::TCSANOW, mode);
+        if libc::isatty(1) == 1 {
+            libc::write(1, BRACKETED_PASTE_OFF.as_ptr().cast(), BRACKETED_PASTE_OFF.len());
+        }
+    }
+}
+
+fn emit(seq: &str) {
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return;
+    }
+    let _ = stdout.write_all(seq.as_bytes());
+    let _ = stdout.flush();
+}
+
+/// Tell the terminal emulator about the working directory (OSC 7) so new
+/// tabs and splits can open in the same place.
+pub fn report_cwd(path: &Path) {
+    let host = gethostname().map(|h| h.to_string_lossy().into_owned()).unwrap_or_default();
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    emit(&format!("\x1b]7;file://{}{}\x1b\\", host, percent_encode(path.as_os_str().as_bytes())));
+}
+
+/// OSC 133;A, emitted right before the prompt is drawn.
+pub fn prompt_start() {
+    emit("\x1b]133;A\x07");
+}
+
+/// OSC 133;C, emitted once the command line is accepted and output begins.
+pub fn command_start() {
+    emit("\x1b]133;C\x07");
+}
+
+/// OSC 133;D, emitted when the command finishes, carrying its exit status.
+pub fn command_end(status: i32) {
+    emit(&format!("\x1b]133;D;{}\x07", status));
+}
+
+/// Percent-encode a path for use in a `file://` URL, leaving `/` intact.
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
@@ -0,0 +1,87 @@
+//! `TMOUT`: log out of an interactive shell left idle at the prompt for that
+//! many seconds, as hardened environments require.
+//!
+//! The line editor cannot be told to give up on a read, so a timer thread
+//! does it: shortly before the time is up it prints a warning above the
+//! prompt, and when it runs out it puts `/dev/null` on standard input and
+//! wakes the main thread, which then reads end of input. Standard input and
+//! the terminal mode are put back afterwards.
+
+use std::{fs::File, os::fd::{AsRawFd, OwnedFd, FromRawFd}, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}, mpsc::{self, RecvTimeoutError}}, thread::JoinHandle, time::Duration};
+
+use nix::{sys::{pthread::{pthread_kill, pthread_self}, signal::Signal, termios::{self, SetArg, Termios}}, unistd::{dup, dup2}};
+use rustyline::ExternalPrinter;
+
+use crate::Shell;
+
+/// Prints messages above the prompt while the line editor is reading.
+pub(crate) type Printer = Arc<Mutex<Box<dyn ExternalPrinter + Send>>>;
+
+/// The warning is given this long before logging out, or halfway through
+/// for shorter timeouts.
+const WARNING: u64 = 10;
+
+pub(crate) struct IdleTimer {
+    cancel: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+    expired: Arc<AtomicBool>,
+    /// The real standard input and its terminal mode before reading.
+    stdin: OwnedFd,
+    mode: Option<Termios>,
+}
+
+impl Shell {
+    /// The idle timeout set by `TMOUT`, if it is a positive number of seconds.
+    pub(crate) fn idle_timeout(&self) -> Option<u64> {
+        self.var("TMOUT")?.trim().parse().ok().filter(|&seconds| seconds > 0)
+    }
+}
+
+impl IdleTimer {
+    /// Start timing a read from the prompt that should end after `seconds`.
+    pub(crate) fn start(seconds: u64, printer: Option<Printer>) -> Option<Self> {
+        let stdin = dup(0).ok()?;
+        // SAFETY: `dup` just returned this descriptor and nothing else owns it
+        let stdin = unsafe { OwnedFd::from_raw_fd(stdin) };
+        let mode = termios::tcgetattr(0).ok();
+        let expired = Arc::new(AtomicBool::new(false));
+        let (cancel, cancelled) = mpsc::channel();
+        let main = pthread_self();
+        let flag = Arc::clone(&expired);
+        let thread = std::thread::spawn(move || {
+            let warning = WARNING.min(seconds / 2);
+            if warning > 0 {
+                if cancelled.recv_timeout(Duration::from_secs(seconds - warning)) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+                if let Some(printer) = printer {
+                    let _ = printer.lock().unwrap().print(format!("mash: auto-logout in {} seconds\n", warning));
+                }
+            }
+            let remaining = if warning > 0 { warning } else { seconds };
+            if cancelled.recv_timeout(Duration::from_secs(remaining)) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            flag.store(true, Ordering::SeqCst);
+            if let Ok(null) = File::open("/dev/null") {
+                let _ = dup2(null.as_raw_fd(), 0);
+            }
+            let _ = pthread_kill(main, Signal::SIGALRM);
+        });
+        Some(Self { cancel, thread, expired, stdin, mode })
+    }
+
+    /// Stop the timer once the read is over, returning whether it ran out.
+    pub(crate) fn stop(self) -> bool {
+        let _ = self.cancel.send(());
+        let _ = self.thread.join();
+        let expired = self.expired.load(Ordering::SeqCst);
+        if expired {
+            let _ = dup2(self.stdin.as_raw_fd(), 0);
+            if let Some(mode) = &self.mode {
+                let _ = termios::tcsetattr(0, SetArg::TCSADRAIN, mode);
+            }
+        }
+        expired
+    }
+}
@@ -0,0 +1,90 @@
+//! How long commands take: `time pipeline` reports it on standard error,
+//! and so does an interactive shell for each command line that runs for at
+//! least `REPORTTIME` seconds, as in zsh.
+//!
+//! The real time is measured from start to finish. The user and system CPU
+//! times are those of the shell and of the children it waited for meanwhile,
+//! as `getrusage` counts them.
+
+use std::{io::Write, time::{Duration, Instant}};
+
+use nix::sys::resource::{UsageWho, getrusage};
+
+use crate::{Shell, parser::Node};
+
+/// The time spent since a [`Clock`] was started.
+pub(crate) struct Times {
+    pub(crate) real: Duration,
+    pub(crate) user: Duration,
+    pub(crate) system: Duration,
+}
+
+pub(crate) struct Clock {
+    start: Instant,
+    user: Duration,
+    system: Duration,
+}
+
+impl Clock {
+    pub(crate) fn start() -> Clock {
+        let (user, system) = cpu_times();
+        Clock { start: Instant::now(), user, system }
+    }
+
+    pub(crate) fn stop(&self) -> Times {
+        let (user, system) = cpu_times();
+        Times { real: self.start.elapsed(), user: user.saturating_sub(self.user), system: system.saturating_sub(self.system) }
+    }
+}
+
+/// The user and system time used by the shell and its waited-for children.
+fn cpu_times() -> (Duration, Duration) {
+    let mut times = (Duration::ZERO, Duration::ZERO);
+    for who in [UsageWho::RUSAGE_SELF, UsageWho::RUSAGE_CHILDREN] {
+        if let Ok(usage) = getrusage(who) {
+            times.0 += duration(usage.user_time());
+            times.1 += duration(usage.system_time());
+        }
+    }
+    times
+}
+
+fn duration(time: nix::sys::time::TimeVal) -> Duration {
+    Duration::from_secs(time.tv_sec().max(0) as u64) + Duration::from_micros(time.tv_usec().max(0) as u64)
+}
+
+/// `1m2.345s`, as bash's `time` prints a duration.
+fn minutes(time: Duration) -> String {
+    let seconds = time.as_secs_f64();
+    format!("{}m{:.3}s", (seconds / 60.0).floor(), seconds % 60.0)
+}
+
+impl Shell {
+    /// Run `node` and print how long it took, as `real`, `user` and `sys`
+    /// lines. The status is the node's.
+    pub(crate) fn run_timed(&mut self, node: &Node, posix: bool) {
+        let clock = Clock::start();
+        self.run_node(node);
+        let times = clock.stop();
+        let _ = std::io::stdout().flush();
+        if posix {
+            eprintln!("real {:.2}\nuser {:.2}\nsys {:.2}", times.real.as_secs_f64(), times.user.as_secs_f64(), times.system.as_secs_f64());
+        } else {
+            eprintln!("\nreal\t{}\nuser\t{}\nsys\t{}", minutes(times.real), minutes(times.user), minutes(times.system));
+        }
+    }
+
+    /// Report the times of a command line typed at the prompt if it ran for
+    /// at least `REPORTTIME` seconds.
+    pub(crate) fn report_time(&self, command: &str, times: &Times) {
+        let Some(limit) = self.var("REPORTTIME").and_then(|limit| limit.parse::<f64>().ok()) else {
+            return;
+        };
+        let real = times.real.as_secs_f64();
+        if real < limit {
+            return;
+        }
+        let cpu = (times.user + times.system).as_secs_f64() / real.max(f64::EPSILON) * 100.0;
+        eprintln!("{}  {:.2}s user {:.2}s system {:.0}% cpu {:.3} total", command, times.user.as_secs_f64(), times.system.as_secs_f64(), cpu, real);
+    }
+}
@@ -0,0 +1,125 @@
+//! `trap`: commands the shell runs when certain events happen. `DEBUG` runs
+//! before each simple command and `ERR` after one fails. Like other shells,
+//! neither fires inside functions.
+//!
+//! A trap can also be set on a signal, by name with or without `SIG` or by
+//! number, and on `EXIT` (or 0), which runs as the shell leaves. A signal is
+//! only noted when it arrives; its action runs once the command the shell
+//! is running has finished.
+
+use std::io::Write;
+
+use crate::{Shell, ShellError, exec::quote, signals};
+
+const EVENTS: &[&str] = &["DEBUG", "ERR", "EXIT"];
+
+/// The name `event` is kept under, or `None` if it is no event or signal.
+/// Signals are named without `SIG`.
+fn event_name(event: &str) -> Option<String> {
+    let upper = event.to_ascii_uppercase();
+    if event == "0" {
+        return Some(String::from("EXIT"));
+    }
+    if EVENTS.contains(&upper.as_str()) {
+        return Some(upper);
+    }
+    signals::parse(event).map(|signal| signals::name(signal).to_string())
+}
+
+impl Shell {
+    /// `trap [action event ...]`. An action of `-` resets the events and an
+    /// empty one ignores them; without arguments, or with `-p`, the current
+    /// traps are listed.
+    pub(crate) fn trap(&mut self, args: &[String], out: &mut dyn Write) -> Result<(), ShellError> {
+        let (action, events) = match args {
+            [flag] if flag != "-p" => ("-", args), // a lone event name resets it
+            [action, events @ ..] if !events.is_empty() => (action.as_str(), events),
+            _ => {
+                self.print_traps(out);
+                return Ok(());
+            }
+        };
+        for event in events {
+            let Some(name) = event_name(event) else {
+                return Err(ShellError::Failed(format!("trap: {}: invalid signal specification", event)));
+            };
+            if let Some(signal) = signals::parse(&name) {
+                match action {
+                    "-" => signals::untrap(signal),
+                    _ => signals::trap(signal, action.is_empty()).map_err(|e| ShellError::sys(format!("trap: {}", event), e))?,
+                }
+            }
+            if action == "-" {
+                self.traps.remove(&name);
+            } else {
+                self.traps.insert(name, action.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn print_traps(&self, out: &mut dyn Write) {
+        let mut traps: Vec<_> = self.traps.iter().collect();
+        traps.sort();
+        for (event, action) in traps {
+            let _ = writeln!(out, "trap -- {} {}", quote(action), event);
+        }
+    }
+
+    /// Run the action set for `event`, if any. `$?` is left as it was, and
+    /// commands run by the action do not fire traps themselves.
+    pub(crate) fn run_trap(&mut self, event: &str) {
+        if self.in_trap || (self.function_depth > 0 && matches!(event, "DEBUG" | "ERR")) {
+            return;
+        }
+        let Some(action) = self.traps.get(event).filter(|a| !a.is_empty()).cloned() else {
+            return;
+        };
+        let status = self.last_status;
+        self.in_trap = true;
+        // the action's commands report the line of the command that fired it
+        let lineno = self.lineno;
+        if let Err(e) = self.eval_at(&action, lineno) {
+            self.error(e);
+        }
+        self.in_trap = false;
+        // unless the action left the shell, with a status of its own
+        if !self.exiting {
+            self.last_status = status;
+        }
+        self.lineno = lineno;
+    }
+
+    /// Run the actions of the trapped signals that arrived since this was
+    /// last called. A signal arriving during an action waits for the next
+    /// call.
+    pub(crate) fn run_signal_traps(&mut self) {
+        if self.in_trap {
+            return;
+        }
+        for signal in signals::take_trapped() {
+            self.run_trap(signals::name(signal));
+        }
+    }
+
+    /// Run the `EXIT` trap, if one is set, as the shell is about to leave.
+    /// The action runs once, and an `exit` in it sets the status the shell
+    /// leaves with.
+    pub fn run_exit_trap(&mut self) {
+        let Some(action) = self.traps.remove("EXIT").filter(|a| !a.is_empty()) else {
+            return;
+        };
+        self.run_signal_traps();
+        let (status, exiting) = (self.last_status, self.exiting);
+        self.exiting = false;
+        self.in_trap = true;
+        if let Err(e) = self.eval_at(&action, self.lineno) {
+            self.error(e);
+        }
+        self.in_trap = false;
+        if !self.exiting {
+            self.last_status = status;
+        }
+        self.exiting |= exiting;
+    }
+}
@@ -0,0 +1,596 @@
+//! The shell variable table. A variable holds a string, an indexed array or
+//! an associative array; using an array as a plain string refers to its
+//! element 0.
+//!
+//! Exported variables live in the process environment instead, which
+//! commands the shell runs inherit. Variables the shell started with are
+//! exported, and assigning to one changes what commands see.
+
+use std::{borrow::Cow, cell::Cell, collections::BTreeMap, io::Write, rc::Rc, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+use crate::{Shell, ShellError, exec::Frame, parser::{AssignValue, Assignment, Function, Node, is_name}};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Scalar(String),
+    /// Indexed arrays may be sparse.
+    Indexed(BTreeMap<usize, String>),
+    Assoc(BTreeMap<String, String>),
+}
+
+impl Value {
+    /// The variable used as a string.
+    pub fn scalar(&self) -> Option<&str> {
+        match self {
+            Value::Scalar(s) => Some(s),
+            Value::Indexed(a) => a.get(&0).map(String::as_str),
+            Value::Assoc(a) => a.get("0").map(String::as_str),
+        }
+    }
+
+    /// Every element in order; a string is an array of one.
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(s) => vec![s.clone()],
+            Value::Indexed(a) => a.values().cloned().collect(),
+            Value::Assoc(a) => a.values().cloned().collect(),
+        }
+    }
+
+    /// The indices or keys of the elements, as listed by `${!a[@]}`.
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(_) => vec![String::from("0")],
+            Value::Indexed(a) => a.keys().map(usize::to_string).collect(),
+            Value::Assoc(a) => a.keys().cloned().collect(),
+        }
+    }
+
+    /// Render the value the way `declare -p` shows it.
+    fn declaration(&self, name: &str) -> String {
+        let elements = |pairs: Vec<(String, &String)>| pairs.into_iter().map(|(k, v)| format!("[{}]={}", k, quote(v))).collect::<Vec<_>>().join(" ");
+        match self {
+            Value::Scalar(s) => format!("declare -- {}={}", name, quote(s)),
+            Value::Indexed(a) => format!("declare -a {}=({})", name, elements(a.iter().map(|(k, v)| (k.to_string(), v)).collect())),
+            Value::Assoc(a) => format!("declare -A {}=({})", name, elements(a.iter().map(|(k, v)| (quote(k), v)).collect())),
+        }
+    }
+}
+
+/// State behind the variables whose values are computed when they are read:
+/// `RANDOM`, `SECONDS`, `LINENO` and `EPOCHSECONDS`.
+pub struct Dynamic {
+    /// `SECONDS` counts up from `base` at `since`.
+    since: Instant,
+    base: u64,
+    random: Cell<u64>,
+}
+
+impl Default for Dynamic {
+    fn default() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Self { since: Instant::now(), base: 0, random: Cell::new(nanos ^ u64::from(std::process::id()) << 32) }
+    }
+}
+
+impl Dynamic {
+    /// The next value of `RANDOM`, between 0 and 32767.
+    fn random(&self) -> u64 {
+        // xorshift64*, which is plenty for jitter and temporary names
+        let mut x = self.random.get().max(1);
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.random.set(x);
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) % 32768
+    }
+}
+
+/// Double-quote `s` so that it reads back as the same word.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl Shell {
+    /// Look up a variable as a string: shell variables first, then the
+    /// environment.
+    pub(crate) fn var(&self, name: &str) -> Option<String> {
+        match self.value(name) {
+            Some(value) => value.scalar().map(String::from),
+            None => std::env::var(name).ok(),
+        }
+    }
+
+    /// Look up a shell variable, computing the dynamic ones. `FUNCNAME`,
+    /// `MASH_SOURCE` and `MASH_LINENO` describe the call stack innermost
+    /// first: element 0 is the running function, the file it comes from and
+    /// the current line, element 1 its caller, and so on.
+    pub(crate) fn value(&self, name: &str) -> Option<Cow<'_, Value>> {
+        let scalar = |s: String| Some(Cow::Owned(Value::Scalar(s)));
+        let stack = |current: String, caller: fn(&Frame) -> String| {
+            let values = std::iter::once(current).chain(self.frames.iter().rev().map(caller));
+            Some(Cow::Owned(Value::Indexed(values.enumerate().collect())))
+        };
+        match name {
+            "RANDOM" => scalar(self.dynamic.random().to_string()),
+            "SECONDS" => scalar((self.dynamic.base + self.dynamic.since.elapsed().as_secs()).to_string()),
+            "LINENO" => scalar(self.lineno.to_string()),
+            "EPOCHSECONDS" => scalar(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()).to_string()),
+            "FUNCNAME" => {
+                // the outermost entry is `main`, so the top level has nothing
+                let names: Vec<_> = self.frames.iter().rev().map(|f| f.name.clone()).chain((!self.frames.is_empty()).then(|| String::from("main"))).collect();
+                (!names.is_empty()).then(|| Cow::Owned(Value::Indexed(names.into_iter().enumerate().collect())))
+            }
+            "MASH_SOURCE" => stack(self.file.clone(), |f| f.file.clone()),
+            "MASH_LINENO" => stack(self.lineno.to_string(), |f| f.line.to_string()),
+            _ => self.vars.get(name).map(Cow::Borrowed),
+        }
+    }
+
+    /// Set a variable to a string. For an array this sets element 0.
+    /// Assigning to `RANDOM` seeds it and to `SECONDS` restarts the count
+    /// from the value given.
+    pub(crate) fn set_var(&mut self, name: &str, value: String) {
+        match name {
+            "RANDOM" => return self.dynamic.random.set(value.trim().parse().unwrap_or(0)),
+            "SECONDS" => {
+                self.dynamic = Dynamic { since: Instant::now(), base: value.trim().parse().unwrap_or(0), ..std::mem::take(&mut self.dynamic) };
+                return;
+            }
+            "LINENO" | "EPOCHSECONDS" => return,
+            _ => {}
+        }
+        match self.vars.get_mut(name) {
+            Some(Value::Indexed(a)) => {
+                a.insert(0, value);
+            }
+            Some(Value::Assoc(a)) => {
+                a.insert(String::from("0"), value);
+            }
+            None if std::env::var_os(name).is_some() => std::env::set_var(name, value),
+            _ => {
+                self.vars.insert(name.to_string(), Value::Scalar(value));
+            }
+        }
+    }
+
+    /// `export [-n] [name[=value] ...]`: move variables into the environment,
+    /// or with `-n` back out of it. Without names, or with `-p`, the exported
+    /// variables are listed.
+    pub(crate) fn export(&mut self, args: &[String], out: &mut dyn Write) -> Result<(), ShellError> {
+        let mut unexport = false;
+        let mut names = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-n" if names.is_empty() => unexport = true,
+                "-p" if names.is_empty() => {}
+                flag if names.is_empty() && flag.len() > 1 && flag.starts_with('-') => return Err(ShellError::Usage(format!("export: {}: invalid option", flag))),
+                _ => names.push(arg),
+            }
+        }
+        if names.is_empty() {
+            let mut all: Vec<_> = std::env::vars_os().map(|(name, value)| (name.to_string_lossy().into_owned(), value.to_string_lossy().into_owned())).collect();
+            all.sort();
+            for (name, value) in all {
+                let _ = writeln!(out, "export {}={}", name, quote(&value));
+            }
+            return Ok(());
+        }
+        let mut status = Ok(());
+        for arg in names {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            if !is_name(name) {
+                status = Err(ShellError::Failed(format!("export: `{}': not a valid identifier", arg)));
+                continue;
+            }
+            if value.is_some() || unexport {
+                if let Err(e) = self.restrict_variable(name) {
+                    status = Err(e);
+                    continue;
+                }
+            }
+            if unexport {
+                if let Some(exported) = std::env::var_os(name) {
+                    std::env::remove_var(name);
+                    self.vars.entry(name.to_string()).or_insert_with(|| Value::Scalar(exported.to_string_lossy().into_owned()));
+                }
+                if let Some(value) = value {
+                    self.set_var(name, value);
+                }
+                continue;
+            }
+            if matches!(self.vars.get(name), Some(Value::Indexed(_) | Value::Assoc(_))) {
+                status = Err(ShellError::Failed(format!("export: {}: cannot export an array", name)));
+                continue;
+            }
+            // a name that is not set has nothing to export yet
+            let Some(value) = value.or_else(|| self.var(name)) else {
+                continue;
+            };
+            self.vars.remove(name);
+            std::env::set_var(name, value);
+        }
+        status
+    }
+
+    /// `unset [-v] name ...`: remove variables, from the environment too.
+    pub(crate) fn unset(&mut self, args: &[String]) -> Result<(), ShellError> {
+        // `-f` removes functions and `-v` variables; without either, a
+        // function goes if there is no variable of that name
+        let mut only = None;
+        let mut names = args;
+        while let Some(flags) = names.first().and_then(|arg| arg.strip_prefix('-')).filter(|f| !f.is_empty()) {
+            for flag in flags.chars() {
+                match flag {
+                    'f' | 'v' => only = Some(flag),
+                    _ => return Err(ShellError::Usage(format!("unset: -{}: invalid option", flag))),
+                }
+            }
+            names = &names[1..];
+        }
+        let mut status = Ok(());
+        for name in names {
+            if only == Some('f') {
+                self.functions.remove(name.as_str());
+                continue;
+            }
+            if !is_name(name) {
+                status = Err(ShellError::Failed(format!("unset: `{}': not a valid identifier", name)));
+                continue;
+            }
+            if let Err(e) = self.restrict_variable(name) {
+                status = Err(e);
+                continue;
+            }
+            self.declared.remove(name.as_str());
+            let was_set = self.vars.remove(name.as_str()).is_some() || std::env::var_os(name).is_some();
+            std::env::remove_var(name);
+            if !was_set && only.is_none() {
+                self.functions.remove(name.as_str());
+            }
+        }
+        status
+    }
+
+    /// List every variable, shell and exported, as `set` does without
+    /// arguments.
+    pub(crate) fn print_vars(&self, out: &mut dyn Write) {
+        let mut all: BTreeMap<String, String> = std::env::vars_os().map(|(name, value)| (name.to_string_lossy().into_owned(), quote(&value.to_string_lossy()))).collect();
+        for (name, value) in &self.vars {
+            let value = match value {
+                Value::Scalar(s) => quote(s),
+                // the declaration without `declare -a name=` in front
+                array => array.declaration(name).split_once('=').map_or(String::new(), |(_, v)| v.to_string()),
+            };
+            all.insert(name.clone(), value);
+        }
+        for (name, value) in all {
+            let _ = writeln!(out, "{}={}", name, value);
+        }
+    }
+
+    /// Resolve an array subscript to an index. Negative subscripts count back
+    /// from the end of the array.
+    pub(crate) fn index(&self, name: &str, subscript: &str) -> Result<usize, ShellError> {
+        let subscript = subscript.trim();
+        let n = match subscript.parse::<i64>() {
+            Ok(n) => n,
+            // a bare variable name evaluates to its value, as in arithmetic
+            Err(_) if is_name(subscript) => self.var(subscript).and_then(|v| v.trim().parse().ok()).unwrap_or(0),
+            Err(_) if subscript.is_empty() => return Err(ShellError::Failed(format!("{}: bad array subscript", name))),
+            Err(_) => return Err(ShellError::Failed(format!("{}: bad array subscript", subscript))),
+        };
+        if n >= 0 {
+            return Ok(n as usize);
+        }
+        let len = match self.value(name).as_deref() {
+            Some(Value::Indexed(a)) => a.keys().next_back().map_or(0, |k| k + 1),
+            Some(Value::Scalar(_)) => 1,
+            _ => 0,
+        };
+        usize::try_from(len as i64 + n).map_err(|_| ShellError::Failed(format!("{}[{}]: bad array subscript", name, subscript)))
+    }
+
+    /// Look up one element of an array, reading the subscript as a key for
+    /// associative arrays and as an index otherwise.
+    pub(crate) fn element(&self, name: &str, subscript: &str) -> Option<String> {
+        match self.value(name).as_deref() {
+            Some(Value::Assoc(a)) => a.get(subscript).cloned(),
+            Some(Value::Indexed(a)) => a.get(&self.index(name, subscript).ok()?).cloned(),
+            _ => (self.index(name, subscript).ok()? == 0).then(|| self.var(name)).flatten(),
+        }
+    }
+
+    /// Set one element of an array, turning a string variable into an
+    /// indexed array first.
+    fn set_element(&mut self, name: &str, subscript: &str, value: String) -> Result<(), ShellError> {
+        if let Some(Value::Assoc(a)) = self.vars.get_mut(name) {
+            a.insert(subscript.to_string(), value);
+            return Ok(());
+        }
+        let index = self.index(name, subscript)?;
+        match self.vars.get_mut(name) {
+            Some(Value::Indexed(a)) => {
+                a.insert(index, value);
+            }
+            _ => {
+                let mut array = BTreeMap::new();
+                if let Some(old) = self.var(name) {
+                    array.insert(0, old);
+                }
+                array.insert(index, value);
+                self.vars.insert(name.to_string(), Value::Indexed(array));
+            }
+        }
+        Ok(())
+    }
+
+    /// Perform an assignment word such as `a=x`, `a[1]=x` or `a=(x y z)`.
+    pub(crate) fn assign(&mut self, assignment: &Assignment) -> Result<(), ShellError> {
+        self.restrict_variable(&assignment.name)?;
+        if assignment.index.is_some() || matches!(assignment.value, AssignValue::Array(_)) {
+            self.extension("arrays")?;
+        }
+        match (&assignment.index, &assignment.value) {
+            (None, AssignValue::Scalar(value)) => {
+                let value = self.expand_word(value)?;
+                self.set_var(&assignment.name, value);
+            }
+            (Some(subscript), AssignValue::Scalar(value)) => {
+                let subscript = self.expand_word(subscript)?;
+                let value = self.expand_word(value)?;
+                self.set_element(&assignment.name, &subscript, value)?;
+            }
+            (Some(_), AssignValue::Array(_)) => return Err(ShellError::Failed(format!("{}: cannot assign list to array member", assignment.name))),
+            (None, AssignValue::Array(words)) => {
+                let value = self.compound_value(&assignment.name, words)?;
+                self.vars.insert(assignment.name.clone(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the array assigned by `name=(words)`. Words of the form
+    /// `[key]=value` set that element; other words are appended in order.
+    fn compound_value(&mut self, name: &str, words: &[String]) -> Result<Value, ShellError> {
+        let assoc = matches!(self.vars.get(name), Some(Value::Assoc(_)));
+        let mut indexed = BTreeMap::new();
+        let mut keyed = BTreeMap::new();
+        let mut next = 0;
+        for word in words {
+            if let Some((subscript, value)) = word.strip_prefix('[').and_then(|w| w.split_once("]=")) {
+                let subscript = self.expand_word(subscript)?;
+                let value = self.expand_word(value)?;
+                if assoc {
+                    keyed.insert(subscript, value);
+                } else {
+                    let index = self.index(name, &subscript)?;
+                    indexed.insert(index, value);
+                    next = index + 1;
+                }
+                continue;
+            }
+            if assoc {
+                return Err(ShellError::Failed(format!("{}: {}: must use subscript when assigning associative array", name, word)));
+            }
+            for field in self.expand_words(std::slice::from_ref(word))? {
+                indexed.insert(next, field);
+                next += 1;
+            }
+        }
+        Ok(if assoc { Value::Assoc(keyed) } else { Value::Indexed(indexed) })
+    }
+
+    /// `declare [-aAp] [name[=value] ...]`: give variables attributes, or
+    /// print them. Assignments in the arguments have already been expanded,
+    /// except compound ones such as `m=([k]=v)`, which the parser set aside
+    /// and which are assigned once the name has its attributes.
+    pub(crate) fn declare(&mut self, args: &[String], out: &mut dyn Write) -> Result<(), ShellError> {
+        let mut arrays = std::mem::take(&mut self.declare_arrays);
+        let mut kind = None;
+        let mut print = false;
+        let mut functions = None;
+        let mut names = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if names.is_empty() && !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'a' | 'A' => kind = Some(flag),
+                            'p' => print = true,
+                            'f' | 'F' => functions = Some(flag),
+                            _ => return Err(ShellError::Usage(format!("declare: -{}: invalid option", flag))),
+                        }
+                    }
+                }
+                _ => names.push(arg),
+            }
+        }
+        if let Some(flag) = functions {
+            return self.print_functions(&names, flag == 'F', out);
+        }
+        if names.is_empty() && (print || kind.is_none()) {
+            let mut all: Vec<_> = self.vars.iter().filter(|(_, v)| match kind {
+                Some('a') => matches!(v, Value::Indexed(_)),
+                Some('A') => matches!(v, Value::Assoc(_)),
+                _ => true,
+            }).collect();
+            all.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, value) in all {
+                let _ = writeln!(out, "{}", value.declaration(name));
+            }
+            if kind.is_none() {
+                let mut unset: Vec<_> = self.declared.iter().filter(|name| !self.vars.contains_key(*name)).collect();
+                unset.sort();
+                for name in unset {
+                    let _ = writeln!(out, "declare -- {}", name);
+                }
+            }
+            return Ok(());
+        }
+        let mut status = Ok(());
+        for arg in names {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_name(name) {
+                status = Err(ShellError::Failed(format!("declare: `{}': not a valid identifier", arg)));
+                continue;
+            }
+            if print {
+                match self.vars.get(name) {
+                    Some(value) => {
+                        let _ = writeln!(out, "{}", value.declaration(name));
+                    }
+                    None if self.declared.contains(name) => {
+                        let _ = writeln!(out, "declare -- {}", name);
+                    }
+                    None => status = Err(ShellError::Failed(format!("declare: {}: not found", name))),
+                }
+                continue;
+            }
+            if let Err(e) = self.restrict_variable(name) {
+                status = Err(e);
+                continue;
+            }
+            match (kind, self.vars.get(name)) {
+                (Some('A'), Some(Value::Indexed(_))) => {
+                    status = Err(ShellError::Failed(format!("declare: {}: cannot convert indexed to associative array", name)));
+                    continue;
+                }
+                (Some('a'), Some(Value::Assoc(_))) => {
+                    status = Err(ShellError::Failed(format!("declare: {}: cannot convert associative to indexed array", name)));
+                    continue;
+                }
+                _ => {}
+            }
+            // an exported name stays in the environment unless it becomes
+            // an array, which cannot be exported
+            let current = match self.vars.remove(name) {
+                Some(value) => Some(value),
+                None if kind.is_some() => std::env::var_os(name).map(|exported| {
+                    std::env::remove_var(name);
+                    Value::Scalar(exported.to_string_lossy().into_owned())
+                }),
+                None => None,
+            };
+            let converted = match (kind, current) {
+                (Some('a'), Some(Value::Scalar(s))) => Some(Value::Indexed(BTreeMap::from([(0, s)]))),
+                (Some('A'), Some(Value::Scalar(s))) => Some(Value::Assoc(BTreeMap::from([(String::from("0"), s)]))),
+                (Some('a'), None) => Some(Value::Indexed(BTreeMap::new())),
+                (Some('A'), None) => Some(Value::Assoc(BTreeMap::new())),
+                (_, value) => value,
+            };
+            match converted {
+                Some(converted) => {
+                    self.vars.insert(name.to_string(), converted);
+                }
+                None if std::env::var_os(name).is_none() => {
+                    self.declared.insert(name.to_string());
+                }
+                None => {}
+            }
+            if let Some(value) = value {
+                self.set_var(name, value.to_string());
+            }
+            if let Some(i) = arrays.iter().position(|a| a.name == name) {
+                if let Err(e) = self.assign(&arrays.remove(i)) {
+                    status = Err(e);
+                }
+            }
+        }
+        status
+    }
+
+    /// `declare -f`: print the definitions of the named functions, or of
+    /// all of them, or with `-F` just their names.
+    fn print_functions(&self, names: &[&String], names_only: bool, out: &mut dyn Write) -> Result<(), ShellError> {
+        let names: Vec<&String> = match names.is_empty() {
+            true => {
+                let mut all: Vec<_> = self.functions.keys().collect();
+                all.sort();
+                all
+            }
+            false => names.to_vec(),
+        };
+        let mut status = Ok(());
+        for name in names {
+            let Some(function) = self.functions.get(name.as_str()) else {
+                status = Err(ShellError::Failed(format!("declare: {}: not found", name)));
+                continue;
+            };
+            let _ = match names_only {
+                true => writeln!(out, "declare -f {}", name),
+                false => writeln!(out, "{}", Node::Function(Function { name: name.clone(), body: Rc::clone(&function.body) })),
+            };
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Value;
+    use crate::{Shell, ShellError};
+
+    fn declare(shell: &mut Shell, args: &[&str]) -> Result<String, ShellError> {
+        let mut out = Vec::new();
+        shell.declare(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>(), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn compound_assignments_build_indexed_and_associative_arrays() {
+        let mut shell = Shell::new();
+        shell.eval("a=(x y [5]=z w); a[1]=Y; declare -A m; m=([k]=v [two]=2)").unwrap();
+        assert_eq!(shell.vars["a"], Value::Indexed(BTreeMap::from([(0, "x".into()), (1, "Y".into()), (5, "z".into()), (6, "w".into())])));
+        assert_eq!(shell.vars["m"], Value::Assoc(BTreeMap::from([("k".into(), "v".into()), ("two".into(), "2".into())])));
+        assert_eq!(shell.element("a", "-1").as_deref(), Some("w"));
+        assert_eq!(shell.var("a").as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn declare_takes_compound_assignments_as_arguments() {
+        let mut shell = Shell::new();
+        assert_eq!(shell.eval("declare -A m=([a]=1 [b]='x y'); declare -a l=(p q)").unwrap(), 0);
+        assert_eq!(declare(&mut shell, &["-p", "m", "l"]).unwrap(), "declare -A m=([\"a\"]=\"1\" [\"b\"]=\"x y\")\ndeclare -a l=([0]=\"p\" [1]=\"q\")\n");
+    }
+
+    #[test]
+    fn declare_converts_strings_but_not_between_array_kinds() {
+        let mut shell = Shell::new();
+        shell.eval("s=one; declare -A m=([k]=v); declare -a l=(x)").unwrap();
+        declare(&mut shell, &["-a", "s"]).unwrap();
+        assert_eq!(shell.vars["s"], Value::Indexed(BTreeMap::from([(0, "one".into())])));
+        let error = |result: Result<String, ShellError>| result.err().map(|e| e.to_string());
+        assert_eq!(error(declare(&mut shell, &["-a", "m"])).as_deref(), Some("declare: m: cannot convert associative to indexed array"));
+        assert_eq!(error(declare(&mut shell, &["-A", "l"])).as_deref(), Some("declare: l: cannot convert indexed to associative array"));
+        assert!(matches!(shell.vars["m"], Value::Assoc(_)));
+    }
+
+    #[test]
+    fn declare_leaves_exported_and_unset_names_as_they_are() {
+        let mut shell = Shell::new();
+        std::env::set_var("MASH_TEST_DECLARED", "bar");
+        declare(&mut shell, &["MASH_TEST_DECLARED", "fresh"]).unwrap();
+        assert_eq!(shell.var("MASH_TEST_DECLARED").as_deref(), Some("bar"));
+        assert_eq!(std::env::var("MASH_TEST_DECLARED").as_deref(), Ok("bar"));
+        assert_eq!(shell.var("fresh"), None);
+        assert_eq!(declare(&mut shell, &["-p", "fresh"]).unwrap(), "declare -- fresh\n");
+        std::env::remove_var("MASH_TEST_DECLARED");
+    }
+}
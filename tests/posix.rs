@@ -0,0 +1,341 @@
+//! `mash --posix` against expectations taken from the POSIX shell
+//! specification, for the parts of the language mash implements.
+
+use std::{io::Write, process::{Command, Output, Stdio}};
+
+fn sh(script: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mash")).args(["--posix", "-c", script]).env_remove("RUST_LOG").output().expect("run mash")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn compound_commands() {
+    let output = sh("for i in 1 2; do /bin/echo for-$i; done
+        if false; then /bin/echo no; elif true; then /bin/echo elif; fi
+        case abc in a*c) /bin/echo case;; *) /bin/echo default;; esac
+        n=x; while [ $n != xxx ]; do n=${n}x; done; /bin/echo $n");
+    assert_eq!(stdout(&output), "for-1\nfor-2\nelif\ncase\nxxx\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn and_or_lists() {
+    let output = sh("false && /bin/echo no || /bin/echo yes; true || /bin/echo no; /bin/echo $?
+        set -e; false && /bin/echo no; /bin/echo survived; true && false; /bin/echo no");
+    assert_eq!(stdout(&output), "yes\n0\nsurvived\n");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cd_tracks_the_previous_directory_and_searches_cdpath() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path().canonicalize().unwrap();
+    std::fs::create_dir_all(dir.join("projects/mash/src")).unwrap();
+    let output = sh(&format!("cd {0}; cd projects/mash/src/..; /bin/echo $PWD; cd -; /bin/echo $OLDPWD
+        CDPATH=:{0}/projects; cd mash; cd src; cd /nonexistent || /bin/echo $PWD", dir.display()));
+    let dir = dir.display();
+    assert_eq!(stdout(&output), format!("{0}/projects/mash\n{0}\n{0}/projects/mash\n{0}/projects/mash\n{0}/projects/mash/src\n", dir));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cd: /nonexistent"));
+}
+
+#[test]
+fn hash_remembers_programs_until_path_changes() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let program = dir.path().join("prog");
+    std::fs::write(&program, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let output = sh(&format!("PATH={0}; hash; prog; prog; hash; PATH={0}:/nonexistent; hash; hash -t prog; hash -r; hash nosuch; /bin/echo $?", dir.path().display()));
+    let program = program.display();
+    assert_eq!(stdout(&output), format!("hash: hash table empty\nhits\tcommand\n   2\t{0}\nhash: hash table empty\n{0}\n1\n", program));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "hash: nosuch: not found\n");
+}
+
+#[test]
+fn filename_expansion() {
+    let dir = tempfile::tempdir().unwrap();
+    for file in ["a.txt", "b.txt", ".hidden.txt", "sub/c.txt"] {
+        let path = dir.path().join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let output = sh(&format!("cd {}; /bin/echo *.txt; /bin/echo '*.txt' \"*\".txt; /bin/echo */?.txt [!a].txt none*; set -f; /bin/echo *.txt", dir.path().display()));
+    assert_eq!(stdout(&output), "a.txt b.txt\n*.txt *.txt\nsub/c.txt b.txt none*\n*.txt\n");
+}
+
+#[test]
+fn aliases() {
+    let output = sh("alias say='/bin/echo said' echo='echo again'
+        say hi; alias say; unalias say; alias");
+    assert_eq!(stdout(&output), "said hi\nalias say='/bin/echo said'\nalias echo='echo again'\n");
+}
+
+#[test]
+fn scripts_get_their_arguments_and_exit_with_the_last_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("script.sh");
+    std::fs::write(&script, format!("#!{}\n/bin/echo \"$# $1 $2\"\n/bin/sh -c 'exit 4'\n", env!("CARGO_BIN_EXE_mash"))).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_mash")).arg("--posix").arg(&script).args(["a", "b c"]).output().unwrap();
+    assert_eq!(stdout(&output), "2 a b c\n");
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn commands_are_read_from_piped_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mash")).arg("--posix").stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    // the command reading stdin gets the line after it, not the shell
+    child.stdin.take().unwrap().write_all(b"x=1\nfor i in a b\ndo /bin/echo $i$x; done\n/bin/sh -c 'read l; /bin/echo got $l'\ndata\n/bin/echo end\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(stdout(&output), "a1\nb1\ngot data\nend\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn functions_get_their_own_positional_parameters() {
+    let output = sh("f() { /bin/echo \"$1-$#\"; }; set -- x y z; f a b; /bin/echo \"$1-$#\"");
+    assert_eq!(stdout(&output), "a-2\nx-3\n");
+}
+
+#[test]
+fn parameter_expansion_respects_quotes() {
+    let output = sh("x='a  b'; /bin/echo $x \"$x\" '$x' ${x}c \\$x; /bin/false; /bin/echo $? \"${?}\" '$?'");
+    assert_eq!(stdout(&output), "a b a  b $x a bc $x\n1 1 $?\n");
+}
+
+#[test]
+fn unsupported_braced_expansions_are_bad_substitutions() {
+    let output = sh("x=abc; /bin/echo ${x:-d}; /bin/echo $?; /bin/echo \"${x#a}\" $?; /bin/echo ${y:-hello world}; /bin/echo $? ${x} ${#x}");
+    assert_eq!(stdout(&output), "1\n1 abc 3\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "mash: ${x:-d}: bad substitution\nmash: ${x#a}: bad substitution\nmash: ${y:-hello world}: bad substitution\n");
+}
+
+#[test]
+fn unset_variables_are_fatal_with_set_u() {
+    let output = sh("x=set; set -u; /bin/echo $x ${x}; /bin/echo $UNDEFINED; /bin/echo after");
+    assert_eq!(stdout(&output), "set set\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "mash: UNDEFINED: unbound variable\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn pipelines_can_be_negated() {
+    let output = sh("! /bin/true; /bin/echo $?; ! /bin/false | /bin/false; /bin/echo $?; if ! /bin/false; then /bin/echo then; fi
+        set -e; ! /bin/true; /bin/echo survived; ! /bin/false && /bin/echo and");
+    assert_eq!(stdout(&output), "1\n0\nthen\nsurvived\nand\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn dollar_dollar_is_the_shell_process_even_in_subshells() {
+    let output = sh("/bin/echo $$ ${$} \"$$\"; (/bin/echo $$); /bin/echo $(/bin/echo $$)");
+    let pid = output.stdout.split(|&b| b == b' ').next().map(|pid| String::from_utf8_lossy(pid).into_owned()).unwrap();
+    assert!(pid.parse::<u32>().is_ok(), "{}", pid);
+    assert_eq!(stdout(&output), format!("{0} {0} {0}\n{0}\n{0}\n", pid));
+}
+
+#[test]
+fn unquoted_expansions_split_on_ifs() {
+    let output = sh("p() { /usr/bin/printf '[%s]' \"$@\"; /bin/echo; }; x=' a  b:c::d: '
+        p $x; IFS=:; p $x; IFS=' :'; p $x; p a$x; IFS=; p $x; unset IFS; p $x $(/bin/echo '1  2')");
+    assert_eq!(stdout(&output), "[a][b:c::d:]\n[ a  b][c][][d][ ]\n[a][b][c][][d]\n[a][a][b][c][][d]\n[ a  b:c::d: ]\n[a][b:c::d:][1][2]\n");
+}
+
+#[test]
+fn only_exported_variables_reach_commands() {
+    let output = sh("a=1; export b=2; /usr/bin/env | /bin/grep -E '^[ab]='; unset b; /usr/bin/env | /bin/grep -c '^b='");
+    assert_eq!(stdout(&output), "b=2\n0\n");
+}
+
+#[test]
+fn exit_takes_a_status() {
+    assert_eq!(sh("exit 3; /bin/echo reached").status.code(), Some(3));
+    assert_eq!(sh("/bin/false; exit").status.code(), Some(1));
+    let output = sh("/bin/sh -c 'exit 5'; /bin/echo $?");
+    assert_eq!(stdout(&output), "5\n");
+}
+
+#[test]
+fn special_builtin_errors_exit_the_shell() {
+    // XCU 2.8.1: an error in a special builtin causes a non-interactive
+    // shell to exit
+    let output = sh("shift 3; /bin/echo reached");
+    assert_eq!(stdout(&output), "");
+    assert!(!output.status.success());
+
+    let output = sh("set -o nosuchoption; /bin/echo reached");
+    assert_eq!(stdout(&output), "");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn other_errors_do_not_exit_the_shell() {
+    let output = sh("cd /nonexistent/directory; /bin/echo reached");
+    assert_eq!(stdout(&output), "reached\n");
+}
+
+#[test]
+fn programs_that_cannot_run_exit_126_or_127() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    for (name, interpreter) in [("missing", "/nonexistent/interpreter"), ("directory", "/")] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, format!("#!{}\n", interpreter)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let output = sh(&format!("cd {}; ./missing; /bin/echo $?; /bin/true | ./missing; /bin/echo $?; ./directory; /bin/echo $?", dir.path().display()));
+    assert_eq!(stdout(&output), "127\n127\n126\n");
+}
+
+#[test]
+fn dot_reads_a_file_in_the_current_shell() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("lib.sh");
+    std::fs::write(&file, "sourced=yes\n").unwrap();
+    let output = sh(&format!(". {}; /bin/echo $sourced", file.display()));
+    assert_eq!(stdout(&output), "yes\n");
+}
+
+#[test]
+fn extensions_are_unavailable() {
+    let output = sh("source /dev/null");
+    assert_eq!(output.status.code(), Some(127));
+
+    let output = sh("a=(1 2)");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not supported in POSIX mode"));
+
+    let output = sh("/bin/cat <(/bin/echo no)");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("process substitution: not supported in POSIX mode"));
+
+    let output = sh("x=1; /bin/echo ${x[0]}");
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn extensions_work_without_posix_mode() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mash")).args(["-c", "a=(x y); /bin/echo ${a[1]}"]).output().unwrap();
+    assert_eq!(stdout(&output), "y\n");
+}
+
+#[test]
+fn running_as_sh_turns_on_posix_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let sh = dir.path().join("sh");
+    std::os::unix::fs::symlink(env!("CARGO_BIN_EXE_mash"), &sh).unwrap();
+    let output = Command::new(&sh).args(["-c", "declare x=1"]).env_remove("RUST_LOG").output().unwrap();
+    assert_eq!(output.status.code(), Some(127));
+}
+
+#[test]
+fn command_substitution_splices_output_into_words() {
+    let output = sh("x=$(/bin/echo a; /bin/echo; /bin/echo); /bin/echo \"[$x]\" $(/bin/echo $(/bin/echo nested) words) `/bin/echo back`
+        y=$(exit 3); /bin/echo $?
+        /bin/echo \"$(/bin/echo '  kept  ')\"");
+    assert_eq!(stdout(&output), "[a] nested words back\n3\n  kept  \n");
+}
+
+#[test]
+fn here_documents_and_here_strings_feed_standard_input() {
+    let output = sh("x=world
+        /bin/cat <<EOF; /bin/cat <<'EOF'
+hello $x \\$x \"$(/bin/echo sub)\"
+EOF
+literal $x
+EOF
+        /bin/cat <<-END
+\t\tstripped
+\tEND
+        /usr/bin/tr a-z A-Z <<< \"here $x\"
+        /bin/echo $LINENO");
+    assert_eq!(stdout(&output), "hello world $x \"sub\"\nliteral $x\nstripped\nHERE WORLD\n11\n");
+}
+
+#[test]
+fn compound_commands_take_redirections() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = format!("cd {}
+        for i in 1 2; do /bin/echo loop-$i; done >out
+        while /usr/bin/head -n 1; do /bin/echo read; break; done <out
+        if /bin/echo hidden; then /bin/echo also hidden; fi >/dev/null
+        case x in x) /bin/cat;; esac <<EOF
+case input
+EOF", dir.path().display());
+    let output = sh(&script);
+    assert_eq!(stdout(&output), "loop-1\nread\ncase input\n");
+}
+
+#[test]
+fn subshells_keep_changes_to_themselves_and_groups_do_not() {
+    let output = sh("x=1; (x=2; cd /; /bin/echo in $x $PWD) >&2; /bin/echo $x
+        { x=3; /bin/echo grouped; } | /usr/bin/tr a-z A-Z; /bin/echo $x
+        { x=4; } >/dev/null; /bin/echo $x
+        (exit 3); /bin/echo $?
+        ( (/bin/echo nested) && /bin/echo then )");
+    assert_eq!(stdout(&output), "1\nGROUPED\n1\n4\n3\nnested\nthen\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "in 2 /\n");
+}
+
+#[test]
+fn read_splits_lines_into_variables() {
+    let output = sh("/usr/bin/printf 'one two three\\nx\\\\ y\\\\\\nz\\n' | while read first rest; do /bin/echo \"[$first] [$rest]\"; done
+        IFS=: read -r user password <<EOF
+root:x:0
+EOF
+        /bin/echo \"$user $password\"
+        read line </dev/null; /bin/echo $? \"[$line]\"");
+    assert_eq!(stdout(&output), "[one] [two three]\n[x yz] []\nroot x:0\n1 []\n");
+}
+
+#[test]
+fn traps_run_on_signals_and_at_exit() {
+    let output = sh("trap '/bin/echo bye $?' EXIT; trap '/bin/echo got usr1' USR1
+        /bin/sh -c 'kill -USR1 $PPID'; /bin/echo after
+        trap '' SIGINT; /bin/sh -c 'kill -2 $PPID'; /bin/echo still here; false");
+    assert_eq!(stdout(&output), "got usr1\nafter\nstill here\nbye 1\n");
+    assert_eq!(output.status.code(), Some(1));
+    let output = sh("trap 'exit 7' TERM; /bin/sh -c 'kill $PPID'; /bin/echo not reached");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn time_reports_on_standard_error() {
+    let output = sh("time -p /bin/sh -c 'exit 3' | /bin/cat; /bin/echo $?; time /bin/echo timed");
+    assert_eq!(stdout(&output), "0\ntimed\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<_> = stderr.lines().collect();
+    assert_eq!(lines.len(), 7, "{}", stderr);
+    assert!(lines[0].starts_with("real ") && lines[2].starts_with("sys "));
+    assert!(lines[3].is_empty() && lines[4].starts_with("real\t0m0.") && lines[6].starts_with("sys\t0m"));
+}
+
+#[test]
+fn kill_signals_and_wait_collects_background_jobs() {
+    let output = sh("/bin/sleep 5 & p=$!; /bin/sh -c 'exit 3' & q=$!
+        kill -s KILL $p; wait $p; /bin/echo killed $?; /bin/sleep 0.1; wait $q; /bin/echo exited $?
+        /bin/sleep 5 & kill %1; wait %1; /bin/echo terminated $?; kill -l 137 TERM
+        /bin/sleep 0.1 & /bin/sleep 0.2 & wait; /bin/echo all $?; wait 1; /bin/echo $?");
+    assert_eq!(stdout(&output), "killed 137\nexited 3\nterminated 143\nKILL\n15\nall 0\n127\n");
+}
+
+#[test]
+fn arithmetic_expands_to_its_value() {
+    let output = sh("i=0; while [ $i -lt 3 ]; do i=$((i + 1)); done; /bin/echo $i \"$(( (i << 2) % 5 ? 010 : -1 ))\" $(($(/bin/echo 6) / 4)); x=$((1 / 0)); /bin/echo $?");
+    assert_eq!(stdout(&output), "3 8 1\n1\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 / 0: division by zero"));
+}
+
+#[test]
+fn umask_ulimit_and_exec_change_the_shell_process() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = sh(&format!("cd {}; umask 077; umask; /bin/sh -c umask; umask -S; :>file; /usr/bin/stat -c %a file
+        ulimit -S -n 64; ulimit -n; /bin/sh -c 'ulimit -n'
+        exec 3>fd3; /bin/echo three >&3; exec 3>&-; /bin/cat fd3
+        exec /bin/echo replaced; /bin/echo never", dir.path().display()));
+    assert_eq!(stdout(&output), "0077\n0077\nu=rwx,g=,o=\n600\n64\n64\nthree\nreplaced\n");
+    let output = sh("exec /no/such/program; /bin/echo never");
+    assert_eq!(output.status.code(), Some(127));
+    assert_eq!(stdout(&output), "");
+}
@@ -0,0 +1,480 @@
+//! Drive the mash binary through a pseudo-terminal, as a user at a terminal
+//! would, to catch regressions in the interactive shell.
+
+use std::{fs, path::{Path, PathBuf}, process::Command, time::Duration};
+
+use expectrl::{ControlCode, Eof, Expect, Regex, Session, session::OsSession};
+use tempfile::TempDir;
+
+/// A home directory for one test, so history and configuration never touch
+/// the real ones.
+struct Home(TempDir);
+
+impl Home {
+    fn new() -> Self {
+        Home(tempfile::tempdir().expect("create home directory"))
+    }
+
+    fn path(&self) -> PathBuf {
+        self.0.path().canonicalize().expect("canonicalize home directory")
+    }
+
+    /// Start an interactive shell in this home directory.
+    fn spawn(&self) -> OsSession {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_mash"));
+        command
+            .arg("-i")
+            .current_dir(self.path())
+            .env("HOME", self.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("RUST_LOG");
+        let mut session = Session::spawn(command).expect("spawn mash");
+        session.set_expect_timeout(Some(Duration::from_secs(10)));
+        session
+    }
+
+    fn write(&self, file: &str, contents: &str) {
+        let path = self.path().join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+fn prompt(dir: &Path) -> String {
+    format!("{} % ", dir.display())
+}
+
+/// Type a line and press return.
+fn run(session: &mut OsSession, line: &str) {
+    session.send(format!("{}\r", line)).unwrap();
+}
+
+#[test]
+fn prompt_shows_working_directory() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    fs::create_dir(home.path().join("sub")).unwrap();
+    run(&mut shell, "cd sub");
+    shell.expect(prompt(&home.path().join("sub"))).unwrap();
+    run(&mut shell, "exit");
+    shell.expect(Eof).unwrap();
+}
+
+#[test]
+fn runs_commands_and_prompts_again() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo hello");
+    shell.expect("hello").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "exit");
+    shell.expect(Eof).unwrap();
+}
+
+#[test]
+fn continues_incomplete_commands() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "for x in a b");
+    shell.expect("> ").unwrap();
+    run(&mut shell, "do /bin/echo item-$x; done");
+    shell.expect("item-a").unwrap();
+    shell.expect("item-b").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn reporttime_reports_slow_command_lines() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "REPORTTIME=0.2");
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/sleep 0.3");
+    shell.expect(Regex(r"/bin/sleep 0\.3  \d+\.\d\ds user \d+\.\d\ds system \d+% cpu 0\.3\d\d total")).unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn precmd_and_preexec_functions_run_around_commands() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "preexec() { /bin/echo \"about to run [$1]\"; }; precmd() { /bin/echo \"status was $?\"; }");
+    shell.expect("status was 0").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/sh -c 'exit 4'");
+    shell.expect("about to run [/bin/sh -c 'exit 4']").unwrap();
+    shell.expect("status was 4").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo $?");
+    shell.expect("about to run [/bin/echo $?]").unwrap();
+    shell.expect("4\r\n").unwrap();
+}
+
+#[test]
+fn bind_changes_keys_from_the_next_prompt() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "bind ctrl-t kill-whole-line");
+    shell.expect(prompt(&home.path())).unwrap();
+    shell.send("garbage").unwrap();
+    shell.send(ControlCode::DC4).unwrap();
+    run(&mut shell, "/bin/echo bound | /usr/bin/tr a-z A-Z");
+    shell.expect("BOUND\r\n").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "bind; bind alt-q no-such-command");
+    shell.expect("bind ctrl-t kill-whole-line").unwrap();
+    shell.expect("mash: bind: no-such-command: unknown editor command").unwrap();
+}
+
+#[test]
+fn typed_commands_are_coloured_and_suggested_from_history() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo suggested");
+    shell.expect("133;C\x07suggested\r\n").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    shell.send("no-such-command-anywhere; /bin/echo s").unwrap();
+    shell.expect("\x1b[31mno-such-command-anywhere\x1b[0m; \x1b[32m/bin/echo\x1b[0m s").unwrap();
+    shell.send("\x15/bin/echo s").unwrap();
+    shell.expect("\x1b[32m/bin/echo\x1b[0m s\x1b[2muggested\x1b[0m").unwrap();
+    shell.send("\x1b[C\r").unwrap();
+    shell.expect("133;C\x07suggested\r\n").unwrap();
+}
+
+#[test]
+fn correct_offers_the_closest_command_and_directory() {
+    let home = Home::new();
+    home.write(".mashrc", "CORRECT=1\n");
+    fs::create_dir(home.path().join("target")).unwrap();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "ehco fixed");
+    shell.expect("mash: did you mean 'echo'? [y/N] ").unwrap();
+    run(&mut shell, "y");
+    shell.expect("fixed\r\n").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "ehco kept");
+    shell.expect("[y/N] ").unwrap();
+    run(&mut shell, "n");
+    shell.expect("mash: ehco: command not found").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "cd tagret");
+    shell.expect("mash: did you mean 'target'? [y/N] ").unwrap();
+    run(&mut shell, "y");
+    shell.expect(prompt(&home.path().join("target"))).unwrap();
+}
+
+#[test]
+fn ps2_prompts_for_the_rest_of_a_line() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "PS2='more> '");
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo one |");
+    shell.expect("more> ").unwrap();
+    run(&mut shell, "/usr/bin/tr a-z A-Z &&");
+    shell.expect("more> ").unwrap();
+    run(&mut shell, "/bin/echo 'two");
+    shell.expect("more> ").unwrap();
+    run(&mut shell, "three' \\");
+    shell.expect("more> ").unwrap();
+    run(&mut shell, "four");
+    shell.expect("ONE").unwrap();
+    shell.expect("two\r\nthree four").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn here_documents_read_continuation_lines() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/usr/bin/tr a-z A-Z <<END");
+    shell.expect("> ").unwrap();
+    run(&mut shell, "first line");
+    shell.expect("> ").unwrap();
+    run(&mut shell, "END");
+    shell.expect("FIRST LINE").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn read_prompts_and_can_hide_input() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "read -s -p 'password: ' secret; /bin/echo \"got ${#secret}\"");
+    shell.expect("password: ").unwrap();
+    run(&mut shell, "hunter2");
+    shell.expect("got 7").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn tab_without_completions_keeps_the_line() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    shell.send("/bin/echo ab\tc\r").unwrap();
+    shell.expect("abc").unwrap();
+}
+
+#[test]
+fn interrupt_discards_the_line() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    shell.send("/bin/echo never").unwrap();
+    shell.send(ControlCode::EndOfText).unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo after");
+    shell.expect("after").unwrap();
+}
+
+#[test]
+fn interrupt_stops_a_running_loop() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "while true; do /bin/sleep 0.1; done");
+    std::thread::sleep(Duration::from_millis(500));
+    shell.send(ControlCode::EndOfText).unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo still-here");
+    shell.expect("still-here").unwrap();
+}
+
+#[test]
+fn quit_only_reaches_the_running_command() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    shell.send(ControlCode::FileSeparator).unwrap();
+    run(&mut shell, "/bin/sleep 5");
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send(ControlCode::FileSeparator).unwrap();
+    shell.expect("Quit").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo status-$?");
+    shell.expect("status-131").unwrap();
+}
+
+#[test]
+fn only_commands_killed_by_a_signal_are_reported() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/sh -c 'exit 137'; /bin/echo status-$?");
+    let output = shell.expect("status-137").unwrap();
+    assert!(!String::from_utf8_lossy(output.before()).contains("Killed"));
+    run(&mut shell, "/bin/sh -c 'kill -KILL $$'");
+    shell.expect("Killed").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn stopped_command_can_be_resumed() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/sleep 1");
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send(ControlCode::Substitute).unwrap();
+    shell.expect("Stopped").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "jobs");
+    shell.expect("[1]+  Stopped").unwrap();
+    run(&mut shell, "fg %1");
+    shell.expect("/bin/sleep 1").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo after");
+    shell.expect("after").unwrap();
+}
+
+#[test]
+fn history_is_kept_between_sessions() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo remembered");
+    shell.expect("remembered").unwrap();
+    run(&mut shell, "exit");
+    shell.expect(Eof).unwrap();
+
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    // up arrow twice: past `exit` to the echo
+    shell.send("\x1b[A\x1b[A\r").unwrap();
+    shell.expect("remembered").unwrap();
+}
+
+#[test]
+fn concurrent_sessions_share_history() {
+    let home = Home::new();
+    let mut first = home.spawn();
+    first.expect(prompt(&home.path())).unwrap();
+    let mut second = home.spawn();
+    second.expect(prompt(&home.path())).unwrap();
+    run(&mut first, "/bin/echo from first");
+    first.expect("133;C\x07from first\r\n").unwrap();
+    first.expect(prompt(&home.path())).unwrap();
+    run(&mut second, "/bin/echo from second");
+    second.expect("133;C\x07from second\r\n").unwrap();
+    second.expect(prompt(&home.path())).unwrap();
+    run(&mut second, "history -m; history");
+    second.expect("    2  history -m; history\r\n").unwrap();
+    second.expect("    3  /bin/echo from first\r\n").unwrap();
+    run(&mut first, "exit");
+    first.expect(Eof).unwrap();
+    run(&mut second, "exit");
+    second.expect(Eof).unwrap();
+    let history = fs::read_to_string(home.path().join(".local/state/mash/history")).unwrap();
+    let entries: Vec<_> = history.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(entries, ["/bin/echo from first", "/bin/echo from second", "history -m; history", "exit", "exit"]);
+}
+
+#[test]
+fn history_variables_limit_what_is_kept() {
+    let home = Home::new();
+    home.write(".mashrc", "HISTSIZE=2\nHISTCONTROL=ignoreboth\n");
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    for line in [" /bin/echo secret", "/bin/echo one", "/bin/echo two", "/bin/echo two"] {
+        run(&mut shell, line);
+        // the output after the command's mark, not the line redrawn as it
+        // is typed
+        shell.expect(format!("133;C\x07{}\r\n", line.rsplit(' ').next().unwrap())).unwrap();
+        shell.expect(prompt(&home.path())).unwrap();
+    }
+    run(&mut shell, "exit");
+    shell.expect(Eof).unwrap();
+    let history = fs::read_to_string(home.path().join(".local/state/mash/history")).unwrap();
+    let entries: Vec<_> = history.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(entries, ["/bin/echo two", "exit"]);
+}
+
+#[test]
+fn unusable_history_file_is_not_fatal() {
+    let home = Home::new();
+    fs::create_dir_all(home.path().join(".local/state/mash/history")).unwrap();
+    let mut shell = home.spawn();
+    shell.expect("mash: history: ").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo still running");
+    shell.expect("still running").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "exit");
+    shell.expect(Eof).unwrap();
+}
+
+#[test]
+fn mistyped_commands_get_a_suggestion() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "ecoh hi; /bin/echo status $?");
+    shell.expect("mash: ecoh: command not found").unwrap();
+    shell.expect("mash: did you mean 'echo'?").unwrap();
+    shell.expect("status 127").unwrap();
+}
+
+#[test]
+fn bang_events_rerun_history_entries() {
+    let home = Home::new();
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "/bin/echo first");
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "!! again");
+    shell.expect("/bin/echo first again").unwrap();
+    shell.expect("first again").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "history");
+    shell.expect("    1  /bin/echo first").unwrap();
+    shell.expect("    2  /bin/echo first again").unwrap();
+    shell.expect("    3  history").unwrap();
+}
+
+#[test]
+fn directory_stack_rotates() {
+    let home = Home::new();
+    for dir in ["a", "b"] {
+        fs::create_dir(home.path().join(dir)).unwrap();
+    }
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "pushd a");
+    shell.expect("~/a ~\r\n").unwrap();
+    run(&mut shell, "pushd ../b");
+    shell.expect("~/b ~/a ~\r\n").unwrap();
+    run(&mut shell, "pushd +2");
+    shell.expect("~ ~/b ~/a\r\n").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "popd +1");
+    shell.expect("~ ~/a\r\n").unwrap();
+    run(&mut shell, "popd");
+    shell.expect("~/a\r\n").unwrap();
+    shell.expect(prompt(&home.path().join("a"))).unwrap();
+    run(&mut shell, "popd");
+    shell.expect("popd: directory stack empty").unwrap();
+}
+
+#[test]
+fn config_sets_prompt_and_aliases() {
+    let home = Home::new();
+    home.write(".config/mash/config.toml", "[prompt]\nsymbol = \"$\"\n\n[aliases]\ngreet = \"/bin/echo hi from alias\"\n");
+    let mut shell = home.spawn();
+    shell.expect(format!("{} $ ", home.path().display())).unwrap();
+    run(&mut shell, "greet");
+    shell.expect("hi from alias").unwrap();
+}
+
+#[test]
+fn hook_programs_add_prompt_segments_and_completions() {
+    use std::os::unix::fs::PermissionsExt;
+    let home = Home::new();
+    home.write(".mash/plugins/ctx.hook", "#!/bin/sh
+case $1 in
+prompt) printf '{\"segment\": \"[%s]\"}' \"${MASH_CWD##*/}\" ;;
+complete) case $MASH_LINE in '/bin/echo ku'*) echo '{\"completions\": [\"kubectl-ctx \"]}' ;; esac ;;
+esac
+");
+    fs::set_permissions(home.path().join(".mash/plugins/ctx.hook"), fs::Permissions::from_mode(0o755)).unwrap();
+    let mut shell = home.spawn();
+    let dir = home.path();
+    shell.expect(format!("[{}] {}", dir.file_name().unwrap().to_string_lossy(), prompt(&dir))).unwrap();
+    shell.send("/bin/echo ku\t\r").unwrap();
+    shell.expect("133;C\x07kubectl-ctx\r\n").unwrap();
+    run(&mut shell, "plugin list");
+    shell.expect("ctx\thook").unwrap();
+}
+
+#[test]
+fn rc_file_runs_before_the_first_prompt() {
+    let home = Home::new();
+    home.write(".mashrc", "/bin/echo rc-ran\n");
+    let mut shell = home.spawn();
+    shell.expect("rc-ran").unwrap();
+    shell.expect(prompt(&home.path())).unwrap();
+}
+
+#[test]
+fn rc_file_settings_last_the_session() {
+    let home = Home::new();
+    home.write(".mashrc", "alias greet='/bin/echo hello from rc'\nnosuchcommand\nexport FROM_RC=yes\n");
+    let mut shell = home.spawn();
+    shell.expect(prompt(&home.path())).unwrap();
+    run(&mut shell, "greet");
+    shell.expect("hello from rc").unwrap();
+    run(&mut shell, "/usr/bin/env | /bin/grep FROM_RC");
+    shell.expect("FROM_RC=yes").unwrap();
+}
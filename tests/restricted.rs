@@ -0,0 +1,84 @@
+//! `mash --restricted`, checking that each thing a restricted shell refuses
+//! is refused and that the rest still works.
+
+use std::{fs, path::Path, process::{Command, Output}};
+
+/// Run `script` in a restricted shell started in `dir`, which is also its
+/// home directory.
+fn rsh(dir: &Path, args: &[&str], script: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mash"))
+        .arg("--restricted")
+        .args(args)
+        .args(["-c", script])
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("PATH", "/usr/bin:/bin")
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("run mash")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn cd_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    let output = rsh(dir.path(), &[], "cd sub; echo $?; pushd sub; pwd");
+    assert_eq!(stdout(&output), format!("1\n{}\n", dir.path().display()));
+    assert_eq!(stderr(&output), "mash: cd: restricted\nmash: cd: restricted\n");
+}
+
+#[test]
+fn path_env_and_shell_cannot_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = rsh(dir.path(), &[], "PATH=/tmp; export ENV=x; unset SHELL; SHELL=x echo run; declare PATH=y; read PATH <<<z; for PATH in w; do :; done; echo $PATH");
+    assert_eq!(stdout(&output), "/usr/bin:/bin\n");
+    assert_eq!(stderr(&output).lines().collect::<Vec<_>>(), ["mash: PATH: restricted", "mash: ENV: restricted", "mash: SHELL: restricted", "mash: SHELL: restricted", "mash: PATH: restricted", "mash: PATH: restricted", "mash: PATH: restricted"]);
+    let output = rsh(dir.path(), &[], "x=1; export y=2; echo $x $y");
+    assert_eq!(stdout(&output), "1 2\n");
+}
+
+#[test]
+fn output_cannot_be_redirected_to_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = rsh(dir.path(), &[], "echo one >out; echo $?; echo two >>out; exec 3>out; echo three 2>&1; cat <<<four");
+    assert_eq!(stdout(&output), "1\nthree\nfour\n");
+    assert_eq!(stderr(&output), "mash: out: restricted\nmash: out: restricted\nmash: out: restricted\n");
+    assert!(!dir.path().join("out").exists());
+}
+
+#[test]
+fn commands_cannot_be_named_with_a_slash() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("script"), "echo sourced\n").unwrap();
+    let output = rsh(dir.path(), &[], "/bin/echo direct; command /bin/echo command; exec /bin/echo exec; . ./script; . script; echo found | tr a-z A-Z");
+    assert_eq!(stdout(&output), "sourced\nFOUND\n");
+    assert_eq!(stderr(&output), "mash: /bin/echo: restricted\nmash: /bin/echo: restricted\nmash: exec: restricted\nmash: ./script: restricted\n");
+}
+
+#[test]
+fn restriction_cannot_be_lifted() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = rsh(dir.path(), &[], "set +r; set +o restricted; cd /; pwd");
+    assert_eq!(stdout(&output), format!("{}\n", dir.path().display()));
+    assert_eq!(stderr(&output), "mash: set +r: restricted\nmash: set +r: restricted\nmash: cd: restricted\n");
+}
+
+#[test]
+fn startup_files_run_before_the_restriction() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("bin")).unwrap();
+    fs::write(dir.path().join(".mash_profile"), format!("PATH={}/bin:$PATH; cd bin\n", dir.path().display())).unwrap();
+    let output = rsh(dir.path(), &["-l"], "echo $PATH; pwd; PATH=/tmp");
+    // /etc/profile may set its own PATH first, and complain about what it uses
+    assert!(stdout(&output).starts_with(&format!("{}/bin:", dir.path().display())), "{}", stdout(&output));
+    assert!(stdout(&output).ends_with(&format!("\n{}/bin\n", dir.path().display())), "{}", stdout(&output));
+    assert_eq!(stderr(&output).lines().last(), Some("mash: PATH: restricted"));
+}
@@ -0,0 +1,207 @@
+//! Run commands through the library's [`Shell`] API, as a program embedding
+//! mash would, against a temporary directory.
+
+use std::{cell::RefCell, fs, rc::Rc};
+
+use mash::{Builtin, Io, ParseError, Shell, ShellError};
+
+/// A builtin that records the arguments of each call, to see what words a
+/// command line expanded to.
+struct Record(Rc<RefCell<Vec<Vec<String>>>>);
+
+impl Builtin for Record {
+    fn name(&self) -> &'static str {
+        "record"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "record [arg ...]"
+    }
+
+    fn run(&self, _: &mut Shell, args: &[String], _: &mut Io) -> Result<i32, ShellError> {
+        self.0.borrow_mut().push(args.to_vec());
+        Ok(0)
+    }
+}
+
+/// A builtin that writes its arguments to stdout and fails when given none.
+struct Say;
+
+impl Builtin for Say {
+    fn name(&self) -> &'static str {
+        "say"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "say word ..."
+    }
+
+    fn run(&self, _: &mut Shell, args: &[String], io: &mut Io) -> Result<i32, ShellError> {
+        if args.is_empty() {
+            return Err(ShellError::Usage(String::from("say: nothing to say")));
+        }
+        writeln!(io.stdout, "{}", args.join(" ")).map_err(|e| ShellError::Io { context: String::from("say"), source: e })?;
+        Ok(3)
+    }
+}
+
+fn recording_shell() -> (Shell, Rc<RefCell<Vec<Vec<String>>>>) {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let mut shell = Shell::new();
+    shell.register_builtin(Record(Rc::clone(&calls)));
+    (shell, calls)
+}
+
+#[test]
+fn commands_write_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut shell = Shell::new();
+    let out = dir.path().join("out");
+    shell.eval_line(&format!("for word in one two; do /bin/echo $word >>{}; done", out.display())).unwrap();
+    assert_eq!(shell.last_status(), 0);
+    assert_eq!(fs::read_to_string(&out).unwrap(), "one\ntwo\n");
+}
+
+#[test]
+fn words_are_expanded_before_running() {
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line("greeting='hello world'; record $greeting \"$greeting\" pre${greeting}").unwrap();
+    assert_eq!(*calls.borrow(), [vec!["hello", "world", "hello world", "prehello", "world"]]);
+}
+
+#[test]
+fn for_loops_bind_each_expanded_word() {
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line("items='a b'; for item in $items \"c d\" ${items}x; do record \"$item\" ${#item}; done; record \"$item\"").unwrap();
+    assert_eq!(*calls.borrow(), [vec!["a", "1"], vec!["b", "1"], vec!["c d", "3"], vec!["a", "1"], vec!["bx", "2"], vec!["bx"]]);
+}
+
+#[test]
+fn statuses_are_kept_between_lines() {
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line("/bin/sh -c 'exit 3'").unwrap();
+    assert_eq!(shell.last_status(), 3);
+    shell.eval_line("record $?").unwrap();
+    assert_eq!(*calls.borrow(), [vec!["3"]]);
+    shell.eval_line("no-such-command-anywhere").unwrap();
+    assert_eq!(shell.last_status(), 127);
+}
+
+#[test]
+fn registered_builtins_are_redirected_and_set_the_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let mut shell = Shell::new();
+    shell.register_builtin(Say);
+    shell.eval_line(&format!("say hello there >{}", out.display())).unwrap();
+    assert_eq!(shell.last_status(), 3);
+    assert_eq!(fs::read_to_string(&out).unwrap(), "hello there\n");
+    shell.eval_line("say").unwrap();
+    assert_eq!(shell.last_status(), 2);
+}
+
+#[test]
+fn functions_take_arguments_and_can_be_listed_and_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line("greet() { record hi $1 $#; }; greet bob x").unwrap();
+    assert_eq!(*calls.borrow(), [vec!["hi", "bob", "2"]]);
+    shell.eval_line(&format!("declare -f greet >{0}; declare -F >>{0}", out.display())).unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "greet() { record hi $1 $#; }\ndeclare -f greet\n");
+    shell.eval_line("unset -f greet; greet").unwrap();
+    assert_eq!(shell.last_status(), 127);
+}
+
+#[test]
+fn type_which_and_command_look_names_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line("alias ll='ls -l'; cat() { record function; }").unwrap();
+    shell.eval_line(&format!("type ll if cat cd >{0}; type -t cat >>{0}; which /bin/sh >>{0}; command -v ll >>{0}", out.display())).unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "ll is aliased to `ls -l'\nif is a shell keyword\ncat is a function\ncd is a shell builtin\nfunction\n/bin/sh\nalias ll='ls -l'\n");
+    shell.eval_line(&format!("command cat {} >/dev/null; cat", out.display())).unwrap();
+    assert_eq!(*calls.borrow(), [vec!["function"]]);
+    shell.eval_line("type no-such-command-anywhere").unwrap();
+    assert_eq!(shell.last_status(), 1);
+}
+
+#[test]
+fn disowned_jobs_leave_the_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let mut shell = Shell::new();
+    shell.eval_line(&format!("/bin/sleep 5 & p=$!; /bin/sleep 5 & disown %1; jobs >{0}; disown; jobs >>{0}; disown %1", out.display())).unwrap();
+    assert_eq!(shell.last_status(), 1);
+    let jobs = fs::read_to_string(&out).unwrap();
+    assert!(jobs.starts_with("[2]+  Running") && jobs.lines().count() == 1, "{}", jobs);
+    shell.eval_line("kill $p $!").unwrap();
+}
+
+#[test]
+fn disowned_jobs_are_reaped_once_they_finish() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let mut shell = Shell::new();
+    shell.eval_line(&format!("/bin/sleep 0.1 & p=$!; disown; /bin/sleep 0.1 & q=$!; disown -a; /bin/sleep 0.5; /bin/ps -o stat= -p $p,$q >{}", out.display())).unwrap();
+    // neither is left as a zombie, so ps finds no such process
+    assert_eq!(fs::read_to_string(&out).unwrap(), "");
+    assert_eq!(shell.last_status(), 1);
+}
+
+#[test]
+fn process_substitutions_are_paths_to_pipes() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line(&format!("/usr/bin/diff <(printf 'b\\na\\n' | /usr/bin/sort) <(/bin/echo a) >{}", out.display())).unwrap();
+    assert_eq!(shell.last_status(), 1);
+    assert_eq!(fs::read_to_string(&out).unwrap(), "2d1\n< b\n");
+    shell.eval_line(&format!("/bin/echo upper >>>(/usr/bin/tr a-z A-Z >{})", out.display())).unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "UPPER\n");
+    shell.eval_line("while read line; do record $line; done < <(/bin/echo one; /bin/echo two); record <(:)").unwrap();
+    let calls = calls.borrow();
+    assert_eq!(calls[..2], [vec!["one"], vec!["two"]]);
+    assert!(calls[2][0].starts_with("/dev/fd/"), "{:?}", calls);
+}
+
+#[test]
+fn arithmetic_commands_succeed_unless_zero() {
+    let (mut shell, calls) = recording_shell();
+    shell.eval_line("i=0; while (( i < 3 )); do record $((i++)); done; ((i == 3)) && record $i; ((0))").unwrap();
+    assert_eq!(*calls.borrow(), [vec!["0"], vec!["1"], vec!["2"], vec!["3"]]);
+    assert_eq!(shell.last_status(), 1);
+}
+
+#[test]
+fn eval_returns_the_status_of_the_last_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut shell = Shell::new();
+    assert_eq!(shell.eval(&format!("cd {}; /bin/echo hi >out", dir.path().display())), Ok(0));
+    assert_eq!(fs::read_to_string(dir.path().join("out")).unwrap(), "hi\n");
+    assert_eq!(shell.eval("/bin/sh -c 'exit 5'\n"), Ok(5));
+    assert_eq!(shell.eval("if true; then\n/bin/echo one\nfi; false"), Ok(1));
+    assert_eq!(shell.eval("while"), Err(ParseError::Incomplete));
+}
+
+#[test]
+fn shells_keep_their_own_variables_and_functions() {
+    let (mut first, calls) = recording_shell();
+    let mut second = Shell::new();
+    second.register_builtin(Record(Rc::clone(&calls)));
+    first.eval("only_here=1; greet() { record hi; }").unwrap();
+    assert_eq!(second.eval("record \"$only_here\"; greet").unwrap(), 127);
+    assert_eq!(first.eval("greet; record $only_here").unwrap(), 0);
+    assert_eq!(*calls.borrow(), [vec![""], vec!["hi"], vec!["1"]]);
+}
+
+#[test]
+fn syntax_errors_are_returned() {
+    let mut shell = Shell::new();
+    assert_eq!(shell.eval_line("if true; then"), Err(ParseError::Incomplete));
+    assert!(shell.eval_line("echo )").is_err());
+    shell.eval_line("exit 4").unwrap();
+    assert!(shell.exiting());
+    assert_eq!(shell.last_status(), 4);
+}